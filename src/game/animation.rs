@@ -0,0 +1,154 @@
+use game::Animation;
+use game::AnimationFrame;
+use game::AnimationState;
+use game::PlaybackMode;
+use FPS;
+
+pub const FRAME_TIME: u32 = 1000 / FPS as u32;
+
+impl Animation {
+    pub fn new(id: String, texture_id: String, mode: PlaybackMode, frames: Vec<AnimationFrame>) -> Animation {
+        Animation {
+            id,
+            texture_id,
+            mode,
+            frames,
+        }
+    }
+
+    pub fn total_duration(&self) -> u32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    pub fn frame_at(&self, elapsed: u32) -> &AnimationFrame {
+        let index = self.frame_index(elapsed);
+        &self.frames[index]
+    }
+
+    fn frame_index(&self, elapsed: u32) -> usize {
+        let count = self.frames.len();
+        let total = self.total_duration();
+        if count <= 1 || total == 0 {
+            return 0;
+        }
+
+        match self.mode {
+            PlaybackMode::Loop => self.index_within(elapsed % total, 0..count),
+            PlaybackMode::Once if elapsed >= total => count - 1,
+            PlaybackMode::Once => self.index_within(elapsed, 0..count),
+            PlaybackMode::PingPong => {
+                // forward pass plays every frame, backward pass skips both ends so they are not shown twice
+                let backward = self.frames[1..count - 1].iter().map(|frame| frame.duration).sum::<u32>();
+                let elapsed = elapsed % (total + backward);
+                if elapsed < total {
+                    self.index_within(elapsed, 0..count)
+                } else {
+                    self.index_within(elapsed - total, (1..count - 1).rev())
+                }
+            }
+        }
+    }
+
+    fn index_within<T: Iterator<Item = usize>>(&self, elapsed: u32, indices: T) -> usize {
+        let mut remaining = elapsed;
+        let mut last = 0;
+        for index in indices {
+            let duration = self.frames[index].duration;
+            if remaining < duration {
+                return index;
+            }
+            remaining -= duration;
+            last = index;
+        }
+        last
+    }
+}
+
+impl AnimationFrame {
+    pub fn new(x: i32, y: i32, width: u32, height: u32, duration: u32) -> AnimationFrame {
+        AnimationFrame {
+            x,
+            y,
+            width,
+            height,
+            duration,
+        }
+    }
+}
+
+impl AnimationState {
+    pub fn new(clip: &str) -> AnimationState {
+        AnimationState {
+            clip: clip.to_string(),
+            elapsed: 0,
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.elapsed += FRAME_TIME;
+    }
+
+    pub fn elapsed(&self) -> u32 {
+        self.elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::Animation;
+    use game::AnimationFrame;
+    use game::PlaybackMode;
+
+    #[test]
+    fn test_loop() {
+        //given
+        let animation = create_fake_animation(PlaybackMode::Loop, 3);
+
+        //when
+        let frames: Vec<i32> = [0, 99, 100, 250, 300, 420].iter().map(|t| animation.frame_at(*t).x).collect();
+
+        //then
+        assert_eq!(frames, vec![0, 0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_once() {
+        //given
+        let animation = create_fake_animation(PlaybackMode::Once, 3);
+
+        //when
+        let frames: Vec<i32> = [0, 150, 299, 300, 1000].iter().map(|t| animation.frame_at(*t).x).collect();
+
+        //then
+        assert_eq!(frames, vec![0, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_ping_pong() {
+        //given
+        let animation = create_fake_animation(PlaybackMode::PingPong, 4);
+
+        //when
+        let frames: Vec<i32> = (0..8).map(|t| animation.frame_at(t * 100).x).collect();
+
+        //then
+        assert_eq!(frames, vec![0, 1, 2, 3, 2, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_single_frame() {
+        //given
+        let animation = create_fake_animation(PlaybackMode::PingPong, 1);
+
+        //when
+        let frame = animation.frame_at(12345);
+
+        //then
+        assert_eq!(frame.x, 0);
+    }
+
+    fn create_fake_animation(mode: PlaybackMode, count: i32) -> Animation {
+        let frames = (0..count).map(|i| AnimationFrame::new(i, 0, 10, 10, 100)).collect();
+        Animation::new(String::from("fake"), String::from("fake"), mode, frames)
+    }
+}
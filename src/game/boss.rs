@@ -0,0 +1,252 @@
+use game::AnimationState;
+use game::BossPhase;
+use game::BossState;
+use game::CollisionState;
+use game::GameObject;
+use game::Id;
+use game::Level;
+use game::Position;
+use game::Rect;
+use game::Renderer;
+use game::Velocity;
+use game::World;
+use game::states;
+use SCREEN_SIZE;
+use std::cmp::Reverse;
+use std::num::ParseFloatError;
+use std::num::ParseIntError;
+
+const DEFAULT_BOSS_HEALTH: u32 = 100;
+const DEFAULT_BOSS_SCORE: u32 = 5000;
+const DEFAULT_BOSS_SPEED: f32 = 1.0;
+
+impl BossState {
+    pub fn new(id: Id, position: Position, width: u32, height: u32) -> BossState {
+        BossState {
+            id,
+            position,
+            velocity: Velocity::new(DEFAULT_BOSS_SPEED, 0.0),
+            health: DEFAULT_BOSS_HEALTH,
+            max_health: DEFAULT_BOSS_HEALTH,
+            is_destroyed: false,
+            width,
+            height,
+            parts: vec![Rect::new(0.0, 0.0, width as f32, height as f32)],
+            phases: vec![BossPhase { threshold: 100, speed: DEFAULT_BOSS_SPEED, pattern: None }],
+            phase: 0,
+            animation: AnimationState::new("boss"),
+            score: DEFAULT_BOSS_SCORE,
+            pattern_ticks: 0,
+        }
+    }
+
+    // Hitboxes as "x,y,width,height" relative to the boss, separated by ';'
+    pub fn set_parts(&mut self, parts: &str) -> Result<(), String> {
+        self.parts = parts
+            .split(';')
+            .map(|part| {
+                let values = part
+                    .split(',')
+                    .map(|value| value.trim().parse::<f32>().map_err(|e| e.to_string()))
+                    .collect::<Result<Vec<f32>, String>>()?;
+                match values.as_slice() {
+                    [x, y, width, height] => Ok(Rect::new(*x, *y, *width, *height)),
+                    _ => Err(format!("Boss part needs x,y,width,height: {:?}", part)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    pub fn set_phases(&mut self, mut phases: Vec<BossPhase>) {
+        phases.sort_by_key(|phase| Reverse(phase.threshold));
+        self.phases = phases;
+        self.enter_phase(0);
+    }
+
+    pub fn draw(&mut self, renderer: &mut dyn Renderer, level: &Level) {
+        renderer.draw_frame(&self.animation, self.position, level);
+    }
+
+    // Does nothing until the view reaches it, from then on the level waits for the fight to end
+    pub fn update(&mut self, world: &mut World) -> Vec<GameObject> {
+        self.animation.update();
+        if !self.is_engaged(world.level) {
+            return Vec::new();
+        }
+
+        let speed = self.phases[self.phase].speed;
+        if self.position.x <= 0.0 {
+            self.velocity.x = speed;
+        } else if self.position.x + self.width as f32 >= SCREEN_SIZE.0 as f32 {
+            self.velocity.x = -speed;
+        }
+        self.position += self.velocity;
+
+        self.fire(world)
+    }
+
+    pub fn is_engaged(&self, level: &Level) -> bool {
+        !self.is_destroyed && self.position.y >= level.position.y
+    }
+
+    pub fn damage(&mut self, amount: u32) {
+        self.health = self.health.saturating_sub(amount);
+        if self.health == 0 {
+            self.is_destroyed = true;
+            return;
+        }
+
+        let health = self.health * 100;
+        let max_health = self.max_health;
+        let phase = self
+            .phases
+            .iter()
+            .rposition(|phase| health <= phase.threshold * max_health)
+            .unwrap_or(0);
+        if phase > self.phase {
+            self.enter_phase(phase);
+        }
+    }
+
+    fn enter_phase(&mut self, phase: usize) {
+        self.phase = phase;
+        self.pattern_ticks = 0;
+        let direction = if self.velocity.x < 0.0 { -1.0 } else { 1.0 };
+        self.velocity.x = direction * self.phases[phase].speed;
+    }
+
+    // Bosses never run out of bullets, a pattern that does not loop starts over once it ends
+    fn fire(&mut self, world: &mut World) -> Vec<GameObject> {
+        let patterns = world.patterns;
+        let pattern = match self.phases[self.phase].pattern.as_ref().and_then(|pattern| patterns.get(pattern)) {
+            Some(pattern) => pattern,
+            None => return Vec::new(),
+        };
+
+        let center = self.position + Velocity::new(self.width as f32 / 2.0, self.height as f32 / 2.0);
+        let bullets = states::fire_pattern(pattern, self.pattern_ticks, center, world, self.id);
+        self.pattern_ticks = pattern.next_tick(self.pattern_ticks).unwrap_or(0);
+        bullets
+    }
+}
+
+impl BossPhase {
+    // "threshold:speed:pattern", the pattern is optional
+    pub fn parse(text: &str) -> Result<BossPhase, String> {
+        let mut fields = text.split(':').map(str::trim);
+        let threshold = fields
+            .next()
+            .ok_or_else(|| format!("Missing boss phase threshold: {:?}", text))?
+            .parse()
+            .map_err(|e: ParseIntError| e.to_string())?;
+        let speed = fields
+            .next()
+            .ok_or_else(|| format!("Missing boss phase speed: {:?}", text))?
+            .parse()
+            .map_err(|e: ParseFloatError| e.to_string())?;
+        let pattern = fields.next().filter(|pattern| !pattern.is_empty()).map(String::from);
+
+        Ok(BossPhase { threshold, speed, pattern })
+    }
+}
+
+impl CollisionState for BossState {
+    fn position(&self) -> Position { self.position }
+    fn size(&self) -> (u32, u32) { (self.width, self.height) }
+    fn collision_padding(&self) -> (u32, u32) { (0, 0) }
+
+    fn collision_rects(&self) -> Vec<Rect> {
+        self.parts
+            .iter()
+            .map(|part| Rect::new(self.position.x + part.x, self.position.y + part.y, part.width, part.height))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::BossPhase;
+    use game::BossState;
+    use game::CollisionState;
+    use game::Level;
+    use game::Position;
+    use game::Rng;
+    use game::World;
+    use std::collections::HashMap;
+
+    fn create_boss() -> BossState {
+        let mut boss = BossState::new(1, Position::new(100.0, 50.0), 200, 100);
+        boss.set_phases(vec![
+            BossPhase::parse("30:3:ring").unwrap(),
+            BossPhase::parse("100:1").unwrap(),
+            BossPhase::parse("60:2:spiral").unwrap(),
+        ]);
+        boss
+    }
+
+    #[test]
+    fn test_parsing_phases() {
+        //when
+        let phase = BossPhase::parse("60:2.5:spiral");
+
+        //then
+        assert_eq!(phase, Ok(BossPhase { threshold: 60, speed: 2.5, pattern: Some(String::from("spiral")) }));
+        assert!(BossPhase::parse("fast").is_err());
+    }
+
+    #[test]
+    fn test_phase_transitions() {
+        //given
+        let mut boss = create_boss();
+
+        //when
+        boss.damage(39);
+        let first_phase = boss.phase;
+        boss.damage(1);
+        let second_phase = boss.phase;
+        boss.damage(40);
+        let third_phase = boss.phase;
+        boss.damage(20);
+
+        //then
+        assert_eq!(first_phase, 0);
+        assert_eq!(second_phase, 1);
+        assert_eq!(third_phase, 2);
+        assert!(boss.is_destroyed);
+        assert_eq!(boss.health, 0);
+    }
+
+    #[test]
+    fn test_waits_for_the_view() {
+        //given
+        let mut boss = create_boss();
+        let level = Level::new(20, 60, vec![]);
+        let patterns = HashMap::new();
+        let mut rng = Rng::new(1);
+        let mut world = World { level: &level, players: Vec::new(), enemies: Vec::new(), patterns: &patterns, rng: &mut rng };
+
+        //when
+        boss.update(&mut world);
+
+        //then
+        assert!(!boss.is_engaged(&level));
+        assert_eq!(boss.position, Position::new(100.0, 50.0));
+    }
+
+    #[test]
+    fn test_multiple_hitboxes() {
+        //given
+        let mut boss = BossState::new(1, Position::new(100.0, 50.0), 200, 100);
+
+        //when
+        let result = boss.set_parts("0,0,50,50; 150,0,50,50");
+
+        //then
+        assert_eq!(result, Ok(()));
+        let rects = boss.collision_rects();
+        assert_eq!(rects.len(), 2);
+        assert_eq!((rects[1].x, rects[1].y), (250.0, 50.0));
+        assert!(boss.set_parts("0,0,50").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use game::Campaign;
+use game::LevelGoal;
+use game::Stage;
+
+impl LevelGoal {
+    pub fn parse(name: &str) -> Result<LevelGoal, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "top" => Ok(LevelGoal::ReachTop),
+            "boss" => Ok(LevelGoal::DefeatBoss),
+            _ => Err(format!("Unknown level goal: {:?}", name)),
+        }
+    }
+}
+
+impl Stage {
+    pub fn new(map: &str, name: &str, goal: LevelGoal) -> Stage {
+        Stage {
+            map: map.to_string(),
+            name: name.to_string(),
+            goal,
+        }
+    }
+}
+
+impl Campaign {
+    pub fn new(stages: Vec<Stage>) -> Campaign {
+        Campaign { stages, current: 0 }
+    }
+
+    pub fn current(&self) -> Option<&Stage> {
+        self.stages.get(self.current)
+    }
+
+    // 1-based, the way it is shown to the player
+    pub fn stage_number(&self) -> usize {
+        self.current + 1
+    }
+
+    // Moves on to the next stage, there is none after the last map
+    pub fn advance(&mut self) -> Option<&Stage> {
+        if self.current + 1 >= self.stages.len() {
+            return None;
+        }
+        self.current += 1;
+        self.current()
+    }
+
+    // Without a campaign a map is played until it scrolled to the top
+    pub fn goal(&self) -> LevelGoal {
+        self.current().map(|stage| stage.goal).unwrap_or(LevelGoal::ReachTop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::Campaign;
+    use game::LevelGoal;
+    use game::Stage;
+
+    #[test]
+    fn test_advancing() {
+        //given
+        let mut campaign = Campaign::new(vec![
+            Stage::new("map1", "COAST", LevelGoal::DefeatBoss),
+            Stage::new("map2", "OPEN SEA", LevelGoal::ReachTop),
+        ]);
+
+        //when
+        let next = campaign.advance().map(|stage| stage.map.clone());
+        let after_last = campaign.advance().is_none();
+
+        //then
+        assert_eq!(next, Some(String::from("map2")));
+        assert!(after_last);
+        assert_eq!(campaign.stage_number(), 2);
+        assert_eq!(campaign.goal(), LevelGoal::ReachTop);
+    }
+
+    #[test]
+    fn test_parsing_goals() {
+        assert_eq!(LevelGoal::parse("Boss"), Ok(LevelGoal::DefeatBoss));
+        assert_eq!(LevelGoal::parse("top"), Ok(LevelGoal::ReachTop));
+        assert!(LevelGoal::parse("survive").is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use game::Console;
+use game::Engine;
+use game::InputHandler;
+use game::InputState;
+use game::ObjectType;
+use game::Position;
+use game::Renderer;
+use game::level::TILE_SIZE;
+use game::states;
+use std::str::FromStr;
+use SCREEN_SIZE;
+
+// the key that opens the console also comes in as text, it is not typed into the line
+const TOGGLE_CHARACTER: char = '`';
+const MAX_OUTPUT_LINES: usize = 100;
+const VISIBLE_LINES: usize = 10;
+const LINE_HEIGHT: f32 = 16.0;
+const MARGIN: f32 = 4.0;
+const HEIGHT: u32 = 200;
+const BACKGROUND_COLOR: (u8, u8, u8) = (20, 20, 20);
+const MAX_FPS: u32 = 1000;
+
+const HELP: &str = "spawn <Enemy|Boss|PowerUp> <x> <y>  on screen coordinates
+god                  planes can not be hit
+noclip               planes fly through everything, off screen too
+scroll speed <n>     pixels per frame
+teleport <y>         moves the view and the planes to that map height
+set tile <x> <y> <id>  changes a tile, 0 clears it
+load map <id>        starts the map from game.xml
+set fps <n>          frames per second";
+
+impl Console {
+    pub fn toggle(&mut self) {
+        self.is_open = !self.is_open;
+        self.held.clear();
+    }
+
+    // The line once it is entered
+    pub fn type_input(&mut self, inputs: &[InputState]) -> Option<String> {
+        let pressed: Vec<InputState> = inputs.iter().filter(|input| !self.held.contains(input)).cloned().collect();
+        // only the arrows, the second player's keys are letters that are typed
+        self.held = inputs
+            .iter()
+            .filter(|input| matches!(input, InputState::Up(0) | InputState::Down(0)))
+            .cloned()
+            .collect();
+
+        let mut entered = None;
+        for input in pressed {
+            match input {
+                InputState::Character(TOGGLE_CHARACTER) => {}
+                InputState::Character(character) => self.line.push(character),
+                InputState::Backspace => {
+                    self.line.pop();
+                }
+                InputState::Up(0) => self.browse_back(),
+                InputState::Down(0) => self.browse_forward(),
+                InputState::Confirm => entered = Some(self.enter()),
+                _ => {}
+            }
+        }
+        entered
+    }
+
+    pub fn print(&mut self, text: &str) {
+        self.output.extend(text.lines().map(String::from));
+        let overflow = self.output.len().saturating_sub(MAX_OUTPUT_LINES);
+        self.output.drain(..overflow);
+    }
+
+    pub fn draw<R: Renderer>(&self, renderer: &mut R) {
+        let (screen_width, _) = SCREEN_SIZE;
+        renderer.fill_rect(Position::new(0.0, 0.0), screen_width, HEIGHT, BACKGROUND_COLOR);
+
+        let first_visible = self.output.len().saturating_sub(VISIBLE_LINES);
+        for (index, line) in self.output[first_visible..].iter().enumerate() {
+            renderer.draw_text(line, Position::new(MARGIN, MARGIN + index as f32 * LINE_HEIGHT));
+        }
+        let prompt = format!("> {}_", self.line);
+        renderer.draw_text(&prompt, Position::new(MARGIN, HEIGHT as f32 - LINE_HEIGHT - MARGIN));
+    }
+
+    fn enter(&mut self) -> String {
+        let line = self.line.trim().to_string();
+        self.line.clear();
+        self.browsing = None;
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.print(&format!("> {}", line));
+        line
+    }
+
+    fn browse_back(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = self.browsing.map_or(self.history.len() - 1, |index| index.saturating_sub(1));
+        self.browsing = Some(index);
+        self.line = self.history[index].clone();
+    }
+
+    fn browse_forward(&mut self) {
+        match self.browsing {
+            Some(index) if index + 1 < self.history.len() => {
+                self.browsing = Some(index + 1);
+                self.line = self.history[index + 1].clone();
+            }
+            _ => {
+                self.browsing = None;
+                self.line.clear();
+            }
+        }
+    }
+}
+
+impl<R, I> Engine<R, I>
+    where
+        R: Renderer,
+        I: InputHandler,
+{
+    pub fn toggle_console(&mut self) {
+        self.console.toggle();
+    }
+
+    pub fn is_console_open(&self) -> bool {
+        self.console.is_open
+    }
+
+    // Keys typed while the console is open go to it instead of the planes
+    pub fn type_into_console(&mut self, inputs: &[InputState]) {
+        if let Some(command) = self.console.type_input(inputs) {
+            let output = self.run_command(&command).unwrap_or_else(|e| e);
+            self.console.print(&output);
+        }
+    }
+
+    pub fn target_fps(&self) -> u32 {
+        self.target_fps
+    }
+
+    pub fn run_command(&mut self, command: &str) -> Result<String, String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] => Ok(String::from(HELP)),
+            ["spawn", object_type, x, y] => {
+                let position = self.level.position + Position::new(number(x)?, number(y)?);
+                let game_object = states::spawn(ObjectType::parse(object_type), position)?;
+                let id = game_object.id;
+                self.add_new_objects(vec![Some(game_object)]);
+                Ok(format!("spawned {} {}", object_type, id))
+            }
+            ["god"] => {
+                self.cheats.god = !self.cheats.god;
+                Ok(format!("god mode {}", on_or_off(self.cheats.god)))
+            }
+            ["noclip"] => {
+                self.cheats.noclip = !self.cheats.noclip;
+                Ok(format!("noclip {}", on_or_off(self.cheats.noclip)))
+            }
+            ["scroll", "speed", speed] => {
+                let speed: f32 = number(speed)?;
+                if speed < 0.0 {
+                    return Err(String::from("The view only scrolls up"));
+                }
+                self.level.scroll_speed = speed;
+                Ok(format!("scrolling {} pixels per frame", speed))
+            }
+            ["teleport", y] => {
+                let top = (self.level.height * TILE_SIZE).saturating_sub(SCREEN_SIZE.1) as f32;
+                let y = number::<f32>(y)?.max(0.0).min(top);
+                let offset = Position::new(0.0, y - self.level.position.y);
+                self.level.position.y = y;
+                for player in self.game_objects.iter_mut().flatten().filter_map(|game_object| game_object.player.as_mut()) {
+                    player.position += offset;
+                }
+                Ok(format!("view moved to {}", y))
+            }
+            ["load", "map", map_id] => {
+                self.pending_map = Some(map_id.to_string());
+                Ok(format!("loading map {}", map_id))
+            }
+            ["set", "tile", column, row, tile_id] => {
+                // the bottom tile layer, the one the planes fly over
+                let layer = (0..self.level.layers.len())
+                    .find(|layer| self.level.tile_layer(*layer).is_some())
+                    .ok_or_else(|| String::from("The map has no tiles"))?;
+                self.level.set_tile(layer, number(column)?, number(row)?, number(tile_id)?)?;
+                Ok(format!("tile {},{} set to {}", column, row, tile_id))
+            }
+            ["set", "fps", fps] => {
+                let fps: u32 = number(fps)?;
+                if fps == 0 || fps > MAX_FPS {
+                    return Err(format!("Frames per second go from 1 to {}", MAX_FPS));
+                }
+                self.target_fps = fps;
+                Ok(format!("running at {} fps", fps))
+            }
+            _ => Err(format!("Unknown command: {:?}, try help", command)),
+        }
+    }
+}
+
+fn number<T: FromStr>(text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("Not a number: {:?}", text))
+}
+
+fn on_or_off(is_on: bool) -> &'static str {
+    if is_on { "on" } else { "off" }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::AnimationState;
+    use game::Console;
+    use game::Engine;
+    use game::GameObject;
+    use game::InputHandler;
+    use game::InputState;
+    use game::Level;
+    use game::ObjectType;
+    use game::Position;
+    use game::Renderer;
+
+    struct MockRenderer {}
+
+    struct MockInputHandler {}
+
+    impl Renderer for MockRenderer {
+        fn clear_scene(&mut self) {
+            unimplemented!()
+        }
+
+        fn draw_scene(&mut self) {
+            unimplemented!()
+        }
+
+        fn draw_texture(&mut self, _texture_id: &str, _position: Position, _level: &Level) {
+            unimplemented!()
+        }
+
+        fn draw_tile(&mut self, _texture_id: &str, _position: Position, _tile_id: u8) {
+            unimplemented!()
+        }
+
+        fn draw_frame(&mut self, _animation: &AnimationState, _position: Position, _level: &Level) {
+            unimplemented!()
+        }
+
+        fn draw_text(&mut self, _text: &str, _position: Position) {
+            unimplemented!()
+        }
+
+        fn fill_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+
+        fn draw_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+
+        fn draw_line(&mut self, _from: Position, _to: Position, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+    }
+
+    impl InputHandler for MockInputHandler {
+        fn capture(&mut self) -> Vec<InputState> {
+            unimplemented!()
+        }
+    }
+
+    fn typed(text: &str) -> Vec<InputState> {
+        text.chars().map(InputState::Character).chain(vec![InputState::Confirm]).collect()
+    }
+
+    #[test]
+    fn test_typing_and_browsing_the_history() {
+        //given
+        let mut console = Console::default();
+        console.toggle();
+        console.type_input(&typed("god"));
+        console.type_input(&typed("`noclipp"));
+        console.type_input(&[InputState::Character('x'), InputState::Backspace]);
+
+        //when
+        console.type_input(&[InputState::Up(0)]);
+        let held = console.line.clone();
+        console.type_input(&[]);
+        console.type_input(&[InputState::Up(0)]);
+        let pressed_again = console.line.clone();
+        console.type_input(&[InputState::Down(0)]);
+        let entered = console.type_input(&[InputState::Down(0), InputState::Confirm]);
+
+        //then
+        assert_eq!(held, "noclipp");
+        assert_eq!(pressed_again, "god");
+        assert_eq!(entered, Some(String::from("noclipp")));
+        assert_eq!(console.history, vec![String::from("god"), String::from("noclipp")]);
+        assert_eq!(console.output.last(), Some(&String::from("> noclipp")));
+    }
+
+    #[test]
+    fn test_running_commands() {
+        //given
+        let plane = GameObject::new(1, Position::new(100.0, 1500.0), ObjectType::Player, 65, 65);
+        let mut engine = Engine::new(vec![Some(plane)], Level::new(20, 60, vec![0; 20 * 60]), MockRenderer {}, MockInputHandler {});
+
+        //when
+        let spawned = engine.run_command("spawn Enemy 200 100");
+        let god = engine.run_command("god");
+        let scroll = engine.run_command("scroll speed 3");
+        let teleport = engine.run_command("teleport 320");
+        let fps = engine.run_command("set fps 30");
+        engine.run_command("load map map2").unwrap();
+        let unknown = engine.run_command("fly me to the moon");
+        let incorrect = engine.run_command("spawn Bullet 1 2");
+
+        //then
+        assert!(spawned.unwrap().starts_with("spawned Enemy"));
+        let enemy = engine.game_objects.iter().flatten().find(|game_object| game_object.enemy.is_some()).unwrap();
+        assert_eq!(enemy.position(), Position::new(200.0, 1420.0));
+        assert_eq!(god, Ok(String::from("god mode on")));
+        assert!(engine.cheats.god);
+        assert_eq!(scroll, Ok(String::from("scrolling 3 pixels per frame")));
+        assert_eq!(engine.level.scroll_speed, 3.0);
+        assert_eq!(teleport, Ok(String::from("view moved to 320")));
+        assert_eq!(engine.level.position, Position::new(0.0, 320.0));
+        let plane = engine.game_objects.iter().flatten().find(|game_object| game_object.player.is_some()).unwrap();
+        assert_eq!(plane.position(), Position::new(100.0, 500.0));
+        assert_eq!(fps, Ok(String::from("running at 30 fps")));
+        assert_eq!(engine.target_fps(), 30);
+        assert_eq!(engine.take_pending_map(), Some(String::from("map2")));
+        assert!(unknown.is_err());
+        assert!(incorrect.is_err());
+    }
+}
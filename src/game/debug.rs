@@ -0,0 +1,197 @@
+use game::Engine;
+use game::GameObject;
+use game::InputHandler;
+use game::Level;
+use game::ObjectType;
+use game::Position;
+use game::ProfiledSection;
+use game::Profiler;
+use game::Renderer;
+use game::level::TILE_SIZE;
+use game::profiler::HISTORY_FRAMES;
+use std::time::Duration;
+use FPS;
+use SCREEN_SIZE;
+
+// velocities are drawn as where the object gets to in this many frames
+const VELOCITY_FRAMES: f32 = 10.0;
+// above the object, one line of the bitmap font
+const LABEL_OFFSET: f32 = 16.0;
+
+const GRID_COLOR: (u8, u8, u8) = (70, 70, 110);
+const CAMERA_COLOR: (u8, u8, u8) = (255, 0, 255);
+const VELOCITY_COLOR: (u8, u8, u8) = (255, 255, 255);
+
+const BAR_WIDTH: u32 = 2;
+const GRAPH_HEIGHT: u32 = 100;
+const GRAPH_MARGIN: u32 = 10;
+const PIXELS_PER_MILLISECOND: f32 = 4.0;
+const GRAPH_COLOR: (u8, u8, u8) = (0, 200, 0);
+// a frame has to be done by this line to keep up the frame rate
+const BUDGET_COLOR: (u8, u8, u8) = (255, 80, 0);
+
+impl<R, I> Engine<R, I>
+    where
+        R: Renderer,
+        I: InputHandler,
+{
+    pub fn toggle_debug_overlay(&mut self) {
+        self.debug_overlay = !self.debug_overlay;
+    }
+}
+
+// Everything is drawn in screen coordinates, the level's position is the camera
+pub fn draw_overlay<R: Renderer>(renderer: &mut R, level: &Level, game_objects: &[Option<GameObject>], profiler: &Profiler) {
+    let camera = level.position;
+    let (screen_width, screen_height) = SCREEN_SIZE;
+
+    for column in 0..=level.width {
+        let x = (column * TILE_SIZE) as f32 - camera.x;
+        renderer.draw_line(Position::new(x, 0.0), Position::new(x, screen_height as f32), GRID_COLOR);
+    }
+    let first_row = (camera.y.max(0.0) as u32) / TILE_SIZE;
+    for row in first_row..=(first_row + screen_height / TILE_SIZE + 1).min(level.height) {
+        let y = (row * TILE_SIZE) as f32 - camera.y;
+        renderer.draw_line(Position::new(0.0, y), Position::new(screen_width as f32, y), GRID_COLOR);
+    }
+
+    for game_object in game_objects.iter().flatten() {
+        let color = hitbox_color(game_object.object_type);
+        for hitbox in game_object.hitboxes() {
+            let position = Position::new(hitbox.x, hitbox.y) - camera;
+            renderer.draw_rect(position, hitbox.width as u32, hitbox.height as u32, color);
+        }
+
+        let position = game_object.position() - camera;
+        let label = format!("{} {:?}", game_object.id, game_object.object_type);
+        renderer.draw_text(&label, position - Position::new(0.0, LABEL_OFFSET));
+
+        let velocity = game_object.velocity();
+        if velocity.x != 0.0 || velocity.y != 0.0 {
+            renderer.draw_line(position, position + velocity * VELOCITY_FRAMES, VELOCITY_COLOR);
+        }
+    }
+
+    renderer.draw_rect(Position::new(0.0, 0.0), screen_width, screen_height, CAMERA_COLOR);
+    let camera_label = format!("camera {},{}", camera.x.round(), camera.y.round());
+    renderer.draw_text(&camera_label, Position::new(4.0, (screen_height - 20) as f32));
+
+    draw_profile(renderer, profiler);
+}
+
+// A bar per frame of the recent history, with the averages and the last frame's counters above it
+fn draw_profile<R: Renderer>(renderer: &mut R, profiler: &Profiler) {
+    let (screen_width, screen_height) = SCREEN_SIZE;
+    let left = (screen_width - HISTORY_FRAMES as u32 * BAR_WIDTH - GRAPH_MARGIN) as f32;
+    let bottom = (screen_height - GRAPH_MARGIN) as f32;
+
+    for (index, frame) in profiler.history().iter().enumerate() {
+        let height = (milliseconds(frame.total()) * PIXELS_PER_MILLISECOND).min(GRAPH_HEIGHT as f32).max(1.0);
+        let position = Position::new(left + (index as u32 * BAR_WIDTH) as f32, bottom - height);
+        renderer.fill_rect(position, BAR_WIDTH, height as u32, GRAPH_COLOR);
+    }
+    let budget = bottom - (1000.0 / f32::from(FPS) * PIXELS_PER_MILLISECOND);
+    renderer.draw_line(Position::new(left, budget), Position::new(left + (HISTORY_FRAMES as u32 * BAR_WIDTH) as f32, budget), BUDGET_COLOR);
+
+    let mut lines: Vec<String> = ProfiledSection::all()
+        .iter()
+        .map(|section| format!("{} {:.2}ms", section.name(), milliseconds(profiler.average(*section))))
+        .collect();
+    if let Some(frame) = profiler.history().back() {
+        lines.push(format!("objects {} bullets {}", frame.objects, frame.bullets));
+        lines.push(format!("draw calls {}", frame.draw_calls));
+    }
+    let top = bottom - GRAPH_HEIGHT as f32 - (lines.len() as f32 * LABEL_OFFSET);
+    for (index, line) in lines.iter().enumerate() {
+        renderer.draw_text(line, Position::new(left, top + index as f32 * LABEL_OFFSET));
+    }
+}
+
+fn milliseconds(duration: Duration) -> f32 {
+    duration.as_secs() as f32 * 1000.0 + duration.subsec_nanos() as f32 / 1_000_000.0
+}
+
+fn hitbox_color(object_type: ObjectType) -> (u8, u8, u8) {
+    match object_type {
+        ObjectType::Player => (0, 255, 0),
+        ObjectType::Enemy | ObjectType::Boss => (255, 0, 0),
+        ObjectType::Bullet => (255, 255, 0),
+        ObjectType::PowerUp => (0, 255, 255),
+        ObjectType::Unknown => (255, 255, 255),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::AnimationState;
+    use game::Engine;
+    use game::GameObject;
+    use game::InputHandler;
+    use game::InputState;
+    use game::Level;
+    use game::ObjectType;
+    use game::Position;
+    use game::Renderer;
+
+    struct MockRenderer {
+        rects: Vec<(Position, u32, u32)>,
+        texts: Vec<(String, Position)>,
+        lines: usize,
+    }
+
+    struct MockInputHandler {}
+
+    impl Renderer for MockRenderer {
+        fn clear_scene(&mut self) {}
+
+        fn draw_scene(&mut self) {}
+
+        fn draw_texture(&mut self, _texture_id: &str, _position: Position, _level: &Level) {}
+
+        fn draw_tile(&mut self, _texture_id: &str, _position: Position, _tile_id: u8) {}
+
+        fn draw_frame(&mut self, _animation: &AnimationState, _position: Position, _level: &Level) {}
+
+        fn draw_text(&mut self, text: &str, position: Position) {
+            self.texts.push((text.to_string(), position));
+        }
+
+        fn fill_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {}
+
+        fn draw_rect(&mut self, position: Position, width: u32, height: u32, _color: (u8, u8, u8)) {
+            self.rects.push((position, width, height));
+        }
+
+        fn draw_line(&mut self, _from: Position, _to: Position, _color: (u8, u8, u8)) {
+            self.lines += 1;
+        }
+    }
+
+    impl InputHandler for MockInputHandler {
+        fn capture(&mut self) -> Vec<InputState> {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_drawing_hitboxes_and_labels() {
+        //given
+        let plane = GameObject::new(7, Position::new(100.0, 1400.0), ObjectType::Player, 65, 65);
+        let level = Level::new(20, 60, vec![0; 20 * 60]);
+        let renderer = MockRenderer { rects: Vec::new(), texts: Vec::new(), lines: 0 };
+        let mut engine = Engine::new(vec![Some(plane)], level, renderer, MockInputHandler {});
+        engine.toggle_debug_overlay();
+
+        //when
+        engine.draw();
+
+        //then
+        let renderer = engine.renderer_mut();
+        assert_eq!(renderer.rects[0], (Position::new(103.0, 90.0), 62, 55));
+        assert_eq!(renderer.rects[1], (Position::new(0.0, 0.0), 800, 600));
+        assert!(renderer.texts.contains(&(String::from("7 Player"), Position::new(100.0, 64.0))));
+        assert!(renderer.texts.contains(&(String::from("camera 0,1320"), Position::new(4.0, 580.0))));
+        // the grid and the frame budget in the profile graph
+        assert_eq!(renderer.lines, 21 + 20 + 1);
+    }
+}
@@ -1,10 +1,48 @@
+use game::BossState;
+use game::Campaign;
+use game::Cheats;
+use game::Console;
+use game::DrawCounter;
 use game::Engine;
+use game::EngineState;
+use game::GameEvent;
+use game::BulletPattern;
 use game::GameObject;
+use game::GameState;
+use game::HighScores;
+use game::Hud;
+use game::Id;
 use game::InputHandler;
 use game::InputState;
 use game::Level;
+use game::LevelGoal;
+use game::ObjectType;
+use game::PlayerStatus;
+use game::Position;
+use game::PowerUpKind;
+use game::ProfiledSection;
+use game::Profiler;
+use game::Record;
 use game::Renderer;
+use game::Rng;
+use game::World;
+use game::debug;
+use game::save::SAVE_VERSION;
+use game::states;
+use helpers::parsers::save_file;
+use std::collections::HashMap;
 use std::mem;
+use std::time::Instant;
+use FPS;
+
+const MAX_NAME_LENGTH: usize = 8;
+// used until the game is given a seed of its own
+const DEFAULT_SEED: u64 = 0x5EED;
+// how long the name of the next stage is shown before it starts
+const TRANSITION_TICKS: u32 = 2 * FPS as u32;
+// where the maps start the first plane inside the view, the second one flies beside it
+const RESPAWN_OFFSET: (f32, f32) = (288.0, 440.0);
+const RESPAWN_SPACING: f32 = 96.0;
 
 impl<R, I> Engine<R, I>
     where
@@ -12,65 +50,524 @@ impl<R, I> Engine<R, I>
         I: InputHandler,
 {
     pub fn new(game_objects: Vec<Option<GameObject>>, level: Level, renderer: R, input_handler: I) -> Engine<R, I> {
-        Engine {
+        let mut engine = Engine {
             is_running: true,
             renderer,
             input_handler,
             level,
             game_objects,
+            hud: Hud::new(),
+            state: GameState::Playing,
+            players: Vec::new(),
+            player_count: 1,
+            high_scores: HighScores::default(),
+            patterns: HashMap::new(),
+            campaign: Campaign::default(),
+            pending_map: None,
+            pending_restore: None,
+            checkpoint: None,
+            save_filename: None,
+            rng: Rng::new(DEFAULT_SEED),
+            debug_overlay: false,
+            profiler: Profiler::default(),
+            console: Console::default(),
+            cheats: Cheats::default(),
+            target_fps: u32::from(FPS),
+        };
+        engine.renderer.prepare_level(&engine.level);
+        engine.sync_players();
+        engine
+    }
+
+    // Co-op needs a map with a plane for every player, planes of players that are not playing are left out
+    pub fn set_player_count(&mut self, player_count: usize) {
+        self.player_count = player_count;
+        self.sync_players();
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    // Checkpoints are written here and the game is continued from it
+    pub fn set_save_file(&mut self, filename: &str) {
+        self.save_filename = Some(filename.to_string());
+    }
+
+    pub fn set_campaign(&mut self, campaign: Campaign) {
+        self.campaign = campaign;
+    }
+
+    pub fn current_map(&self) -> Option<&str> {
+        self.campaign.current().map(|stage| stage.map.as_str())
+    }
+
+    // The map of a stage that was just reached, it has to be handed back through `start_level`
+    pub fn take_pending_map(&mut self) -> Option<String> {
+        self.pending_map.take()
+    }
+
+    pub fn set_patterns(&mut self, patterns: HashMap<String, BulletPattern>) {
+        self.patterns = patterns;
+    }
+
+    pub fn set_high_scores(&mut self, high_scores: HighScores) {
+        self.high_scores = high_scores;
+    }
+
+    pub fn restart(&mut self, game_objects: Vec<Option<GameObject>>, level: Level) {
+        self.game_objects = game_objects;
+        self.level = level;
+        self.renderer.prepare_level(&self.level);
+        self.sync_players();
+    }
+
+    // Like a restart, but every player keeps the weapon they had at the end of the previous stage
+    pub fn start_level(&mut self, mut game_objects: Vec<Option<GameObject>>, level: Level) {
+        for player in game_objects.iter_mut().flatten().filter_map(|game_object| game_object.player.as_mut()) {
+            if let Some(status) = self.players.get(player.index) {
+                player.weapon = status.weapon;
+            }
         }
+        self.hud.boss_health = None;
+        self.checkpoint = None;
+        self.restart(game_objects, level);
+
+        if let Some(records) = self.pending_restore.take() {
+            if let Err(e) = self.restore(&records) {
+                println!("Error restoring saved game: {}", e);
+            }
+            self.checkpoint = Some(records);
+        }
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut R {
+        &mut self.renderer
+    }
+
+    pub fn set_fps(&mut self, fps: u32) {
+        self.hud.set_fps(fps);
     }
 
     pub fn draw(&mut self) {
-        self.renderer.clear_scene();
+        let start = Instant::now();
+        let mut renderer = DrawCounter::new(&mut self.renderer);
+        renderer.clear_scene();
 
-        self.level.draw(&mut self.renderer);
+        self.level.draw(&mut renderer);
 
         for game_object in &mut self.game_objects {
             if let Some(game_object) = game_object {
-                game_object.draw(&mut self.renderer, &self.level);
+                if game_object.is_visible(&self.level) {
+                    game_object.draw(&mut renderer, &self.level);
+                }
             }
         }
 
-        self.renderer.draw_scene();
+        self.hud.draw(&mut renderer, &self.players);
+        match self.state {
+            GameState::Playing => {}
+            GameState::LevelComplete(ref tallies) => self.hud.draw_tally(&mut renderer, tallies),
+            GameState::Transition { .. } => {
+                if let Some(stage) = self.campaign.current() {
+                    self.hud.draw_transition(&mut renderer, self.campaign.stage_number(), &stage.name)
+                }
+            }
+            GameState::GameOver { ref name, is_entering_name } => {
+                self.hud.draw_game_over(&mut renderer, &self.high_scores, name, is_entering_name)
+            }
+        }
+
+        if self.debug_overlay {
+            debug::draw_overlay(&mut renderer, &self.level, &self.game_objects, &self.profiler);
+        }
+        if self.console.is_open {
+            self.console.draw(&mut renderer);
+        }
+        renderer.draw_scene();
+
+        let draw_calls = renderer.calls();
+        self.profiler.add(ProfiledSection::Draw, start.elapsed());
+        let objects = self.game_objects.iter().flatten().count();
+        let bullets = self.game_objects.iter().flatten().filter(|game_object| game_object.object_type == ObjectType::Bullet).count();
+        self.profiler.end_frame(objects, bullets, draw_calls);
+    }
+
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    pub fn profiler_mut(&mut self) -> &mut Profiler {
+        &mut self.profiler
+    }
+
+    pub fn capture_input(&mut self) -> Vec<InputState> {
+        self.input_handler.capture()
+    }
+
+    // One frame played with inputs that did not come from this engine's input handler
+    pub fn step(&mut self, input_state: &[InputState]) {
+        let start = Instant::now();
+        self.apply_input(input_state);
+        self.profiler.add(ProfiledSection::Input, start.elapsed());
+        self.update();
     }
 
-    pub fn handle_input(&mut self) {
-        let input_state = self.input_handler.capture();
+    // Unlike a snapshot nothing is left out, a loaded state plays on exactly like the saved one did.
+    // Ids handed out after the state was saved are not reused, nothing depends on their values.
+    pub fn save_state(&self) -> EngineState {
+        EngineState {
+            level: self.level.clone(),
+            game_objects: self.game_objects.clone(),
+            hud: self.hud.clone(),
+            state: self.state.clone(),
+            players: self.players.clone(),
+            stage: self.campaign.current,
+            pending_map: self.pending_map.clone(),
+            checkpoint: self.checkpoint.clone(),
+            rng: self.rng.clone(),
+        }
+    }
 
-        if Self::should_quit(&input_state) {
+    pub fn load_state(&mut self, saved: &EngineState) {
+        let fps = self.hud.fps;
+        self.level = saved.level.clone();
+        self.game_objects = saved.game_objects.clone();
+        self.hud = saved.hud.clone();
+        self.hud.fps = fps;
+        self.state = saved.state.clone();
+        self.players = saved.players.clone();
+        self.campaign.current = saved.stage;
+        self.pending_map = saved.pending_map.clone();
+        self.checkpoint = saved.checkpoint.clone();
+        self.rng = saved.rng.clone();
+    }
+
+    fn apply_input(&mut self, input_state: &[InputState]) {
+        if Self::should_quit(input_state) {
             self.is_running = false;
             return;
         }
+        if input_state.contains(&InputState::Load) {
+            self.load_game();
+            return;
+        }
 
-        for game_object in &mut self.game_objects {
-            if let Some(game_object) = game_object {
-                game_object.handle_input(&input_state);
+        match self.state {
+            GameState::Playing => {
+                for game_object in &mut self.game_objects {
+                    if let Some(game_object) = game_object {
+                        game_object.handle_input(input_state);
+                    }
+                }
+            }
+            GameState::LevelComplete(_) => {
+                if input_state.contains(&InputState::Confirm) {
+                    self.next_stage();
+                }
             }
+            GameState::Transition { .. } => {}
+            GameState::GameOver { .. } => self.enter_name(input_state),
         }
     }
 
     pub fn update(&mut self) {
+        if let GameState::Transition { ticks } = self.state {
+            self.state = if ticks > 1 { GameState::Transition { ticks: ticks - 1 } } else { GameState::Playing };
+            return;
+        }
+        if self.state != GameState::Playing {
+            return;
+        }
+
+        let start = Instant::now();
         self.update_objects();
-        self.check_collisions();
+        let collisions_start = Instant::now();
+        let events = self.check_collisions();
+        let removal_start = Instant::now();
         self.remove_destroyed_objects();
+        self.profiler.add(ProfiledSection::UpdateObjects, collisions_start - start);
+        self.profiler.add(ProfiledSection::CheckCollisions, removal_start - collisions_start);
+        self.profiler.add(ProfiledSection::RemoveDestroyedObjects, removal_start.elapsed());
+
+        let boss_health = self.engaged_boss().map(|boss| (boss.health, boss.max_health));
+        self.level.is_halted = boss_health.is_some();
+        self.hud.boss_health = boss_health;
 
         self.level.update();
+        for player in &mut self.players {
+            player.score.update();
+        }
+        self.handle_events(&events);
+
+        if self.state == GameState::Playing && self.level.pass_checkpoints() {
+            self.save_checkpoint();
+        }
+
+        if self.state == GameState::Playing && self.is_level_cleared() {
+            self.state = GameState::LevelComplete(self.players.iter_mut().map(|player| player.tally()).collect());
+        }
+    }
+
+    fn handle_events(&mut self, events: &[GameEvent]) {
+        for event in events {
+            match event {
+                GameEvent::EnemyKilled { score, player } => {
+                    if let Some(status) = self.status_mut(*player) {
+                        status.score.add_kill(*score);
+                    }
+                }
+                GameEvent::PlayerKilled { player } => {
+                    // in co-op the game goes on as long as someone is still flying
+                    if !self.respawn(*player) && !self.has_player_planes() {
+                        self.game_over();
+                    }
+                }
+                GameEvent::PowerUpDropped { kind, position } => {
+                    self.add_new_objects(vec![Some(states::spawn_power_up(*position, *kind))])
+                }
+                GameEvent::PowerUpCollected { kind: PowerUpKind::Bomb, .. } => self.hud.bombs += 1,
+                GameEvent::PowerUpCollected { kind: PowerUpKind::Life, player } => {
+                    if let Some(status) = self.status_mut(*player) {
+                        status.lives += 1;
+                    }
+                }
+                GameEvent::PowerUpCollected { kind: PowerUpKind::Weapon, .. } => {}
+            }
+        }
+    }
+
+    fn status_mut(&mut self, id: Id) -> Option<&mut PlayerStatus> {
+        self.players.iter_mut().find(|status| status.id == id)
+    }
+
+    fn has_player_planes(&self) -> bool {
+        self.game_objects.iter().flatten().any(|game_object| game_object.player.is_some())
+    }
+
+    // Takes the planes of players that are not playing off the map and points every status at its player's plane
+    fn sync_players(&mut self) {
+        let player_count = self.player_count;
+        for game_object in &mut self.game_objects {
+            let is_extra = match game_object {
+                Some(GameObject { player: Some(ref player), .. }) => player.index >= player_count,
+                _ => false,
+            };
+            if is_extra {
+                *game_object = None;
+            }
+        }
+
+        self.players.truncate(player_count);
+        while self.players.len() < player_count {
+            self.players.push(PlayerStatus::new(0));
+        }
+        for player in self.game_objects.iter().flatten().filter_map(|game_object| game_object.player.as_ref()) {
+            self.players[player.index].id = player.id;
+        }
+    }
+
+    // Either the map scrolled to the top with every enemy gone, or its boss went down
+    fn is_level_cleared(&self) -> bool {
+        let objects = || self.game_objects.iter().flatten();
+        match self.campaign.goal() {
+            LevelGoal::ReachTop => {
+                self.level.position.y <= 0.0
+                    && !objects().any(|game_object| game_object.enemy.is_some() || game_object.boss.is_some())
+            }
+            LevelGoal::DefeatBoss => !objects().any(|game_object| game_object.boss.is_some()),
+        }
+    }
+
+    // The campaign ends after its last stage, the same way it does when the player gets shot down
+    fn next_stage(&mut self) {
+        match self.campaign.advance() {
+            Some(stage) => {
+                self.pending_map = Some(stage.map.clone());
+                self.state = GameState::Transition { ticks: TRANSITION_TICKS };
+            }
+            None => self.game_over(),
+        }
+    }
+
+    // The boss the view has reached, scrolling waits until it is beaten
+    fn engaged_boss(&self) -> Option<&BossState> {
+        self.game_objects
+            .iter()
+            .flatten()
+            .filter_map(|game_object| game_object.boss.as_ref())
+            .find(|boss| boss.is_engaged(&self.level))
+    }
+
+    fn save_checkpoint(&mut self) {
+        let records = self.snapshot();
+        if let Some(ref filename) = self.save_filename {
+            if let Err(e) = save_file::write(filename, SAVE_VERSION, &records) {
+                println!("Error saving game to {:?}: {}", filename, e);
+            }
+        }
+        self.checkpoint = Some(records);
+    }
+
+    // A lost life brings the plane back, the player is out once there are none left. The last plane flying goes
+    // back to the checkpoint, otherwise it comes back where the view is. Nobody else's plane or status changes.
+    fn respawn(&mut self, id: Id) -> bool {
+        let index = match self.players.iter().position(|status| status.id == id) {
+            Some(index) => index,
+            None => return false,
+        };
+        if self.players[index].lives == 0 {
+            return false;
+        }
+        self.players[index].lives -= 1;
+
+        if !self.has_player_planes() {
+            if let Some(checkpoint) = self.checkpoint.clone() {
+                self.rewind_to_checkpoint(index, &checkpoint);
+            }
+        }
+        if !self.has_player_planes() || !self.is_flying(index) {
+            let offset = Position::new(RESPAWN_OFFSET.0 + RESPAWN_SPACING * index as f32, RESPAWN_OFFSET.1);
+            let plane = states::spawn_player(id, index, self.level.position + offset);
+            self.add_new_objects(vec![Some(plane)]);
+        }
+        true
+    }
+
+    // Puts the world back as it was at the checkpoint, only the given player's plane and status come with it
+    fn rewind_to_checkpoint(&mut self, index: usize, checkpoint: &[Record]) {
+        let players = self.players.clone();
+        if let Err(e) = self.restore(checkpoint) {
+            println!("Error restoring checkpoint: {}", e);
+            return;
+        }
+        for game_object in &mut self.game_objects {
+            let is_out = match game_object {
+                Some(GameObject { player: Some(ref player), .. }) => player.index != index,
+                _ => false,
+            };
+            if is_out {
+                *game_object = None;
+            }
+        }
+        for (i, (status, before)) in self.players.iter_mut().zip(players).enumerate() {
+            if i == index {
+                status.lives = before.lives;
+            } else {
+                *status = before;
+            }
+        }
+    }
+
+    fn is_flying(&self, index: usize) -> bool {
+        self.game_objects
+            .iter()
+            .flatten()
+            .any(|game_object| game_object.player.iter().any(|player| player.index == index))
+    }
+
+    // Continues from the save file, the saved stage's map is loaded again before the snapshot goes on top of it
+    fn load_game(&mut self) {
+        let filename = match self.save_filename {
+            Some(ref filename) => filename.clone(),
+            None => return,
+        };
+        let records = match save_file::parse(&filename) {
+            Ok((SAVE_VERSION, records)) => records,
+            Ok((version, _)) => return println!("Save file {:?} has version {}, expected {}", filename, version, SAVE_VERSION),
+            Err(e) => return println!("{}", e),
+        };
+        if let Err(e) = self.restore(&records) {
+            return println!("Error restoring saved game: {}", e);
+        }
+
+        self.checkpoint = Some(records.clone());
+        if let Some(map) = self.current_map().map(String::from) {
+            self.pending_map = Some(map);
+            self.pending_restore = Some(records);
+        }
+    }
+
+    fn game_over(&mut self) {
+        self.state = GameState::GameOver {
+            name: String::new(),
+            is_entering_name: self.high_scores.qualifies(self.best_score()),
+        };
+    }
+
+    // A co-op game goes on the table under the score of its best player
+    fn best_score(&self) -> u32 {
+        self.players.iter().map(|status| status.score.points).max().unwrap_or(0)
+    }
+
+    fn enter_name(&mut self, inputs: &[InputState]) {
+        let score = self.best_score();
+        let (name, is_entering_name) = match self.state {
+            GameState::GameOver { ref mut name, ref mut is_entering_name } => (name, is_entering_name),
+            _ => return,
+        };
+        if !*is_entering_name {
+            return;
+        }
+
+        for input in inputs {
+            match input {
+                InputState::Character(character) if character.is_ascii_alphanumeric() && name.len() < MAX_NAME_LENGTH => {
+                    name.push(character.to_ascii_uppercase())
+                }
+                InputState::Backspace => {
+                    name.pop();
+                }
+                InputState::Confirm if !name.is_empty() => {
+                    self.high_scores.insert(name, score);
+                    *is_entering_name = false;
+                    return;
+                }
+                _ => {}
+            }
+        }
     }
 
     fn update_objects(&mut self) {
         let mut new_object = Vec::new();
+        let players = self.positions_of(|game_object| game_object.player.is_some());
+        let enemies = self.positions_of(|game_object| game_object.enemy.is_some());
+        let mut world = World {
+            level: &self.level,
+            players,
+            enemies,
+            patterns: &self.patterns,
+            rng: &mut self.rng,
+        };
 
         for game_object in &mut self.game_objects {
             if let Some(ref mut game_object) = game_object {
-                game_object.update(&mut new_object, &self.level);
+                if let Some(ref mut player) = game_object.player {
+                    player.is_invulnerable = self.cheats.god;
+                    player.is_noclip = self.cheats.noclip;
+                }
+                game_object.update(&mut new_object, &mut world);
+                if let Some(ref player) = game_object.player {
+                    if let Some(status) = self.players.get_mut(player.index) {
+                        status.weapon = player.weapon;
+                    }
+                }
             }
         }
 
         self.add_new_objects(new_object);
     }
 
-    fn add_new_objects(&mut self, new_objects: Vec<Option<GameObject>>) {
+    fn positions_of<F: Fn(&GameObject) -> bool>(&self, filter: F) -> Vec<Position> {
+        self.game_objects
+            .iter()
+            .flatten()
+            .filter(|game_object| filter(game_object))
+            .map(|game_object| game_object.position())
+            .collect()
+    }
+
+    pub fn add_new_objects(&mut self, new_objects: Vec<Option<GameObject>>) {
         let mut next_new_object = new_objects.into_iter().filter(|obj| obj.is_some());
 
         for i in 0..self.game_objects.len() {
@@ -90,17 +587,19 @@ impl<R, I> Engine<R, I>
         self.game_objects.extend(new_objects);
     }
 
-    fn check_collisions(&mut self) {
+    fn check_collisions(&mut self) -> Vec<GameEvent> {
+        let mut events = Vec::new();
         for i in 0..self.game_objects.len() {
             let (me, tail) = self.game_objects[i..].split_first_mut().unwrap();
             if let Some(me) = me {
                 for candidate in tail {
                     if let Some(candidate) = candidate {
-                        me.check_collision(candidate);
+                        me.check_collision(candidate, &mut events);
                     }
                 }
             }
         }
+        events
     }
 
     fn remove_destroyed_objects(&mut self) {
@@ -123,16 +622,24 @@ impl<R, I> Engine<R, I>
 
 #[cfg(test)]
 mod tests {
+    use game::AnimationState;
+    use game::Campaign;
     use game::Engine;
+    use game::GameEvent;
     use game::GameObject;
+    use game::GameState;
     use game::Id;
     use game::InputHandler;
     use game::InputState;
     use game::Level;
+    use game::LevelGoal;
     use game::ObjectType;
     use game::PlayerState;
     use game::Position;
     use game::Renderer;
+    use game::Stage;
+    use game::WeaponTier;
+    use helpers::parsers;
 
     struct MockRenderer {}
 
@@ -155,7 +662,23 @@ mod tests {
             unimplemented!()
         }
 
-        fn draw_frame(&mut self, _texture_id: &str, _position: Position, _level: &Level) {
+        fn draw_frame(&mut self, _animation: &AnimationState, _position: Position, _level: &Level) {
+            unimplemented!()
+        }
+
+        fn draw_text(&mut self, _text: &str, _position: Position) {
+            unimplemented!()
+        }
+
+        fn fill_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+
+        fn draw_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+
+        fn draw_line(&mut self, _from: Position, _to: Position, _color: (u8, u8, u8)) {
             unimplemented!()
         }
     }
@@ -213,13 +736,15 @@ mod tests {
     #[test]
     fn test_removal_of_list_of_some_removable_objects() {
         //given
-        let mut player_state = PlayerState::new(1, Position::new(0, 0), 0, 0);
+        let mut player_state = PlayerState::new(1, Position::new(0.0, 0.0), 0, 0);
         player_state.is_destroyed = true;
 
         let obj = Some(GameObject {
             player: Some(player_state),
             enemy: None,
             bullet: None,
+            power_up: None,
+            boss: None,
             id: 1,
             object_type: ObjectType::Player,
         });
@@ -355,6 +880,331 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_scoring_kills() {
+        //given
+        let level = create_fake_level();
+        let player = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 64, 64);
+        let mut engine = Engine::new(vec![Some(player)], level, MockRenderer {}, MockInputHandler {});
+
+        //when
+        engine.handle_events(&[
+            GameEvent::EnemyKilled { score: 100, player: 1 },
+            GameEvent::EnemyKilled { score: 100, player: 1 },
+        ]);
+
+        //then
+        assert_eq!(engine.players[0].score.points, 300);
+        assert_eq!(engine.state, GameState::Playing);
+    }
+
+    #[test]
+    fn test_boss_halts_scrolling() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let start = level.position;
+        let boss = GameObject::new(1, start + Position::new(100.0, 50.0), ObjectType::Boss, 200, 100);
+        let mut engine = Engine::new(vec![Some(boss)], level, MockRenderer {}, MockInputHandler {});
+
+        //when
+        engine.update();
+        engine.update();
+
+        //then
+        assert_eq!(engine.level.position, start);
+        assert_eq!(engine.hud.boss_health, Some((100, 100)));
+        assert_eq!(engine.state, GameState::Playing);
+    }
+
+    #[test]
+    fn test_playing_map2_to_the_top() {
+        //given
+        let (game_objects, level, _, _, _) = parsers::map_file::parse("assets/map2.tmx");
+        let mut engine = Engine::new(game_objects, level, MockRenderer {}, MockInputHandler {});
+        engine.set_campaign(Campaign::new(vec![Stage::new("map2", "OPEN SEA", LevelGoal::ReachTop)]));
+        let enemies = |engine: &Engine<MockRenderer, MockInputHandler>| {
+            engine.game_objects.iter().flatten().filter(|game_object| game_object.enemy.is_some()).count()
+        };
+
+        //when
+        engine.update();
+        let after_first_frame = enemies(&engine);
+        let mut frames = 1;
+        while engine.state == GameState::Playing && frames < 5000 {
+            engine.update();
+            frames += 1;
+        }
+
+        //then
+        // the enemies wait above the view, then fly out of its top once it stops there
+        assert_eq!(after_first_frame, 4);
+        assert_eq!(enemies(&engine), 0);
+        match engine.state {
+            GameState::LevelComplete(_) => {}
+            ref state => panic!("Unexpected state after {} frames: {:?}", frames, state),
+        }
+    }
+
+    #[test]
+    fn test_power_ups_wait_above_the_view() {
+        //given
+        let (game_objects, mut level, _, _, _) = parsers::map_file::parse("assets/map1.tmx");
+        level.scroll_speed = 10.0;
+        let mut engine = Engine::new(game_objects, level, MockRenderer {}, MockInputHandler {});
+        let extra_life = |engine: &Engine<MockRenderer, MockInputHandler>| {
+            let mut game_objects = engine.game_objects.iter().flatten();
+            game_objects.find(|game_object| game_object.power_up.is_some()).map(GameObject::position)
+        };
+        let position = extra_life(&engine).unwrap();
+        let start = engine.level.position.y;
+
+        //when
+        let mut positions = Vec::new();
+        while engine.level.position.y > position.y {
+            engine.update();
+            positions.push(extra_life(&engine));
+        }
+
+        //then
+        assert!(position.y < start);
+        assert!(positions.iter().all(|kept| *kept == Some(position)));
+        assert!(engine.level.is_visible(position, (24, 24)));
+    }
+
+    #[test]
+    fn test_defeating_the_boss_clears_the_stage() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let enemy = GameObject::new(1, level.position + Position::new(100.0, 50.0), ObjectType::Enemy, 64, 64);
+        let mut engine = Engine::new(vec![Some(enemy)], level, MockRenderer {}, MockInputHandler {});
+        engine.set_campaign(Campaign::new(vec![Stage::new("map1", "COASTLINE", LevelGoal::DefeatBoss)]));
+
+        //when
+        engine.update();
+
+        //then
+        match engine.state {
+            GameState::LevelComplete(_) => {}
+            ref state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+
+    #[test]
+    fn test_moving_on_to_the_next_stage() {
+        //given
+        let level = create_fake_level();
+        let player = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 64, 64);
+        let mut engine = Engine::new(vec![Some(player)], level, MockRenderer {}, MockInputHandler {});
+        engine.set_campaign(Campaign::new(vec![
+            Stage::new("map1", "COASTLINE", LevelGoal::DefeatBoss),
+            Stage::new("map2", "OPEN SEA", LevelGoal::ReachTop),
+        ]));
+        engine.handle_events(&[GameEvent::EnemyKilled { score: 100, player: 1 }]);
+        engine.players[0].weapon = WeaponTier::Spread;
+
+        //when
+        engine.next_stage();
+        let pending_map = engine.take_pending_map();
+        let player = GameObject::new(2, Position::new(0.0, 0.0), ObjectType::Player, 64, 64);
+        engine.start_level(vec![Some(player)], create_fake_level());
+
+        //then
+        assert_eq!(pending_map, Some(String::from("map2")));
+        assert_eq!(engine.current_map(), Some("map2"));
+        assert_eq!(engine.state, GameState::Transition { ticks: 120 });
+        assert_eq!(engine.players[0].score.points, 100);
+        assert_eq!(engine.players[0].id, 2);
+        assert_eq!(engine.game_objects[0].as_ref().unwrap().player.as_ref().unwrap().weapon, WeaponTier::Spread);
+    }
+
+    #[test]
+    fn test_campaign_ends_after_the_last_stage() {
+        //given
+        let level = create_fake_level();
+        let mut engine = Engine::new(vec![], level, MockRenderer {}, MockInputHandler {});
+        engine.set_campaign(Campaign::new(vec![Stage::new("map1", "COASTLINE", LevelGoal::DefeatBoss)]));
+
+        //when
+        engine.next_stage();
+
+        //then
+        assert_eq!(engine.take_pending_map(), None);
+        assert_eq!(engine.state, GameState::GameOver { name: String::new(), is_entering_name: false });
+    }
+
+    #[test]
+    fn test_respawning_at_checkpoint() {
+        //given
+        let mut level = Level::new(20, 60, vec![]);
+        level.checkpoints = vec![level.position.y - 1.0];
+        let player = GameObject::new(1, level.position + Position::new(300.0, 400.0), ObjectType::Player, 64, 64);
+        let mut engine = Engine::new(vec![Some(player)], level, MockRenderer {}, MockInputHandler {});
+        engine.update();
+        let checkpoint_position = engine.level.position;
+        engine.update();
+
+        //when
+        engine.game_objects = vec![None];
+        engine.handle_events(&[GameEvent::PlayerKilled { player: 1 }]);
+
+        //then
+        assert_eq!(engine.state, GameState::Playing);
+        assert_eq!(engine.players[0].lives, 2);
+        assert_eq!(engine.level.position, checkpoint_position);
+        assert!(engine.game_objects[0].as_ref().unwrap().player.is_some());
+    }
+
+    #[test]
+    fn test_respawning_before_any_checkpoint() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let player = GameObject::new(1, level.position + Position::new(300.0, 400.0), ObjectType::Player, 64, 64);
+        let mut engine = Engine::new(vec![Some(player)], level, MockRenderer {}, MockInputHandler {});
+        engine.update();
+        let position = engine.level.position;
+
+        //when
+        engine.game_objects = vec![None];
+        engine.handle_events(&[GameEvent::PlayerKilled { player: 1 }]);
+
+        //then
+        assert_eq!(engine.state, GameState::Playing);
+        assert_eq!(engine.players[0].lives, 2);
+        assert_eq!(engine.level.position, position);
+        let plane = engine.game_objects.iter().flatten().find(|game_object| game_object.player.is_some()).unwrap();
+        assert_eq!(plane.id, 1);
+        assert!(engine.level.is_visible(plane.position(), plane.size()));
+    }
+
+    #[test]
+    fn test_respawning_leaves_the_partner_alone() {
+        //given
+        let mut level = Level::new(20, 60, vec![]);
+        level.checkpoints = vec![level.position.y - 1.0];
+        let first = GameObject::new(1, level.position + Position::new(200.0, 400.0), ObjectType::Player, 64, 64);
+        let mut second = GameObject::new(2, level.position + Position::new(400.0, 400.0), ObjectType::Player, 64, 64);
+        second.player.as_mut().unwrap().index = 1;
+        let mut engine = Engine::new(vec![], Level::new(20, 60, vec![]), MockRenderer {}, MockInputHandler {});
+        engine.set_player_count(2);
+        engine.restart(vec![Some(first), Some(second)], level);
+        engine.update();
+        engine.update();
+        engine.handle_events(&[GameEvent::EnemyKilled { score: 100, player: 2 }]);
+        let partner = engine.game_objects[1].clone();
+        let position = engine.level.position;
+
+        //when
+        engine.game_objects[0] = None;
+        engine.handle_events(&[GameEvent::PlayerKilled { player: 1 }]);
+
+        //then
+        assert_eq!(engine.state, GameState::Playing);
+        assert_eq!(engine.level.position, position);
+        assert_eq!(engine.players[0].lives, 2);
+        assert_eq!(engine.players[1].lives, 3);
+        assert_eq!(engine.players[1].score.points, 100);
+        assert_eq!(engine.game_objects[1], partner);
+        assert!(engine.game_objects.iter().flatten().any(|game_object| game_object.id == 1));
+    }
+
+    #[test]
+    fn test_co_op_goes_on_while_a_player_is_left() {
+        //given
+        let level = create_fake_level();
+        let first = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 64, 64);
+        let mut second = GameObject::new(2, Position::new(100.0, 0.0), ObjectType::Player, 64, 64);
+        second.player.as_mut().unwrap().index = 1;
+        let mut engine = Engine::new(vec![], create_fake_level(), MockRenderer {}, MockInputHandler {});
+        engine.set_player_count(2);
+        engine.restart(vec![Some(first), Some(second)], level);
+        engine.handle_events(&[GameEvent::EnemyKilled { score: 100, player: 2 }]);
+        engine.players[0].lives = 0;
+        engine.players[1].lives = 0;
+
+        //when
+        engine.game_objects[0] = None;
+        engine.handle_events(&[GameEvent::PlayerKilled { player: 1 }]);
+        let state_with_one_left = engine.state.clone();
+        engine.game_objects[1] = None;
+        engine.handle_events(&[GameEvent::PlayerKilled { player: 2 }]);
+
+        //then
+        assert_eq!(state_with_one_left, GameState::Playing);
+        assert_eq!(engine.players[0].score.points, 0);
+        assert_eq!(engine.players[1].score.points, 100);
+        assert_eq!(engine.state, GameState::GameOver { name: String::new(), is_entering_name: true });
+    }
+
+    #[test]
+    fn test_second_plane_is_left_out_of_single_player() {
+        //given
+        let level = create_fake_level();
+        let first = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 64, 64);
+        let mut second = GameObject::new(2, Position::new(100.0, 0.0), ObjectType::Player, 64, 64);
+        second.player.as_mut().unwrap().index = 1;
+
+        //when
+        let engine = Engine::new(vec![Some(first), Some(second)], level, MockRenderer {}, MockInputHandler {});
+
+        //then
+        assert_eq!(engine.players.len(), 1);
+        assert!(engine.game_objects[0].is_some());
+        assert_eq!(engine.game_objects[1], None);
+    }
+
+    #[test]
+    fn test_loaded_state_plays_on_the_same() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let player = GameObject::new(1, level.position + Position::new(300.0, 400.0), ObjectType::Player, 64, 64);
+        let enemy = GameObject::new(2, level.position + Position::new(100.0, 50.0), ObjectType::Enemy, 64, 64);
+        let mut engine = Engine::new(vec![Some(player), Some(enemy)], level, MockRenderer {}, MockInputHandler {});
+        engine.step(&[InputState::Up(0)]);
+        let saved = engine.save_state();
+        let play = |engine: &mut Engine<MockRenderer, MockInputHandler>| {
+            for _ in 0..10 {
+                engine.step(&[InputState::Left(0), InputState::Up(0)]);
+            }
+            (engine.game_objects.clone(), engine.level.position, engine.players.clone())
+        };
+        let first_run = play(&mut engine);
+
+        //when
+        engine.load_state(&saved);
+        let second_run = play(&mut engine);
+
+        //then
+        assert_eq!(first_run, second_run);
+    }
+
+    #[test]
+    fn test_entering_name_on_game_over() {
+        //given
+        let level = create_fake_level();
+        let player = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 64, 64);
+        let mut engine = Engine::new(vec![Some(player)], level, MockRenderer {}, MockInputHandler {});
+        engine.handle_events(&[GameEvent::EnemyKilled { score: 100, player: 1 }]);
+        engine.players[0].lives = 0;
+        engine.game_objects = vec![None];
+        engine.handle_events(&[GameEvent::PlayerKilled { player: 1 }]);
+
+        //when
+        engine.enter_name(&[
+            InputState::Character('a'),
+            InputState::Character('!'),
+            InputState::Character('c'),
+            InputState::Character('x'),
+            InputState::Backspace,
+            InputState::Character('e'),
+            InputState::Confirm,
+        ]);
+
+        //then
+        assert_eq!(engine.state, GameState::GameOver { name: String::from("ACE"), is_entering_name: false });
+        assert_eq!(engine.high_scores.entries[0].name, "ACE");
+        assert_eq!(engine.high_scores.entries[0].score, 100);
+    }
+
     fn create_fake_object(id: Id) -> GameObject {
         GameObject {
             id,
@@ -362,6 +1212,8 @@ mod tests {
             player: None,
             enemy: None,
             bullet: None,
+            power_up: None,
+            boss: None,
         }
     }
 
@@ -1,6 +1,8 @@
+use game::BossState;
 use game::BulletState;
 use game::CollisionState;
 use game::EnemyState;
+use game::GameEvent;
 use game::GameObject;
 use game::Id;
 use game::InputState;
@@ -8,11 +10,16 @@ use game::Level;
 use game::ObjectType;
 use game::PlayerState;
 use game::Position;
+use game::PowerUpKind;
+use game::PowerUpState;
+use game::Rect;
 use game::Renderer;
 use game::Velocity;
+use game::World;
 use std::fmt::Debug;
 use std::fmt::Error;
 use std::fmt::Formatter;
+use SCREEN_SIZE;
 
 impl GameObject {
     pub fn new(id: Id, position: Position, object_type: ObjectType, width: u32, height: u32) -> Self {
@@ -21,39 +28,42 @@ impl GameObject {
             player: None,
             enemy: None,
             bullet: None,
+            power_up: None,
+            boss: None,
             object_type,
         };
 
         match object_type {
             ObjectType::Enemy => object.enemy = Some(EnemyState::new(id, position, width, height)),
             ObjectType::Player => object.player = Some(PlayerState::new(id, position, width, height)),
+            ObjectType::PowerUp => object.power_up = Some(PowerUpState::new(position, PowerUpKind::Weapon)),
+            ObjectType::Boss => object.boss = Some(BossState::new(id, position, width, height)),
             _ => panic!("unknown type: {:?}", object_type),
         }
         object
     }
 
-    pub fn new_bullet(id: Id, position: Position, shooter_type: ObjectType, shooter_id: Id) -> Self {
-        let velocity = match shooter_type {
-            ObjectType::Enemy => Velocity::new(0, 4),
-            ObjectType::Player => Velocity::new(0, -4),
-            _ => panic!("Unknown shooter"),
-        };
-
-        let bullet = Some(BulletState {
-            position: position + Velocity::new(0, -35),
-            shooter_type,
-            shooter_id,
-            velocity,
-            is_destroyed: false,
-        });
-        let object_type = ObjectType::Bullet;
+    pub fn from_bullet(id: Id, bullet: BulletState) -> Self {
+        GameObject {
+            id,
+            player: None,
+            enemy: None,
+            bullet: Some(bullet),
+            power_up: None,
+            boss: None,
+            object_type: ObjectType::Bullet,
+        }
+    }
 
+    pub fn new_power_up(id: Id, position: Position, kind: PowerUpKind) -> Self {
         GameObject {
             id,
             player: None,
             enemy: None,
-            bullet,
-            object_type,
+            bullet: None,
+            power_up: Some(PowerUpState::new(position, kind)),
+            boss: None,
+            object_type: ObjectType::PowerUp,
         }
     }
 
@@ -68,65 +78,119 @@ impl GameObject {
     }
 
     pub fn draw(&mut self, renderer: &mut Renderer, level: &Level) {
-        match (&mut self.player, &mut self.enemy, &mut self.bullet) {
-            (Some(ref mut player), _, _) => player.draw(renderer, level),
-            (_, Some(ref mut enemy), _) => enemy.draw(renderer, level),
-            (_, _, Some(ref mut bullet)) => bullet.draw(renderer, level),
+        match (&mut self.player, &mut self.enemy, &mut self.bullet, &mut self.power_up, &mut self.boss) {
+            (Some(ref mut player), _, _, _, _) => player.draw(renderer, level),
+            (_, Some(ref mut enemy), _, _, _) => enemy.draw(renderer, level),
+            (_, _, Some(ref mut bullet), _, _) => bullet.draw(renderer, level),
+            (_, _, _, Some(ref mut power_up), _) => power_up.draw(renderer, level),
+            (_, _, _, _, Some(ref mut boss)) => boss.draw(renderer, level),
             _ => panic!("Incorrectly constructed object"),
         }
     }
 
-    pub fn update(&mut self, new_objects: &mut Vec<Option<GameObject>>, level: &Level) {
-        let new_object = match (&mut self.player, &mut self.enemy, &mut self.bullet) {
-            (Some(ref mut player), _, _) => player.update(level),
-            (_, Some(ref mut enemy), _) => enemy.update(),
-            (_, _, Some(ref mut bullet)) => bullet.update(),
+    pub fn update(&mut self, new_objects: &mut Vec<Option<GameObject>>, world: &mut World) {
+        let level = world.level;
+        let spawned = match (&mut self.player, &mut self.enemy, &mut self.bullet, &mut self.power_up, &mut self.boss) {
+            (Some(ref mut player), _, _, _, _) => player.update(level),
+            (_, Some(ref mut enemy), _, _, _) => enemy.update(world),
+            (_, _, Some(ref mut bullet), _, _) => bullet.update(&world.enemies),
+            (_, _, _, Some(ref mut power_up), _) => power_up.update(),
+            (_, _, _, _, Some(ref mut boss)) => boss.update(world),
             _ => panic!("Incorrectly constructed or unknown object"),
         };
 
-        if new_object.is_some() {
-            new_objects.push(new_object);
-        }
+        new_objects.extend(spawned.into_iter().map(Some));
 
         let position = self.position();
 
-        if position.y < level.position.y {
+        // objects above the view wait until it scrolls to them, the ones it scrolled past are gone
+        if position.y > level.position.y + SCREEN_SIZE.1 as f32 && self.player.is_none() {
+            self.destroy();
+        }
+        // bullets never come back once they left the view, whichever side they left on
+        if self.bullet.is_some() && !self.is_visible(level) {
             self.destroy();
         }
+        if self.enemy.iter().any(|enemy| enemy.has_left_the_view(level)) {
+            self.destroy();
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match (&self.player, &self.enemy, &self.bullet, &self.power_up, &self.boss) {
+            (Some(ref player), _, _, _, _) => player.position,
+            (_, Some(ref enemy), _, _, _) => enemy.position,
+            (_, _, Some(ref bullet), _, _) => bullet.position,
+            (_, _, _, Some(ref power_up), _) => power_up.position,
+            (_, _, _, _, Some(ref boss)) => boss.position,
+            _ => panic!("Incorrectly constructed or unknown object"),
+        }
     }
 
-    fn position(&self) -> Position {
-        match (&self.player, &self.enemy, &self.bullet) {
-            (Some(ref player), _, _) => player.position,
-            (_, Some(ref enemy), _) => enemy.position,
-            (_, _, Some(ref bullet)) => bullet.position,
+    pub fn velocity(&self) -> Velocity {
+        match (&self.player, &self.enemy, &self.bullet, &self.boss) {
+            (Some(ref player), _, _, _) => player.velocity,
+            (_, Some(ref enemy), _, _) => enemy.velocity,
+            (_, _, Some(ref bullet), _) => bullet.velocity,
+            (_, _, _, Some(ref boss)) => boss.velocity,
+            _ => Velocity::new(0.0, 0.0),
+        }
+    }
+
+    // The sprite's size as far as culling goes
+    pub fn size(&self) -> (u32, u32) {
+        match (&self.player, &self.enemy, &self.bullet, &self.power_up, &self.boss) {
+            (Some(ref player), _, _, _, _) => player.size(),
+            (_, Some(ref enemy), _, _, _) => enemy.size(),
+            (_, _, Some(ref bullet), _, _) => bullet.size(),
+            (_, _, _, Some(ref power_up), _) => power_up.size(),
+            (_, _, _, _, Some(ref boss)) => boss.size(),
             _ => panic!("Incorrectly constructed or unknown object"),
         }
     }
 
+    pub fn is_visible(&self, level: &Level) -> bool {
+        level.is_visible(self.position(), self.size())
+    }
+
+    // What collisions are checked against, in level coordinates
+    pub fn hitboxes(&self) -> Vec<Rect> {
+        self.collider().map_or_else(Vec::new, CollisionState::collision_rects)
+    }
+
     pub fn is_destroyed(&self) -> bool {
-        match (&self.player, &self.enemy, &self.bullet) {
-            (Some(ref player), _, _) => player.is_destroyed,
-            (_, Some(ref enemy), _) => enemy.is_destroyed,
-            (_, _, Some(ref bullet)) => bullet.is_destroyed,
+        match (&self.player, &self.enemy, &self.bullet, &self.power_up, &self.boss) {
+            (Some(ref player), _, _, _, _) => player.is_destroyed,
+            (_, Some(ref enemy), _, _, _) => enemy.is_destroyed,
+            (_, _, Some(ref bullet), _, _) => bullet.is_destroyed,
+            (_, _, _, Some(ref power_up), _) => power_up.is_destroyed,
+            (_, _, _, _, Some(ref boss)) => boss.is_destroyed,
             _ => false,
         }
     }
 
     pub fn destroy(&mut self) {
-        match (&mut self.player, &mut self.enemy, &mut self.bullet) {
-            (Some(ref mut player), _, _) => player.is_destroyed = true,
-            (_, Some(ref mut enemy), _) => enemy.is_destroyed = true,
-            (_, _, Some(ref mut bullet)) => bullet.is_destroyed = true,
+        match (&mut self.player, &mut self.enemy, &mut self.bullet, &mut self.power_up, &mut self.boss) {
+            (Some(ref mut player), _, _, _, _) => player.is_destroyed = true,
+            (_, Some(ref mut enemy), _, _, _) => enemy.is_destroyed = true,
+            (_, _, Some(ref mut bullet), _, _) => bullet.is_destroyed = true,
+            (_, _, _, Some(ref mut power_up), _) => power_up.is_destroyed = true,
+            (_, _, _, _, Some(ref mut boss)) => boss.is_destroyed = true,
             _ => {}
         }
     }
 
-    fn collided_with(&mut self, collider: &mut GameObject) {
+    fn collided_with(&mut self, collider: &mut GameObject, events: &mut Vec<GameEvent>) {
+        if self.is_power_up() || collider.is_power_up() {
+            self.picked_up_by(collider, events);
+            collider.picked_up_by(self, events);
+            return;
+        }
+
         let mut hit = false;
         if self.is_bullet() && !collider.is_bullet() {
             hit = match &self.bullet {
-                Some(bullet) if !bullet.is_fired_by(collider) => {
+                Some(bullet) if bullet.can_hit(collider) => {
                     println!("Object {:?} was hit by object {:?}", collider, self);
                     true
                 }
@@ -134,31 +198,87 @@ impl GameObject {
             }
         } else if collider.is_bullet() && !self.is_bullet() {
             hit = match &collider.bullet {
-                Some(bullet) if !bullet.is_fired_by(self) => {
+                Some(bullet) if bullet.can_hit(self) => {
                     println!("Object {:?} was hit by object {:?}", self, collider);
                     true
                 }
                 _ => false,
             }
         } else if !collider.is_bullet() && !self.is_bullet() {
-            println!("Object {:?} collided with object {:?}", self, collider);
-            hit = true;
+            // no friendly fire: planes on the same side fly through each other
+            hit = self.object_type.is_hostile() != collider.object_type.is_hostile();
+            if hit {
+                println!("Object {:?} collided with object {:?}", self, collider);
+            }
         }
 
         if hit {
-            collider.destroy();
-            self.destroy();
+            if !collider.is_piercing() {
+                collider.take_hit();
+            }
+            if !self.is_piercing() {
+                self.take_hit();
+            }
+
+            events.extend(self.killed_by(collider));
+            events.extend(collider.killed_by(self));
+        }
+    }
+
+    // Bosses wear down one hit at a time, invulnerable planes shrug it off, everything else goes down at once
+    fn take_hit(&mut self) {
+        if matches!(self.player, Some(ref player) if player.is_invulnerable) {
+            return;
+        }
+        match self.boss {
+            Some(ref mut boss) => boss.damage(1),
+            None => self.destroy(),
         }
     }
 
-    pub fn check_collision(&mut self, collider: &mut GameObject) -> bool {
+    // Enemies only score when a player, or one of their bullets, takes them down
+    fn killed_by(&self, killer: &GameObject) -> Vec<GameEvent> {
+        let scorer = match (&killer.player, &killer.bullet) {
+            (Some(_), _) => Some(killer.id),
+            (_, Some(bullet)) if bullet.shooter_type == ObjectType::Player => Some(bullet.shooter_id),
+            _ => None,
+        };
+
+        match (&self.player, &self.enemy, &self.boss, scorer) {
+            (Some(player), _, _, _) if player.is_destroyed => vec![GameEvent::PlayerKilled { player: self.id }],
+            (Some(_), _, _, _) => Vec::new(),
+            (_, Some(enemy), _, Some(player)) => {
+                let mut events = vec![GameEvent::EnemyKilled { score: enemy.score, player }];
+                if let Some(kind) = enemy.drop {
+                    events.push(GameEvent::PowerUpDropped { kind, position: enemy.position });
+                }
+                events
+            }
+            (_, _, Some(boss), Some(player)) if boss.is_destroyed => vec![GameEvent::EnemyKilled { score: boss.score, player }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn picked_up_by(&mut self, collector: &mut GameObject, events: &mut Vec<GameEvent>) {
+        if let (Some(power_up), Some(player)) = (&mut self.power_up, &mut collector.player) {
+            player.collect(power_up.kind);
+            power_up.is_destroyed = true;
+            events.push(GameEvent::PowerUpCollected { kind: power_up.kind, player: collector.id });
+        }
+    }
+
+    pub fn check_collision(&mut self, collider: &mut GameObject, events: &mut Vec<GameEvent>) -> bool {
+        if self.is_destroyed() || collider.is_destroyed() {
+            return false;
+        }
+
         let collided = match (self.collider(), collider.collider()) {
             (Some(a), Some(b)) => a.is_colliding(b),
             _ => false,
         };
 
         if collided {
-            self.collided_with(collider);
+            self.collided_with(collider, events);
         }
 
         collided
@@ -168,16 +288,48 @@ impl GameObject {
         self.object_type == ObjectType::Bullet
     }
 
-    fn collider(&self) -> Option<&CollisionState> {
-        match (&self.player, &self.enemy, &self.bullet) {
-            (Some(ref player), _, _) => Some(player),
-            (_, Some(ref enemy), _) => Some(enemy),
-            (_, _, Some(ref bullet)) => Some(bullet),
+    fn is_power_up(&self) -> bool {
+        self.object_type == ObjectType::PowerUp
+    }
+
+    fn is_piercing(&self) -> bool {
+        match self.bullet {
+            Some(ref bullet) => bullet.is_piercing(),
+            None => false,
+        }
+    }
+
+    fn collider(&self) -> Option<&dyn CollisionState> {
+        match (&self.player, &self.enemy, &self.bullet, &self.power_up, &self.boss) {
+            (Some(ref player), _, _, _, _) if player.is_noclip => None,
+            (Some(ref player), _, _, _, _) => Some(player),
+            (_, Some(ref enemy), _, _, _) => Some(enemy),
+            (_, _, Some(ref bullet), _, _) => Some(bullet),
+            (_, _, _, Some(ref power_up), _) => Some(power_up),
+            (_, _, _, _, Some(ref boss)) => Some(boss),
             _ => None,
         }
     }
 }
 
+impl ObjectType {
+    pub fn parse(name: &str) -> ObjectType {
+        match name {
+            "Player" => ObjectType::Player,
+            "Enemy" => ObjectType::Enemy,
+            "Bullet" => ObjectType::Bullet,
+            "PowerUp" => ObjectType::PowerUp,
+            "Boss" => ObjectType::Boss,
+            _ => ObjectType::Unknown,
+        }
+    }
+
+    // Enemies and bosses fight on the same side
+    pub fn is_hostile(self) -> bool {
+        matches!(self, ObjectType::Enemy | ObjectType::Boss)
+    }
+}
+
 impl Debug for GameObject {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         let position = self.position();
@@ -190,3 +342,182 @@ impl Debug for GameObject {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use game::BulletKind;
+    use game::BulletPattern;
+    use game::BulletState;
+    use game::GameEvent;
+    use game::GameObject;
+    use game::Level;
+    use game::ObjectType;
+    use game::PatternStep;
+    use game::Position;
+    use game::PowerUpKind;
+    use game::Rng;
+    use game::Velocity;
+    use game::WeaponTier;
+    use game::World;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_player_bullet_kills_enemy() {
+        //given
+        let player = GameObject::new(1, Position::new(0.0, 200.0), ObjectType::Player, 65, 65);
+        let mut enemy = GameObject::new(2, Position::new(0.0, 0.0), ObjectType::Enemy, 65, 65);
+        let mut bullet = GameObject::from_bullet(3, WeaponTier::Single.fire(Position::new(10.0, 40.0), player.id).remove(0));
+        let mut events = Vec::new();
+
+        //when
+        let collided = bullet.check_collision(&mut enemy, &mut events);
+
+        //then
+        assert!(collided);
+        assert!(enemy.is_destroyed());
+        assert_eq!(events, vec![GameEvent::EnemyKilled { score: 100, player: 1 }]);
+    }
+
+    #[test]
+    fn test_bullets_wear_down_a_boss() {
+        //given
+        let mut boss = GameObject::new(2, Position::new(0.0, 0.0), ObjectType::Boss, 200, 100);
+        boss.boss.as_mut().unwrap().health = 2;
+        let fire = || GameObject::from_bullet(3, WeaponTier::Single.fire(Position::new(10.0, 100.0), 1).remove(0));
+        let mut events = Vec::new();
+
+        //when
+        fire().check_collision(&mut boss, &mut events);
+        let survived_first_hit = !boss.is_destroyed();
+        fire().check_collision(&mut boss, &mut events);
+
+        //then
+        assert!(survived_first_hit);
+        assert!(boss.is_destroyed());
+        assert_eq!(events, vec![GameEvent::EnemyKilled { score: 5000, player: 1 }]);
+    }
+
+    #[test]
+    fn test_enemy_rams_player() {
+        //given
+        let mut player = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 65, 65);
+        let mut enemy = GameObject::new(2, Position::new(10.0, 10.0), ObjectType::Enemy, 65, 65);
+        let mut events = Vec::new();
+
+        //when
+        player.check_collision(&mut enemy, &mut events);
+
+        //then
+        assert_eq!(events, vec![GameEvent::PlayerKilled { player: 1 }, GameEvent::EnemyKilled { score: 100, player: 1 }]);
+    }
+
+    #[test]
+    fn test_enemy_drops_power_up() {
+        //given
+        let mut player = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 65, 65);
+        let mut enemy = GameObject::new(2, Position::new(10.0, 10.0), ObjectType::Enemy, 65, 65);
+        enemy.enemy.as_mut().unwrap().drop = Some(PowerUpKind::Bomb);
+        let mut events = Vec::new();
+
+        //when
+        enemy.check_collision(&mut player, &mut events);
+
+        //then
+        assert_eq!(events[1], GameEvent::PowerUpDropped { kind: PowerUpKind::Bomb, position: Position::new(10.0, 10.0) });
+    }
+
+    #[test]
+    fn test_collecting_power_up() {
+        //given
+        let mut player = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 65, 65);
+        let mut power_up = GameObject::new_power_up(2, Position::new(20.0, 20.0), PowerUpKind::Weapon);
+        let mut events = Vec::new();
+
+        //when
+        power_up.check_collision(&mut player, &mut events);
+
+        //then
+        assert!(power_up.is_destroyed());
+        assert!(!player.is_destroyed());
+        assert_eq!(player.player.unwrap().weapon, WeaponTier::Double);
+        assert_eq!(events, vec![GameEvent::PowerUpCollected { kind: PowerUpKind::Weapon, player: 1 }]);
+    }
+
+    #[test]
+    fn test_players_do_not_collide() {
+        //given
+        let mut first = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Player, 65, 65);
+        let mut second = GameObject::new(2, Position::new(10.0, 10.0), ObjectType::Player, 65, 65);
+        let mut events = Vec::new();
+
+        //when
+        let collided = first.check_collision(&mut second, &mut events);
+
+        //then
+        assert!(collided);
+        assert!(!first.is_destroyed());
+        assert!(!second.is_destroyed());
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_laser_pierces() {
+        //given
+        let mut enemy = GameObject::new(1, Position::new(0.0, 0.0), ObjectType::Enemy, 65, 65);
+        let bullet = WeaponTier::Laser.fire(Position::new(0.0, 40.0), 2).remove(0);
+        let mut laser = GameObject::from_bullet(3, bullet);
+        let mut events = Vec::new();
+
+        //when
+        laser.check_collision(&mut enemy, &mut events);
+
+        //then
+        assert!(enemy.is_destroyed());
+        assert!(!laser.is_destroyed());
+    }
+
+    #[test]
+    fn test_enemy_fires_pattern() {
+        //given
+        let mut enemy = GameObject::new(1, Position::new(100.0, 1400.0), ObjectType::Enemy, 64, 64);
+        enemy.enemy.as_mut().unwrap().pattern = Some(String::from("ring"));
+        let mut patterns = HashMap::new();
+        let steps = vec![PatternStep::Ring { count: 4, angle: 0.0, speed: 2.0 }];
+        patterns.insert(String::from("ring"), BulletPattern::new(&steps, false));
+        let level = Level::new(20, 60, vec![]);
+        let mut rng = Rng::new(1);
+        let mut world = World { level: &level, players: vec![], enemies: vec![], patterns: &patterns, rng: &mut rng };
+        let mut new_objects = Vec::new();
+
+        //when
+        enemy.update(&mut new_objects, &mut world);
+        enemy.update(&mut new_objects, &mut world);
+
+        //then
+        assert_eq!(new_objects.len(), 4);
+        assert_eq!(new_objects[0].as_ref().unwrap().position(), Position::new(127.0, 1425.0));
+        assert_eq!(enemy.enemy.unwrap().pattern, None);
+    }
+
+    #[test]
+    fn test_bullets_leaving_the_view_sideways() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let velocity = Velocity::new(-5.0, 0.0);
+        let leaving = BulletState::new(Position::new(-30.0, 1500.0), velocity, BulletKind::Orb, ObjectType::Enemy, 1);
+        let staying = BulletState::new(Position::new(-5.0, 1500.0), velocity, BulletKind::Orb, ObjectType::Enemy, 1);
+        let mut leaving = GameObject::from_bullet(2, leaving);
+        let mut staying = GameObject::from_bullet(3, staying);
+        let patterns = HashMap::new();
+        let mut rng = Rng::new(1);
+        let mut world = World { level: &level, players: vec![], enemies: vec![], patterns: &patterns, rng: &mut rng };
+
+        //when
+        leaving.update(&mut Vec::new(), &mut world);
+        staying.update(&mut Vec::new(), &mut world);
+
+        //then
+        assert!(leaving.is_destroyed());
+        assert!(!staying.is_destroyed());
+    }
+}
@@ -0,0 +1,244 @@
+use game::HighScores;
+use game::Hud;
+use game::PlayerStatus;
+use game::Position;
+use game::Renderer;
+use game::Tally;
+use SCREEN_SIZE;
+
+const START_BOMBS: u8 = 3;
+const MARGIN: f32 = 10.0;
+const LINE_HEIGHT: f32 = 20.0;
+// players after the first are listed on the right, below the frame counter
+const SIDE_COLUMN_WIDTH: f32 = 200.0;
+const BOSS_BAR_WIDTH: u32 = 300;
+const BOSS_BAR_HEIGHT: u32 = 8;
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            bombs: START_BOMBS,
+            boss_health: None,
+            fps: 0,
+        }
+    }
+
+    pub fn set_fps(&mut self, fps: u32) {
+        self.fps = fps;
+    }
+
+    pub fn draw<R: Renderer>(&self, renderer: &mut R, players: &[PlayerStatus]) {
+        let (screen_width, _) = SCREEN_SIZE;
+
+        for (index, player) in players.iter().enumerate() {
+            let label = if players.len() > 1 { format!("{}P ", index + 1) } else { String::new() };
+            let score = &player.score;
+            let combo = if score.multiplier > 1 { format!(" x{}", score.multiplier) } else { String::new() };
+
+            let mut lines = vec![format!("{}SCORE {:06}{}", label, score.points, combo), format!("LIVES {}", player.lives)];
+            // bombs are shared, they go with the first player
+            if index == 0 {
+                lines.push(format!("BOMBS {}", self.bombs));
+            }
+            lines.push(player.weapon.name().to_string());
+
+            let (x, y) = match index {
+                0 => (MARGIN, MARGIN),
+                _ => (screen_width as f32 - SIDE_COLUMN_WIDTH, MARGIN + (1 + 4 * (index - 1)) as f32 * LINE_HEIGHT),
+            };
+            for (i, line) in lines.iter().enumerate() {
+                renderer.draw_text(line, Position::new(x, y + i as f32 * LINE_HEIGHT));
+            }
+        }
+        renderer.draw_text(&format!("FPS {:3}", self.fps), Position::new(screen_width as f32 - 100.0, MARGIN));
+
+        if let Some((health, max_health)) = self.boss_health {
+            self.draw_boss_health(renderer, health, max_health);
+        }
+    }
+
+    // Bar across the top of the screen that empties as the boss takes damage
+    fn draw_boss_health<R: Renderer>(&self, renderer: &mut R, health: u32, max_health: u32) {
+        let x = (SCREEN_SIZE.0 - BOSS_BAR_WIDTH) as f32 / 2.0;
+        let filled = BOSS_BAR_WIDTH * health / max_health.max(1);
+
+        renderer.draw_text("BOSS", Position::new(x, MARGIN));
+        renderer.fill_rect(Position::new(x, MARGIN + LINE_HEIGHT), BOSS_BAR_WIDTH, BOSS_BAR_HEIGHT, (80, 0, 0));
+        renderer.fill_rect(Position::new(x, MARGIN + LINE_HEIGHT), filled, BOSS_BAR_HEIGHT, (220, 30, 30));
+    }
+
+    pub fn draw_tally<R: Renderer>(&self, renderer: &mut R, tallies: &[Tally]) {
+        let mut lines = vec![String::from("LEVEL COMPLETE")];
+        for (index, tally) in tallies.iter().enumerate() {
+            if tallies.len() > 1 {
+                lines.push(String::new());
+                lines.push(format!("PLAYER {}", index + 1));
+            }
+            lines.push(format!("KILLS       {:6}", tally.kills));
+            lines.push(format!("BEST COMBO  {:>6}", format!("x{}", tally.max_multiplier)));
+            lines.push(format!("LIVES BONUS {:6}", tally.lives_bonus));
+            lines.push(format!("TOTAL       {:6}", tally.points));
+        }
+        lines.push(String::new());
+        lines.push(String::from("PRESS ENTER"));
+        Self::draw_centered_lines(renderer, &lines);
+    }
+
+    pub fn draw_transition<R: Renderer>(&self, renderer: &mut R, stage_number: usize, name: &str) {
+        let lines = [format!("STAGE {}", stage_number), String::new(), name.to_string()];
+        Self::draw_centered_lines(renderer, &lines);
+    }
+
+    pub fn draw_game_over<R: Renderer>(&self, renderer: &mut R, high_scores: &HighScores, name: &str, is_entering_name: bool) {
+        let mut lines = vec![String::from("GAME OVER"), String::new()];
+        if is_entering_name {
+            lines.push(String::from("NEW HIGH SCORE!"));
+            lines.push(format!("NAME: {}_", name));
+        } else {
+            lines.extend(
+                high_scores
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entry)| format!("{:2}. {:8} {:6}", i + 1, entry.name, entry.score)),
+            );
+        }
+        Self::draw_centered_lines(renderer, &lines);
+    }
+
+    // Lines start a fixed distance left of the middle, the renderer decides how wide glyphs are
+    fn draw_centered_lines<R: Renderer>(renderer: &mut R, lines: &[String]) {
+        let (screen_width, screen_height) = SCREEN_SIZE;
+        let x = screen_width as f32 / 2.0 - 100.0;
+        let y = (screen_height as f32 - lines.len() as f32 * LINE_HEIGHT) / 2.0;
+
+        for (i, line) in lines.iter().enumerate() {
+            renderer.draw_text(line, Position::new(x, y + i as f32 * LINE_HEIGHT));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::AnimationState;
+    use game::HighScores;
+    use game::Hud;
+    use game::Level;
+    use game::Position;
+    use game::PlayerStatus;
+    use game::Renderer;
+
+    struct MockRenderer {
+        texts: Vec<(String, Position)>,
+        rects: Vec<(Position, u32, u32)>,
+    }
+
+    impl Renderer for MockRenderer {
+        fn clear_scene(&mut self) {
+            unimplemented!()
+        }
+
+        fn draw_scene(&mut self) {
+            unimplemented!()
+        }
+
+        fn draw_texture(&mut self, _texture_id: &str, _position: Position, _level: &Level) {
+            unimplemented!()
+        }
+
+        fn draw_tile(&mut self, _texture_id: &str, _position: Position, _tile_id: u8) {
+            unimplemented!()
+        }
+
+        fn draw_frame(&mut self, _animation: &AnimationState, _position: Position, _level: &Level) {
+            unimplemented!()
+        }
+
+        fn draw_text(&mut self, text: &str, position: Position) {
+            self.texts.push((text.to_string(), position));
+        }
+
+        fn fill_rect(&mut self, position: Position, width: u32, height: u32, _color: (u8, u8, u8)) {
+            self.rects.push((position, width, height));
+        }
+
+        fn draw_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+
+        fn draw_line(&mut self, _from: Position, _to: Position, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_drawing() {
+        //given
+        let mut hud = Hud::new();
+        hud.set_fps(59);
+        let mut player = PlayerStatus::new(1);
+        player.score.add_kill(1250);
+        let mut renderer = MockRenderer { texts: Vec::new(), rects: Vec::new() };
+
+        //when
+        hud.draw(&mut renderer, &[player]);
+
+        //then
+        let texts: Vec<&str> = renderer.texts.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts, vec!["SCORE 001250", "LIVES 3", "BOMBS 3", "SINGLE", "FPS  59"]);
+        assert_eq!(renderer.texts[0].1, Position::new(10.0, 10.0));
+        assert!(renderer.rects.is_empty());
+    }
+
+    #[test]
+    fn test_drawing_two_players() {
+        //given
+        let hud = Hud::new();
+        let mut second = PlayerStatus::new(2);
+        second.lives = 1;
+        let mut renderer = MockRenderer { texts: Vec::new(), rects: Vec::new() };
+
+        //when
+        hud.draw(&mut renderer, &[PlayerStatus::new(1), second]);
+
+        //then
+        let texts: Vec<&str> = renderer.texts.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["1P SCORE 000000", "LIVES 3", "BOMBS 3", "SINGLE", "2P SCORE 000000", "LIVES 1", "SINGLE", "FPS   0"]
+        );
+        assert_eq!(renderer.texts[4].1, Position::new(600.0, 30.0));
+    }
+
+    #[test]
+    fn test_drawing_boss_health() {
+        //given
+        let mut hud = Hud::new();
+        hud.boss_health = Some((25, 100));
+        let mut renderer = MockRenderer { texts: Vec::new(), rects: Vec::new() };
+
+        //when
+        hud.draw(&mut renderer, &[PlayerStatus::new(1)]);
+
+        //then
+        assert_eq!(renderer.texts.last().unwrap().0, "BOSS");
+        let widths: Vec<u32> = renderer.rects.iter().map(|(_, width, _)| *width).collect();
+        assert_eq!(widths, vec![300, 75]);
+    }
+
+    #[test]
+    fn test_drawing_high_scores() {
+        //given
+        let hud = Hud::new();
+        let mut high_scores = HighScores::default();
+        high_scores.insert("ACE", 12000);
+        let mut renderer = MockRenderer { texts: Vec::new(), rects: Vec::new() };
+
+        //when
+        hud.draw_game_over(&mut renderer, &high_scores, "", false);
+
+        //then
+        let texts: Vec<&str> = renderer.texts.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts, vec!["GAME OVER", "", " 1. ACE       12000"]);
+    }
+}
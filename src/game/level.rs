@@ -1,56 +1,257 @@
+use game::ImageLayer;
 use game::Level;
+use game::LevelLayer;
 use game::Position;
 use game::Renderer;
+use game::TileLayer;
+use game::Velocity;
 use SCREEN_SIZE;
 
+const SCROLL_SPEED: f32 = 1.0;
+pub const TILE_SIZE: u32 = 32;
+// tiles are drawn in squares of this many tiles a side, a renderer may bake each square into one texture
+pub const CHUNK_SIZE: u32 = 16;
+
 impl Level {
     pub fn new(width: u32, height: u32, tiles: Vec<u8>) -> Level {
-        let position = Position::new(0, ((height * 32) - SCREEN_SIZE.1) as i32);
+        let position = Position::new(0.0, ((height * TILE_SIZE) - SCREEN_SIZE.1) as f32);
         Level {
             position,
+            is_halted: false,
+            scroll_speed: SCROLL_SPEED,
             width,
             height,
-            tiles,
+            checkpoints: Vec::new(),
+            layers: vec![LevelLayer::Tiles(TileLayer::new(width, height, tiles))],
         }
     }
 
     pub fn update(&mut self) {
-        if self.position.y > 0 {
-            self.position += Position::new(0, -1)
+        self.position += self.scroll_velocity();
+    }
+
+    // How far the view moves this tick, it stops at the top of the map or while halted by a boss fight
+    pub fn scroll_velocity(&self) -> Velocity {
+        if self.is_halted {
+            return Velocity::new(0.0, 0.0);
         }
+        Velocity::new(0.0, -self.scroll_speed.min(self.position.y.max(0.0)))
+    }
+
+    // Forgets every checkpoint the top of the view went past, true if there was any
+    pub fn pass_checkpoints(&mut self) -> bool {
+        let top = self.position.y;
+        let count = self.checkpoints.len();
+        self.checkpoints.retain(|checkpoint| *checkpoint < top);
+        self.checkpoints.len() < count
+    }
+
+    // Whether anything of the rectangle is inside the camera, in level coordinates
+    pub fn is_visible(&self, position: Position, (width, height): (u32, u32)) -> bool {
+        let (screen_width, screen_height) = SCREEN_SIZE;
+        position.x + width as f32 > self.position.x
+            && position.x < self.position.x + screen_width as f32
+            && position.y + height as f32 > self.position.y
+            && position.y < self.position.y + screen_height as f32
     }
 
     pub fn draw(&self, renderer: &mut Renderer) {
-        let tiles = &self.tiles;
-        for rows in 0..self.height {
-            for cols in 0..self.width {
-                let id = rows * self.width + cols;
-                let tile_id = tiles[id as usize];
-                if tile_id != 0 {
-                    let x = cols as i32 * 32;
-                    let y = rows as i32 * 32;
-                    let tile_position = Position::new(x, y) - self.position;
-                    renderer.draw_tile("tiles", tile_position, tile_id - 1);
+        for (index, layer) in self.layers.iter().enumerate() {
+            match *layer {
+                LevelLayer::Tiles(ref tiles) => {
+                    self.draw_chunks(renderer, index, self.camera(tiles.parallax) - tiles.offset)
+                }
+                LevelLayer::Image(ref image) => self.draw_image(renderer, image),
+            }
+        }
+    }
+
+    // Where the view is for a layer scrolling by these factors
+    pub fn camera(&self, (parallax_x, parallax_y): (f32, f32)) -> Position {
+        Position::new(self.position.x * parallax_x, self.position.y * parallax_y)
+    }
+
+    // Only the chunks under the camera are drawn
+    fn draw_chunks<R: Renderer + ?Sized>(&self, renderer: &mut R, layer: usize, camera: Position) {
+        let (screen_width, screen_height) = SCREEN_SIZE;
+        let (first_col, last_col) = visible_range(camera.x, screen_width, self.width);
+        let (first_row, last_row) = visible_range(camera.y, screen_height, self.height);
+        for chunk_row in first_row / CHUNK_SIZE..chunks(last_row) {
+            for chunk_col in first_col / CHUNK_SIZE..chunks(last_col) {
+                let chunk_size = (CHUNK_SIZE * TILE_SIZE) as f32;
+                let chunk_position =
+                    Position::new(chunk_col as f32 * chunk_size, chunk_row as f32 * chunk_size) - camera;
+                renderer.draw_chunk("tiles", chunk_position, self, layer, (chunk_col, chunk_row));
+            }
+        }
+    }
+
+    fn draw_image<R: Renderer + ?Sized>(&self, renderer: &mut R, image: &ImageLayer) {
+        let origin = image.offset - self.camera(image.parallax);
+        let (screen_width, screen_height) = SCREEN_SIZE;
+        let (repeat_x, repeat_y) = image.repeat;
+        let columns = copies(origin.x, image.width, screen_width, repeat_x);
+        for y in copies(origin.y, image.height, screen_height, repeat_y) {
+            for x in &columns {
+                // draw_texture takes level coordinates
+                let position = Position::new(*x, y) + self.position;
+                if self.is_visible(position, (image.width, image.height)) {
+                    renderer.draw_texture(&image.texture_id, position, self);
+                }
+            }
+        }
+    }
+
+    // The tile layers tile by tile, what drawing looked like before the chunks, kept to compare the two
+    pub fn draw_tiles<R: Renderer>(&self, renderer: &mut R) {
+        let (screen_width, screen_height) = SCREEN_SIZE;
+        for layer in &self.layers {
+            let (tiles, camera) = match *layer {
+                LevelLayer::Tiles(ref tiles) => (&tiles.tiles, self.camera(tiles.parallax) - tiles.offset),
+                LevelLayer::Image(_) => continue,
+            };
+            let (first_col, last_col) = visible_range(camera.x, screen_width, self.width);
+            let (first_row, last_row) = visible_range(camera.y, screen_height, self.height);
+            for rows in first_row..last_row {
+                for cols in first_col..last_col {
+                    let id = rows * self.width + cols;
+                    let tile_id = tiles.get(id as usize).cloned().unwrap_or(0);
+                    if tile_id != 0 {
+                        let x = (cols * TILE_SIZE) as f32;
+                        let y = (rows * TILE_SIZE) as f32;
+                        let tile_position = Position::new(x, y) - camera;
+                        renderer.draw_tile("tiles", tile_position, tile_id - 1);
+                    }
                 }
             }
         }
     }
+
+    pub fn tile_layer(&self, layer: usize) -> Option<&TileLayer> {
+        match self.layers.get(layer) {
+            Some(LevelLayer::Tiles(tiles)) => Some(tiles),
+            _ => None,
+        }
+    }
+
+    // The non-empty tiles of a chunk as column and row within it and the id `draw_tile` takes
+    pub fn chunk_tiles<'a>(&'a self, layer: usize, (chunk_col, chunk_row): (u32, u32))
+        -> impl Iterator<Item = (u32, u32, u8)> + 'a {
+        let tiles = self.tile_layer(layer).map_or(&[][..], |layer| &layer.tiles[..]);
+        let first_col = chunk_col * CHUNK_SIZE;
+        let first_row = chunk_row * CHUNK_SIZE;
+        let last_col = (first_col + CHUNK_SIZE).min(self.width);
+        let last_row = (first_row + CHUNK_SIZE).min(self.height);
+        (first_row..last_row)
+            .flat_map(move |row| (first_col..last_col).map(move |col| (col, row)))
+            .filter_map(move |(col, row)| match tiles.get((row * self.width + col) as usize) {
+                None | Some(0) => None,
+                Some(tile_id) => Some((col - first_col, row - first_row, tile_id - 1)),
+            })
+    }
+
+    // Columns and rows of chunks it takes to cover the map
+    pub fn chunk_grid(&self) -> (u32, u32) {
+        (chunks(self.width), chunks(self.height))
+    }
+
+    // Changes with every tile changed in the chunk, baked chunks of another revision are out of date
+    pub fn chunk_revision(&self, layer: usize, (chunk_col, chunk_row): (u32, u32)) -> u32 {
+        let index = chunk_row * chunks(self.width) + chunk_col;
+        self.tile_layer(layer)
+            .and_then(|layer| layer.chunk_revisions.get(index as usize).cloned())
+            .unwrap_or(0)
+    }
+
+    pub fn set_tile(&mut self, layer: usize, col: u32, row: u32, tile_id: u8) -> Result<(), String> {
+        if col >= self.width || row >= self.height {
+            return Err(format!("No tile at {},{} on a {}x{} map", col, row, self.width, self.height));
+        }
+        let width = self.width;
+        let tiles = match self.layers.get_mut(layer) {
+            Some(LevelLayer::Tiles(tiles)) => tiles,
+            _ => return Err(format!("Layer {} has no tiles", layer)),
+        };
+        match tiles.tiles.get_mut((row * width + col) as usize) {
+            Some(tile) => *tile = tile_id,
+            None => return Err(format!("Layer {} is missing tile {},{}", layer, col, row)),
+        }
+        let index = (row / CHUNK_SIZE * chunks(width) + col / CHUNK_SIZE) as usize;
+        if let Some(revision) = tiles.chunk_revisions.get_mut(index) {
+            *revision = revision.wrapping_add(1);
+        }
+        Ok(())
+    }
+}
+
+impl TileLayer {
+    pub fn new(width: u32, height: u32, tiles: Vec<u8>) -> TileLayer {
+        TileLayer {
+            tiles,
+            chunk_revisions: vec![0; (chunks(width) * chunks(height)) as usize],
+            offset: Position::new(0.0, 0.0),
+            parallax: (1.0, 1.0),
+        }
+    }
+}
+
+// For renderers that draw tile by tile, the tiles of the chunk that end up on screen
+pub fn draw_chunk_tiles<R: Renderer + ?Sized>(renderer: &mut R,
+                                               texture_id: &str,
+                                               position: Position,
+                                               level: &Level,
+                                               layer: usize,
+                                               chunk: (u32, u32)) {
+    for (col, row, tile_id) in level.chunk_tiles(layer, chunk) {
+        let tile_position = position + Position::new((col * TILE_SIZE) as f32, (row * TILE_SIZE) as f32);
+        if level.is_visible(tile_position + level.position, (TILE_SIZE, TILE_SIZE)) {
+            renderer.draw_tile(texture_id, tile_position, tile_id);
+        }
+    }
+}
+
+// Where the copies of an image go along one axis of the screen, the first repeated one starts at or before 0
+fn copies(origin: f32, size: u32, screen: u32, is_repeated: bool) -> Vec<f32> {
+    if !is_repeated || size == 0 {
+        return vec![origin];
+    }
+    let size = size as f32;
+    let first = origin - (origin / size).ceil() * size;
+    let count = ((screen as f32 - first) / size).ceil() as u32;
+    (0..count).map(|copy| first + copy as f32 * size).collect()
+}
+
+// How many chunks it takes to cover that many tiles
+fn chunks(tiles: u32) -> u32 {
+    (tiles as f32 / CHUNK_SIZE as f32).ceil() as u32
+}
+
+// The tiles from `start` over `length` pixels, the last one partly visible included
+fn visible_range(start: f32, length: u32, count: u32) -> (u32, u32) {
+    let first = (start / TILE_SIZE as f32).floor().max(0.0) as u32;
+    let last = ((start + length as f32) / TILE_SIZE as f32).ceil().max(0.0) as u32;
+    (first.min(count), last.min(count))
 }
 
 
 #[cfg(test)]
 mod tests {
+    use game::AnimationState;
+    use game::ImageLayer;
     use game::Level;
+    use game::LevelLayer;
     use game::Position;
     use game::Renderer;
+    use game::TileLayer;
 
     struct MockRenderer {
-        pub interactions: Vec<String>
+        pub interactions: Vec<String>,
+        pub textures_on_screen: Vec<(String, Position)>,
     }
 
     impl MockRenderer {
         fn new() -> MockRenderer {
-            MockRenderer { interactions: vec![] }
+            MockRenderer { interactions: vec![], textures_on_screen: vec![] }
         }
     }
 
@@ -63,51 +264,112 @@ mod tests {
             self.interactions.push(String::from("draw_scene"));
         }
 
-        fn draw_texture(&mut self, _texture_id: &str, _position: Position, _level: &Level) {
+        fn draw_texture(&mut self, texture_id: &str, position: Position, level: &Level) {
             self.interactions.push(String::from("draw_texture"));
+            self.textures_on_screen.push((texture_id.to_string(), position - level.position));
         }
 
         fn draw_tile(&mut self, _texture_id: &str, _position: Position, _tile_id: u8) {
             self.interactions.push(String::from("draw_tile"));
         }
 
-        fn draw_frame(&mut self, _texture_id: &str, _position: Position, _level: &Level) {
+        fn draw_frame(&mut self, _animation: &AnimationState, _position: Position, _level: &Level) {
             self.interactions.push(String::from("draw_frame"));
         }
+
+        fn draw_text(&mut self, _text: &str, _position: Position) {
+            self.interactions.push(String::from("draw_text"));
+        }
+
+        fn fill_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            self.interactions.push(String::from("fill_rect"));
+        }
+
+        fn draw_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            self.interactions.push(String::from("draw_rect"));
+        }
+
+        fn draw_line(&mut self, _from: Position, _to: Position, _color: (u8, u8, u8)) {
+            self.interactions.push(String::from("draw_line"));
+        }
     }
 
     #[test]
     fn test_one_tick_of_update_if_not_allowed() {
         //given
         let mut scene = Level {
-            position: Position::new(0, 0),
+            position: Position::new(0.0, 0.0),
+            is_halted: false,
+            scroll_speed: 1.0,
             width: 0,
             height: 0,
-            tiles: Vec::new(),
+            checkpoints: Vec::new(),
+            layers: Vec::new(),
         };
 
         //when
         scene.update();
 
         //then
-        assert_eq!(scene.position, Position::new(0, 0))
+        assert_eq!(scene.position, Position::new(0.0, 0.0))
     }
 
     #[test]
     fn test_one_tick_of_update() {
         //given
         let mut scene = Level {
-            position: Position::new(0, 10),
+            position: Position::new(0.0, 10.0),
+            is_halted: false,
+            scroll_speed: 1.0,
             width: 0,
             height: 0,
-            tiles: Vec::new(),
+            checkpoints: Vec::new(),
+            layers: Vec::new(),
         };
 
         //when
         scene.update();
 
         //then
-        assert_eq!(scene.position, Position::new(0, 9))
+        assert_eq!(scene.position, Position::new(0.0, 9.0))
+    }
+
+    #[test]
+    fn test_no_scrolling_while_halted() {
+        //given
+        let mut scene = Level {
+            position: Position::new(0.0, 10.0),
+            is_halted: true,
+            scroll_speed: 1.0,
+            width: 0,
+            height: 0,
+            checkpoints: Vec::new(),
+            layers: Vec::new(),
+        };
+
+        //when
+        scene.update();
+
+        //then
+        assert_eq!(scene.position, Position::new(0.0, 10.0))
+    }
+
+    #[test]
+    fn test_passing_checkpoints() {
+        //given
+        let mut scene = Level::new(20, 60, vec![]);
+        let top = scene.position.y;
+        scene.checkpoints = vec![top - 1.0, top - 10.0];
+
+        //when
+        let before = scene.pass_checkpoints();
+        scene.update();
+        let after = scene.pass_checkpoints();
+
+        //then
+        assert!(!before);
+        assert!(after);
+        assert_eq!(scene.checkpoints, vec![top - 10.0]);
     }
 
     #[test]
@@ -123,10 +385,13 @@ mod tests {
         tiles[60] = 5;
 
         let scene = Level {
-            position: Position::new(0, 0),
+            position: Position::new(0.0, 0.0),
+            is_halted: false,
+            scroll_speed: 1.0,
             width: 10,
             height: 10,
-            tiles,
+            checkpoints: Vec::new(),
+            layers: vec![LevelLayer::Tiles(TileLayer::new(10, 10, tiles))],
         };
 
         //when
@@ -135,5 +400,107 @@ mod tests {
         //then
         assert_eq!(renderer.interactions.len(), 6);
     }
-}
 
+    #[test]
+    fn test_drawing_only_visible_tiles() {
+        //given
+        let mut renderer = MockRenderer::new();
+        let mut scene = Level::new(20, 60, vec![1; 20 * 60]);
+        scene.position = Position::new(0.0, 1000.0);
+
+        //when
+        scene.draw(&mut renderer);
+
+        //then
+        // 600 pixels from row 31.25 on touch rows 31 to 49
+        assert_eq!(renderer.interactions.len(), 20 * 19);
+        assert!(scene.is_visible(Position::new(790.0, 990.0), (32, 32)));
+        assert!(!scene.is_visible(Position::new(100.0, 1600.0), (32, 32)));
+        assert!(!scene.is_visible(Position::new(-32.0, 1200.0), (32, 32)));
+    }
+
+    #[test]
+    fn test_changing_a_tile_invalidates_its_chunk() {
+        //given
+        let mut tiles = vec![0; 20 * 60];
+        tiles[17] = 3;
+        let mut scene = Level::new(20, 60, tiles);
+
+        //when
+        scene.set_tile(0, 18, 17, 5).unwrap();
+        let outside = scene.set_tile(0, 20, 0, 1);
+        let no_layer = scene.set_tile(1, 0, 0, 1);
+
+        //then
+        assert_eq!(scene.chunk_grid(), (2, 4));
+        assert_eq!(scene.chunk_revision(0, (1, 1)), 1);
+        assert_eq!(scene.chunk_revision(0, (1, 0)), 0);
+        assert_eq!(scene.chunk_tiles(0, (1, 0)).collect::<Vec<_>>(), vec![(1, 0, 2)]);
+        assert_eq!(scene.chunk_tiles(0, (1, 1)).collect::<Vec<_>>(), vec![(2, 1, 4)]);
+        assert!(outside.is_err());
+        assert!(no_layer.is_err());
+    }
+
+    #[test]
+    fn test_drawing_every_tile_layer() {
+        //given
+        let mut renderer = MockRenderer::new();
+        let mut scene = Level::new(20, 60, vec![0; 20 * 60]);
+        scene.position = Position::new(0.0, 1000.0);
+        let mut ground = vec![0; 20 * 60];
+        ground[40 * 20 + 3] = 1;
+        let mut overhead = vec![0; 20 * 60];
+        overhead[20 * 20 + 5] = 2;
+        overhead[20 * 20 + 6] = 2;
+        let overhead = TileLayer { parallax: (1.0, 0.5), ..TileLayer::new(20, 60, overhead) };
+        scene.layers = vec![LevelLayer::Tiles(TileLayer::new(20, 60, ground)), LevelLayer::Tiles(overhead)];
+
+        //when
+        scene.draw(&mut renderer);
+        scene.set_tile(1, 6, 20, 0).unwrap();
+
+        //then
+        // at half the speed the second layer is 500 pixels in, row 20 is on screen for both
+        assert_eq!(renderer.interactions, vec![String::from("draw_tile"); 3]);
+        assert_eq!(scene.chunk_tiles(0, (0, 2)).collect::<Vec<_>>(), vec![(3, 8, 0)]);
+        assert_eq!(scene.chunk_tiles(1, (0, 1)).collect::<Vec<_>>(), vec![(5, 4, 1)]);
+        assert_eq!(scene.chunk_revision(0, (0, 1)), 0);
+        assert_eq!(scene.chunk_revision(1, (0, 1)), 1);
+    }
+
+    #[test]
+    fn test_drawing_parallax_layers() {
+        //given
+        let mut renderer = MockRenderer::new();
+        let mut scene = Level::new(20, 60, vec![0; 20 * 60]);
+        let clouds = ImageLayer {
+            texture_id: String::from("clouds.png"),
+            source: String::from("assets/clouds.png"),
+            width: 256,
+            height: 256,
+            offset: Position::new(0.0, 0.0),
+            parallax: (1.0, 0.5),
+            repeat: (true, true),
+        };
+        let island = ImageLayer {
+            texture_id: String::from("island.png"),
+            offset: Position::new(100.0, 1400.0),
+            parallax: (1.0, 1.0),
+            repeat: (false, false),
+            ..clouds.clone()
+        };
+        let passed = ImageLayer { offset: Position::new(100.0, 0.0), ..island.clone() };
+        scene.layers = vec![LevelLayer::Image(clouds), LevelLayer::Image(island), LevelLayer::Image(passed)];
+
+        //when
+        scene.draw(&mut renderer);
+
+        //then
+        // at half the speed the clouds are 660 pixels in, the first row starts 148 pixels above the screen
+        assert_eq!(scene.camera((1.0, 0.5)), Position::new(0.0, 660.0));
+        assert_eq!(renderer.textures_on_screen.len(), 4 * 3 + 1);
+        assert_eq!(renderer.textures_on_screen[0], (String::from("clouds.png"), Position::new(0.0, -148.0)));
+        assert_eq!(renderer.textures_on_screen[11], (String::from("clouds.png"), Position::new(768.0, 364.0)));
+        assert_eq!(renderer.textures_on_screen[12], (String::from("island.png"), Position::new(100.0, 80.0)));
+    }
+}
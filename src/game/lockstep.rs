@@ -0,0 +1,205 @@
+use game::AnimationState;
+use game::Divergence;
+use game::Engine;
+use game::FrameHash;
+use game::Headless;
+use game::InputHandler;
+use game::InputScript;
+use game::InputState;
+use game::Level;
+use game::Position;
+use game::Renderer;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+impl InputScript {
+    pub fn inputs(&self, frame: u32) -> Vec<InputState> {
+        self.presses
+            .iter()
+            .filter(|press| press.from <= frame && frame <= press.to)
+            .map(|press| press.input)
+            .collect()
+    }
+}
+
+impl<R, I> Engine<R, I>
+    where
+        R: Renderer,
+        I: InputHandler,
+{
+    // Ids are left out, they come from a counter every engine in the process shares
+    pub fn frame_hash(&self) -> FrameHash {
+        let mut level = DefaultHasher::new();
+        self.level.position.x.to_bits().hash(&mut level);
+        self.level.position.y.to_bits().hash(&mut level);
+
+        let objects = self
+            .game_objects
+            .iter()
+            .flatten()
+            .filter_map(|game_object| {
+                let record = game_object.to_record()?;
+                let mut hasher = DefaultHasher::new();
+                record.attributes.iter().filter(|(key, _)| key != "id").for_each(|attribute| attribute.hash(&mut hasher));
+                Some((game_object.id, game_object.object_type, hasher.finish()))
+            })
+            .collect();
+
+        FrameHash { level: level.finish(), objects }
+    }
+}
+
+impl FrameHash {
+    pub fn compare(&self, other: &FrameHash, frame: u32) -> Result<(), Divergence> {
+        if self.level != other.level {
+            return Err(Divergence { frame, object: None });
+        }
+        let first_difference = self
+            .objects
+            .iter()
+            .zip(&other.objects)
+            .find(|((_, _, mine), (_, _, theirs))| mine != theirs)
+            .map(|((id, object_type, _), _)| (*id, *object_type));
+        // one run has objects the other one does not
+        let extra = || {
+            let (longer, shorter) = if self.objects.len() > other.objects.len() { (self, other) } else { (other, self) };
+            longer.objects.get(shorter.objects.len()).map(|(id, object_type, _)| (*id, *object_type))
+        };
+
+        match first_difference.or_else(extra) {
+            Some(object) => Err(Divergence { frame, object: Some(object) }),
+            None => Ok(()),
+        }
+    }
+}
+
+// Plays the script on both engines side by side, frame 0 is the state before the first input
+pub fn run_lockstep<R, I>(first: &mut Engine<R, I>, second: &mut Engine<R, I>, script: &InputScript) -> Result<(), Divergence>
+    where
+        R: Renderer,
+        I: InputHandler,
+{
+    first.frame_hash().compare(&second.frame_hash(), 0)?;
+    for frame in 0..script.frames {
+        let inputs = script.inputs(frame);
+        first.step(&inputs);
+        second.step(&inputs);
+        first.frame_hash().compare(&second.frame_hash(), frame + 1)?;
+    }
+    Ok(())
+}
+
+impl Renderer for Headless {
+    fn clear_scene(&mut self) {}
+
+    fn draw_scene(&mut self) {}
+
+    fn draw_texture(&mut self, _texture_id: &str, _position: Position, _level: &Level) {}
+
+    fn draw_tile(&mut self, _texture_id: &str, _position: Position, _tile_id: u8) {}
+
+    fn draw_frame(&mut self, _animation: &AnimationState, _position: Position, _level: &Level) {}
+
+    fn draw_text(&mut self, _text: &str, _position: Position) {}
+
+    fn fill_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {}
+
+    fn draw_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {}
+
+    fn draw_line(&mut self, _from: Position, _to: Position, _color: (u8, u8, u8)) {}
+
+    fn draw_chunk(&mut self, _texture_id: &str, _position: Position, _level: &Level, _layer: usize, _chunk: (u32, u32)) {}
+}
+
+impl InputHandler for Headless {
+    fn capture(&mut self) -> Vec<InputState> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::lockstep::run_lockstep;
+    use game::Divergence;
+    use game::Engine;
+    use game::Headless;
+    use game::InputScript;
+    use game::InputState;
+    use game::ObjectType;
+    use game::Position;
+    use game::ScriptedPress;
+    use helpers::parsers;
+    use std::collections::HashMap;
+
+    fn script() -> InputScript {
+        InputScript {
+            map: String::from("assets/map1.tmx"),
+            seed: 42,
+            players: 1,
+            frames: 120,
+            presses: vec![
+                ScriptedPress { from: 0, to: 119, input: InputState::Shoot(0) },
+                ScriptedPress { from: 10, to: 60, input: InputState::Left(0) },
+            ],
+        }
+    }
+
+    // both from the same parsed map, parsing it twice would hand out other ids
+    fn engines(script: &InputScript) -> (Engine<Headless, Headless>, Engine<Headless, Headless>) {
+        let (game_objects, level, _, _, _) = parsers::map_file::parse(&script.map);
+        let mut patterns = HashMap::new();
+        parsers::pattern_file::parse("assets/patterns.xml", &mut patterns);
+        let create_engine = || {
+            let mut engine = Engine::new(Vec::new(), level.clone(), Headless, Headless);
+            engine.set_patterns(patterns.clone());
+            engine.set_seed(script.seed);
+            engine.set_player_count(script.players);
+            engine.restart(game_objects.clone(), level.clone());
+            engine
+        };
+        (create_engine(), create_engine())
+    }
+
+    #[test]
+    fn test_same_runs_stay_the_same() {
+        //given
+        let script = script();
+        let (mut first, mut second) = engines(&script);
+
+        //when
+        let result = run_lockstep(&mut first, &mut second, &script);
+
+        //then
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_lockstep_script_stays_the_same() {
+        //given
+        let script = parsers::input_script_file::parse("assets/lockstep.xml").unwrap();
+        let (mut first, mut second) = engines(&script);
+
+        //when
+        let result = run_lockstep(&mut first, &mut second, &script);
+
+        //then
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_reporting_the_first_divergence() {
+        //given
+        let script = script();
+        let (mut first, mut second) = engines(&script);
+        let plane = second.game_objects.iter_mut().flatten().find(|game_object| game_object.player.is_some()).unwrap();
+        let id = plane.id;
+        plane.player.as_mut().unwrap().position += Position::new(0.5, 0.0);
+
+        //when
+        let result = run_lockstep(&mut first, &mut second, &script);
+
+        //then
+        assert_eq!(result, Err(Divergence { frame: 0, object: Some((id, ObjectType::Player)) }));
+    }
+}
@@ -1,16 +1,16 @@
 use game::Rect;
 
 impl Rect {
-    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Rect {
         Rect { x, y, width, height }
     }
 
     pub fn has_intersection(&self, other: &Rect) -> bool {
         /* Horizontal intersection */
         let mut a_min = self.x;
-        let mut a_max = a_min + self.width as i32;
+        let mut a_max = a_min + self.width;
         let mut b_min = other.x;
-        let mut b_max = b_min + other.width as i32;
+        let mut b_max = b_min + other.width;
 
         if b_min > a_min {
             a_min = b_min;
@@ -25,9 +25,9 @@ impl Rect {
 
         /* Vertical intersection */
         a_min = self.y;
-        a_max = a_min + self.height as i32;
+        a_max = a_min + self.height;
         b_min = other.y;
-        b_max = b_min + other.height as i32;
+        b_max = b_min + other.height;
         if b_min > a_min {
             a_min = b_min;
         }
@@ -50,8 +50,8 @@ mod tests {
     #[test]
     fn test_intersection() {
         //given
-        let a = Rect::new(0, 0, 10, 10);
-        let b = Rect::new(5, 5, 10, 10);
+        let a = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let b = Rect::new(5.0, 5.0, 10.0, 10.0);
 
         //when
         let intersecting = a.has_intersection(&b);
@@ -63,8 +63,8 @@ mod tests {
     #[test]
     fn test_no_intersection() {
         //given
-        let a = Rect::new(0, 0, 1, 1);
-        let b = Rect::new(5, 5, 1, 1);
+        let a = Rect::new(0.0, 0.0, 1.0, 1.0);
+        let b = Rect::new(5.0, 5.0, 1.0, 1.0);
 
         //when
         let intersecting = a.has_intersection(&b);
@@ -76,8 +76,8 @@ mod tests {
     #[test]
     fn test_border_intersection() {
         //given
-        let a = Rect::new(0, 0, 5, 5);
-        let b = Rect::new(5, 5, 5, 5);
+        let a = Rect::new(0.0, 0.0, 5.0, 5.0);
+        let b = Rect::new(5.0, 5.0, 5.0, 5.0);
 
         //when
         let intersecting = a.has_intersection(&b);
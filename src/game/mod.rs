@@ -1,14 +1,32 @@
 use cgmath::Vector2;
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
 
+mod animation;
+mod boss;
+mod campaign;
+mod console;
+mod debug;
 mod engine;
 mod game_object;
+mod hud;
 mod misc;
-mod level;
+pub mod level;
+pub mod lockstep;
+mod patterns;
+mod profiler;
+mod random;
+mod save;
+mod score;
+mod weapons;
 pub mod states;
 
-pub type Position = Vector2<i32>;
-pub type Velocity = Vector2<i32>;
+// The simulation runs on sub-pixel positions, only renderers round them to whole pixels.
+// Nothing in it reads the clock and randomness comes from a seeded Rng, so the same seed
+// and inputs always replay the same game.
+pub type Position = Vector2<f32>;
+pub type Velocity = Vector2<f32>;
 pub type Id = usize;
 
 pub struct Engine<R: Renderer, I: InputHandler> {
@@ -17,6 +35,90 @@ pub struct Engine<R: Renderer, I: InputHandler> {
     input_handler: I,
     level: Level,
     game_objects: Vec<Option<GameObject>>,
+    hud: Hud,
+    state: GameState,
+    players: Vec<PlayerStatus>,
+    // planes of players beyond this are taken off every map
+    player_count: usize,
+    high_scores: HighScores,
+    patterns: HashMap<String, BulletPattern>,
+    campaign: Campaign,
+    // map the next stage is played on, waiting for whoever owns the maps to load it
+    pending_map: Option<String>,
+    // snapshot to put the engine back into once the pending map is loaded
+    pending_restore: Option<Vec<Record>>,
+    checkpoint: Option<Vec<Record>>,
+    save_filename: Option<String>,
+    rng: Rng,
+    // hitboxes, ids and the tile grid drawn over the game
+    debug_overlay: bool,
+    profiler: Profiler,
+    console: Console,
+    cheats: Cheats,
+    // frames per second the game loop is asked to run at
+    target_fps: u32,
+}
+
+// Everything a frame can change, kept in memory so a networked game can go back a few frames
+#[derive(Clone)]
+pub struct EngineState {
+    level: Level,
+    game_objects: Vec<Option<GameObject>>,
+    hud: Hud,
+    state: GameState,
+    players: Vec<PlayerStatus>,
+    stage: usize,
+    pending_map: Option<String>,
+    checkpoint: Option<Vec<Record>>,
+    rng: Rng,
+}
+
+// What objects get to see of each other while they update
+pub struct World<'a> {
+    pub level: &'a Level,
+    pub players: Vec<Position>,
+    pub enemies: Vec<Position>,
+    pub patterns: &'a HashMap<String, BulletPattern>,
+    pub rng: &'a mut Rng,
+}
+
+// Gameplay randomness, the same seed and inputs always give the same game
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rng {
+    seed: u64,
+    streams: Vec<RngStream>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RngStream {
+    state: u64,
+}
+
+// Every system draws from its own stream, so extra rolls in one do not change what the others get
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RngSystem {
+    Patterns,
+    Drops,
+    Effects,
+    Enemies,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum GameState {
+    Playing,
+    LevelComplete(Vec<Tally>),
+    Transition { ticks: u32 },
+    GameOver { name: String, is_entering_name: bool },
+}
+
+// Things that happened during an update which the engine has to react to
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameEvent {
+    // `player` is the object id of the plane that scored, got killed or collected
+    EnemyKilled { score: u32, player: Id },
+    PlayerKilled { player: Id },
+    PowerUpDropped { kind: PowerUpKind, position: Position },
+    PowerUpCollected { kind: PowerUpKind, player: Id },
 }
 
 pub trait Renderer {
@@ -25,17 +127,213 @@ pub trait Renderer {
 
     fn draw_texture(&mut self, texture_id: &str, position: Position, level: &Level);
     fn draw_tile(&mut self, texture_id: &str, position: Position, tile_id: u8);
-    fn draw_frame(&mut self, texture_id: &str, position: Position, level: &Level);
+    fn draw_frame(&mut self, animation: &AnimationState, position: Position, level: &Level);
+    fn draw_text(&mut self, text: &str, position: Position);
+    fn fill_rect(&mut self, position: Position, width: u32, height: u32, color: (u8, u8, u8));
+    // outlines only, in screen coordinates like the two above
+    fn draw_rect(&mut self, position: Position, width: u32, height: u32, color: (u8, u8, u8));
+    fn draw_line(&mut self, from: Position, to: Position, color: (u8, u8, u8));
+
+    // A chunk of one of the level's tile layers, renderers that can keep it baked in one texture do
+    fn draw_chunk(&mut self, texture_id: &str, position: Position, level: &Level, layer: usize, chunk: (u32, u32)) {
+        level::draw_chunk_tiles(self, texture_id, position, level, layer, chunk);
+    }
+
+    // Called whenever the engine is handed a level, before any of it is drawn
+    fn prepare_level(&mut self, _level: &Level) {}
 }
 
-#[derive(Debug, PartialEq)]
+// Drawn on top of the world in screen coordinates
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hud {
+    pub bombs: u8,
+    // (health, max health) of the boss being fought
+    pub boss_health: Option<(u32, u32)>,
+    fps: u32,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PlaybackMode {
+    Loop,
+    Once,
+    PingPong,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnimationFrame {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub duration: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Animation {
+    pub id: String,
+    pub texture_id: String,
+    pub mode: PlaybackMode,
+    pub frames: Vec<AnimationFrame>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnimationState {
+    pub clip: String,
+    elapsed: u32,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Score {
+    pub points: u32,
+    pub multiplier: u32,
+    pub max_multiplier: u32,
+    pub kills: u32,
+    combo_ticks: u32,
+}
+
+// Everything about a player that outlives their plane
+#[derive(Debug, PartialEq, Clone)]
+pub struct PlayerStatus {
+    // object id of the player's plane on the current map
+    pub id: Id,
+    pub score: Score,
+    pub lives: u8,
+    pub weapon: WeaponTier,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Tally {
+    pub points: u32,
+    pub kills: u32,
+    pub max_multiplier: u32,
+    pub lives_bonus: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct HighScores {
+    filename: Option<String>,
+    pub entries: Vec<HighScore>,
+}
+
+// One element of a save file, attributes keep the order they were written in
+#[derive(Debug, PartialEq, Clone)]
+pub struct Record {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+// Flying and shooting carry the index of the player they are meant for
 pub enum InputState {
-    Up,
-    Down,
-    Left,
-    Right,
-    Shoot,
+    Up(usize),
+    Down(usize),
+    Left(usize),
+    Right(usize),
+    Shoot(usize),
     Quit,
+    Character(char),
+    Backspace,
+    Confirm,
+    Load,
+    Screenshot,
+    ToggleDebugOverlay,
+    ToggleConsole,
+}
+
+// Inputs a map is played with frame by frame, for checking that two runs stay the same
+#[derive(Debug, PartialEq, Clone)]
+pub struct InputScript {
+    pub map: String,
+    pub seed: u64,
+    pub players: usize,
+    pub frames: u32,
+    pub presses: Vec<ScriptedPress>,
+}
+
+// `input` is held from frame `from` up to and including frame `to`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScriptedPress {
+    pub from: u32,
+    pub to: u32,
+    pub input: InputState,
+}
+
+// A frame of a lockstep run; object hashes come in the order the engine keeps the objects
+#[derive(Debug, PartialEq)]
+pub struct FrameHash {
+    level: u64,
+    objects: Vec<(Id, ObjectType, u64)>,
+}
+
+// The first difference between two runs, without an object it was the level's position
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    pub frame: u32,
+    pub object: Option<(Id, ObjectType)>,
+}
+
+// Draws nothing and reads no keys, for running the engine without a window
+pub struct Headless;
+
+// Drops down over the game with the backtick key, commands in it change the running game
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Console {
+    pub is_open: bool,
+    line: String,
+    history: Vec<String>,
+    // how far back in the history the arrows went, none while typing a new line
+    browsing: Option<usize>,
+    output: Vec<String>,
+    // the arrows come in as held keys, the history moves once per press
+    held: Vec<InputState>,
+}
+
+// Switched on and off from the console, they apply to every player's plane
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Cheats {
+    pub god: bool,
+    pub noclip: bool,
+}
+
+// Parts of a frame the profiler times on their own
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ProfiledSection {
+    Input,
+    UpdateObjects,
+    CheckCollisions,
+    RemoveDestroyedObjects,
+    Draw,
+}
+
+// How long one frame took and how much there was to go through
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FrameProfile {
+    pub timings: [Duration; 5],
+    pub objects: usize,
+    pub bullets: usize,
+    pub draw_calls: u32,
+}
+
+// It reads the clock, nothing in the simulation reads it back.
+// The last few frames are kept for the debug overlay, every frame while recording.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    // the frame in progress so far
+    current: [Duration; 5],
+    history: VecDeque<FrameProfile>,
+    recording: Option<Vec<FrameProfile>>,
+}
+
+// Passes every call on to the renderer, counting them on the way
+pub struct DrawCounter<'a, R: Renderer + 'a> {
+    renderer: &'a mut R,
+    calls: u32,
 }
 
 pub trait InputHandler {
@@ -43,11 +341,69 @@ pub trait InputHandler {
 }
 
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct Level {
     pub position: Position,
+    pub is_halted: bool,
+    // pixels per frame
+    pub scroll_speed: f32,
     pub width: u32,
     pub height: u32,
+    // map heights of the checkpoints the view has not reached yet
+    pub checkpoints: Vec<f32>,
+    // drawn back to front in map order, everything else is drawn in front of them
+    pub layers: Vec<LevelLayer>,
+}
+
+// Parallax factors of 1 scroll with the view like the planes do, 0 stays put on screen
+#[derive(Debug, PartialEq, Clone)]
+pub enum LevelLayer {
+    Tiles(TileLayer),
+    Image(ImageLayer),
+}
+
+// A grid of tiles the size of the map
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileLayer {
     pub tiles: Vec<u8>,
+    // one per chunk, row by row
+    pub chunk_revisions: Vec<u32>,
+    pub offset: Position,
+    pub parallax: (f32, f32),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImageLayer {
+    pub texture_id: String,
+    // the file the renderer loads it from
+    pub source: String,
+    pub width: u32,
+    pub height: u32,
+    pub offset: Position,
+    pub parallax: (f32, f32),
+    // repeated along an axis it fills the screen along it
+    pub repeat: (bool, bool),
+}
+
+// What has to happen on a map before the stage counts as cleared
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum LevelGoal {
+    ReachTop,
+    DefeatBoss,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Stage {
+    pub map: String,
+    pub name: String,
+    pub goal: LevelGoal,
+}
+
+// Maps played one after another, score, lives and weapon carry over between them
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Campaign {
+    pub stages: Vec<Stage>,
+    current: usize,
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -55,31 +411,98 @@ pub enum ObjectType {
     Player,
     Enemy,
     Bullet,
+    PowerUp,
+    Boss,
     Unknown,
 }
 
-#[derive(PartialEq)]
+// Each weapon power-up moves the player one tier up
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WeaponTier {
+    Single,
+    Double,
+    Spread,
+    Homing,
+    Laser,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PowerUpKind {
+    Weapon,
+    Bomb,
+    Life,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BulletKind {
+    Normal,
+    Homing,
+    Laser,
+    Orb,
+}
+
+// Angles are in degrees, 0 points straight down and they grow counter-clockwise
+#[derive(Debug, PartialEq, Clone)]
+pub enum PatternStep {
+    Ring { count: u32, angle: f32, speed: f32 },
+    Aimed { count: u32, spread: f32, angle: f32, speed: f32 },
+    Wait(u32),
+    Repeat { times: u32, delay: u32, steps: Vec<PatternStep> },
+    Emitter { spin: f32, steps: Vec<PatternStep> },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Shot {
+    pub tick: u32,
+    pub angle: f32,
+    pub speed: f32,
+    pub aimed: bool,
+}
+
+// Steps are flattened into a timeline of shots when the pattern is created
+#[derive(Debug, PartialEq, Clone)]
+pub struct BulletPattern {
+    pub shots: Vec<Shot>,
+    pub duration: u32,
+    pub looping: bool,
+    // every shot is turned by up to this many degrees either way when it is fired
+    pub jitter: f32,
+}
+
+#[derive(PartialEq, Clone)]
 pub struct GameObject {
     pub id: Id,
     pub object_type: ObjectType,
     pub player: Option<PlayerState>,
     pub enemy: Option<EnemyState>,
     pub bullet: Option<BulletState>,
+    pub power_up: Option<PowerUpState>,
+    pub boss: Option<BossState>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct PlayerState {
     id: Id,
+    pub index: usize,
     position: Position,
     pub is_shooting: bool,
     pub is_destroyed: bool,
-    last_shot_date: SystemTime,
+    pub weapon: WeaponTier,
+    cooldown: u32,
     velocity: Velocity,
+    thrust: Velocity,
+    pub acceleration: f32,
+    pub drag: f32,
+    pub max_speed: f32,
     width: u32,
     height: u32,
+    animation: AnimationState,
+    // cheats from the console: nothing takes the plane down, or nothing touches it at all
+    pub is_invulnerable: bool,
+    pub is_noclip: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct EnemyState {
     id: Id,
     position: Position,
@@ -87,23 +510,66 @@ pub struct EnemyState {
     pub is_destroyed: bool,
     width: u32,
     height: u32,
+    animation: AnimationState,
+    pub score: u32,
+    pub drop: Option<PowerUpKind>,
+    pub pattern: Option<String>,
+    pattern_ticks: u32,
+    // set once the view reached it, before that it waits where the map placed it
+    is_engaged: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct BulletState {
     position: Position,
     velocity: Velocity,
+    kind: BulletKind,
     shooter_type: ObjectType,
     shooter_id: Id,
     pub is_destroyed: bool,
 }
 
-#[derive(Debug)]
-struct Rect {
-    x: i32,
-    y: i32,
+#[derive(Debug, PartialEq, Clone)]
+pub struct PowerUpState {
+    position: Position,
+    pub kind: PowerUpKind,
+    pub is_destroyed: bool,
+    animation: AnimationState,
+}
+
+// A boss is one object made of several parts, each with its own hitbox
+#[derive(Debug, PartialEq, Clone)]
+pub struct BossState {
+    id: Id,
+    position: Position,
+    velocity: Velocity,
+    pub health: u32,
+    pub max_health: u32,
+    pub is_destroyed: bool,
     width: u32,
     height: u32,
+    parts: Vec<Rect>,
+    phases: Vec<BossPhase>,
+    pub phase: usize,
+    animation: AnimationState,
+    pub score: u32,
+    pattern_ticks: u32,
+}
+
+// A phase starts once the boss is down to `threshold` percent of its health
+#[derive(Debug, PartialEq, Clone)]
+pub struct BossPhase {
+    pub threshold: u32,
+    pub speed: f32,
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
 }
 
 trait CollisionState {
@@ -111,23 +577,28 @@ trait CollisionState {
     fn size(&self) -> (u32, u32);
     fn collision_padding(&self) -> (u32, u32);
 
+    fn collision_rects(&self) -> Vec<Rect> {
+        vec![self.collision_rect()]
+    }
+
     fn collision_rect(&self) -> Rect {
         let (padding_horizontal, padding_vertical) = self.collision_padding();
         let position = self.position();
         let (width, height) = self.size();
 
         Rect::new(
-            position.x + padding_horizontal as i32,
-            position.y + padding_vertical as i32,
-            width - padding_horizontal,
-            height - padding_vertical,
+            position.x + padding_horizontal as f32,
+            position.y + padding_vertical as f32,
+            (width - padding_horizontal) as f32,
+            (height - padding_vertical) as f32,
         )
     }
 
     fn is_colliding(&self, other: &CollisionState) -> bool {
-        let a = self.collision_rect();
-        let b = other.collision_rect();
+        let others = other.collision_rects();
 
-        a.has_intersection(&b)
+        self.collision_rects()
+            .iter()
+            .any(|a| others.iter().any(|b| a.has_intersection(b)))
     }
 }
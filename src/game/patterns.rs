@@ -0,0 +1,147 @@
+use game::BulletPattern;
+use game::PatternStep;
+use game::Position;
+use game::Shot;
+use game::Velocity;
+
+impl BulletPattern {
+    pub fn new(steps: &[PatternStep], looping: bool) -> BulletPattern {
+        let mut shots = Vec::new();
+        let duration = compile(steps, 0, &[], &mut shots);
+
+        BulletPattern {
+            shots,
+            // a looping pattern needs at least one tick or it would fire everything forever in the same frame
+            duration: if looping { duration.max(1) } else { duration },
+            looping,
+            jitter: 0.0,
+        }
+    }
+
+    pub fn shots_at(&self, tick: u32) -> impl Iterator<Item = &Shot> {
+        self.shots.iter().filter(move |shot| shot.tick == tick)
+    }
+
+    // Pattern ticks advance by one every frame; None once a pattern that does not loop is over
+    pub fn next_tick(&self, tick: u32) -> Option<u32> {
+        match (tick + 1 >= self.duration, self.looping) {
+            (false, _) => Some(tick + 1),
+            (true, true) => Some(0),
+            (true, false) => None,
+        }
+    }
+}
+
+impl Shot {
+    // Aimed shots are turned towards `target`, the others ignore it
+    pub fn velocity(&self, origin: Position, target: Option<Position>) -> Velocity {
+        let base = match (self.aimed, target) {
+            (true, Some(target)) => {
+                let direction = target - origin;
+                direction.x.atan2(direction.y).to_degrees()
+            }
+            _ => 0.0,
+        };
+
+        let radians = (base + self.angle).to_radians();
+        Velocity::new(radians.sin() * self.speed, radians.cos() * self.speed)
+    }
+}
+
+// Returns the tick right after the last step; `spins` are the (start tick, degrees per tick) of every enclosing emitter
+fn compile(steps: &[PatternStep], start: u32, spins: &[(u32, f32)], shots: &mut Vec<Shot>) -> u32 {
+    let mut tick = start;
+
+    for step in steps {
+        let rotation: f32 = spins.iter().map(|(from, spin)| (tick - from) as f32 * spin).sum();
+
+        match step {
+            PatternStep::Ring { count, angle, speed } => {
+                for i in 0..*count {
+                    let angle = angle + rotation + 360.0 * i as f32 / *count as f32;
+                    shots.push(Shot { tick, angle, speed: *speed, aimed: false });
+                }
+            }
+            PatternStep::Aimed { count, spread, angle, speed } => {
+                for i in 0..*count {
+                    let offset = if *count > 1 { -spread / 2.0 + spread * i as f32 / (*count - 1) as f32 } else { 0.0 };
+                    shots.push(Shot { tick, angle: angle + rotation + offset, speed: *speed, aimed: true });
+                }
+            }
+            PatternStep::Wait(ticks) => tick += ticks,
+            PatternStep::Repeat { times, delay, steps } => {
+                for _ in 0..*times {
+                    tick = compile(steps, tick, spins, shots) + delay;
+                }
+            }
+            PatternStep::Emitter { spin, steps } => {
+                let mut spins = spins.to_vec();
+                spins.push((tick, *spin));
+                tick = compile(steps, tick, &spins, shots);
+            }
+        }
+    }
+    tick
+}
+
+#[cfg(test)]
+mod tests {
+    use game::BulletPattern;
+    use game::PatternStep;
+    use game::Position;
+
+    #[test]
+    fn test_ring() {
+        //given
+        let steps = vec![PatternStep::Ring { count: 4, angle: 0.0, speed: 2.0 }];
+
+        //when
+        let pattern = BulletPattern::new(&steps, false);
+
+        //then
+        let angles: Vec<f32> = pattern.shots.iter().map(|shot| shot.angle).collect();
+        assert_eq!(angles, vec![0.0, 90.0, 180.0, 270.0]);
+        assert_eq!(pattern.duration, 0);
+        assert_eq!(pattern.next_tick(0), None);
+    }
+
+    #[test]
+    fn test_repeat_with_spinning_emitter() {
+        //given
+        let steps = vec![PatternStep::Emitter {
+            spin: 5.0,
+            steps: vec![PatternStep::Repeat {
+                times: 3,
+                delay: 2,
+                steps: vec![PatternStep::Ring { count: 1, angle: 0.0, speed: 1.0 }],
+            }],
+        }];
+
+        //when
+        let pattern = BulletPattern::new(&steps, true);
+
+        //then
+        let shots: Vec<(u32, f32)> = pattern.shots.iter().map(|shot| (shot.tick, shot.angle)).collect();
+        assert_eq!(shots, vec![(0, 0.0), (2, 10.0), (4, 20.0)]);
+        assert_eq!(pattern.duration, 6);
+        assert_eq!(pattern.next_tick(5), Some(0));
+        assert_eq!(pattern.shots_at(2).count(), 1);
+    }
+
+    #[test]
+    fn test_aimed_velocity() {
+        //given
+        let steps = vec![PatternStep::Aimed { count: 3, spread: 90.0, angle: 0.0, speed: 2.0 }];
+        let pattern = BulletPattern::new(&steps, false);
+        let origin = Position::new(0.0, 0.0);
+        let target = Position::new(10.0, 0.0);
+
+        //when
+        let velocities: Vec<_> = pattern.shots.iter().map(|shot| shot.velocity(origin, Some(target))).collect();
+
+        //then
+        assert!((velocities[1].x - 2.0).abs() < 0.001);
+        assert!(velocities[1].y.abs() < 0.001);
+        assert!(velocities[0].y > 0.0 && velocities[2].y < 0.0);
+    }
+}
@@ -0,0 +1,205 @@
+use game::AnimationState;
+use game::DrawCounter;
+use game::FrameProfile;
+use game::Level;
+use game::Position;
+use game::ProfiledSection;
+use game::Profiler;
+use game::Renderer;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// two seconds worth of frames for the overlay's graph
+pub const HISTORY_FRAMES: usize = 120;
+
+const CSV_HEADER: &str = "frame,input_us,update_objects_us,check_collisions_us,remove_destroyed_objects_us,draw_us,objects,bullets,draw_calls";
+
+impl ProfiledSection {
+    pub fn all() -> [ProfiledSection; 5] {
+        [
+            ProfiledSection::Input,
+            ProfiledSection::UpdateObjects,
+            ProfiledSection::CheckCollisions,
+            ProfiledSection::RemoveDestroyedObjects,
+            ProfiledSection::Draw,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProfiledSection::Input => "input",
+            ProfiledSection::UpdateObjects => "update",
+            ProfiledSection::CheckCollisions => "collisions",
+            ProfiledSection::RemoveDestroyedObjects => "removal",
+            ProfiledSection::Draw => "draw",
+        }
+    }
+}
+
+impl FrameProfile {
+    pub fn total(&self) -> Duration {
+        self.timings.iter().sum()
+    }
+}
+
+impl Profiler {
+    // A section timed more than once in a frame adds up, a networked game replays frames
+    pub fn add(&mut self, section: ProfiledSection, elapsed: Duration) {
+        self.current[section as usize] += elapsed;
+    }
+
+    pub fn end_frame(&mut self, objects: usize, bullets: usize, draw_calls: u32) {
+        let frame = FrameProfile { timings: self.current, objects, bullets, draw_calls };
+        self.current = Default::default();
+
+        if let Some(ref mut recording) = self.recording {
+            recording.push(frame.clone());
+        }
+        if self.history.len() == HISTORY_FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame);
+    }
+
+    pub fn history(&self) -> &VecDeque<FrameProfile> {
+        &self.history
+    }
+
+    pub fn average(&self, section: ProfiledSection) -> Duration {
+        if self.history.is_empty() {
+            return Duration::default();
+        }
+        let total: Duration = self.history.iter().map(|frame| frame.timings[section as usize]).sum();
+        total / self.history.len() as u32
+    }
+
+    // Every frame from now on is kept for the CSV export
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    // One line per recorded frame, timings in microseconds
+    pub fn csv(&self) -> Option<String> {
+        let recording = self.recording.as_ref()?;
+        let mut csv = String::from(CSV_HEADER);
+        for (index, frame) in recording.iter().enumerate() {
+            let timings: Vec<String> = frame.timings.iter().map(|timing| timing.as_micros().to_string()).collect();
+            csv.push_str(&format!("\n{},{},{},{},{}", index, timings.join(","), frame.objects, frame.bullets, frame.draw_calls));
+        }
+        csv.push('\n');
+        Some(csv)
+    }
+}
+
+impl<'a, R: Renderer> DrawCounter<'a, R> {
+    pub fn new(renderer: &'a mut R) -> DrawCounter<'a, R> {
+        DrawCounter { renderer, calls: 0 }
+    }
+
+    pub fn calls(&self) -> u32 {
+        self.calls
+    }
+}
+
+impl<'a, R: Renderer> Renderer for DrawCounter<'a, R> {
+    fn clear_scene(&mut self) {
+        self.renderer.clear_scene();
+    }
+
+    fn draw_scene(&mut self) {
+        self.renderer.draw_scene();
+    }
+
+    fn draw_texture(&mut self, texture_id: &str, position: Position, level: &Level) {
+        self.calls += 1;
+        self.renderer.draw_texture(texture_id, position, level);
+    }
+
+    fn draw_tile(&mut self, texture_id: &str, position: Position, tile_id: u8) {
+        self.calls += 1;
+        self.renderer.draw_tile(texture_id, position, tile_id);
+    }
+
+    fn draw_frame(&mut self, animation: &AnimationState, position: Position, level: &Level) {
+        self.calls += 1;
+        self.renderer.draw_frame(animation, position, level);
+    }
+
+    fn draw_text(&mut self, text: &str, position: Position) {
+        self.calls += 1;
+        self.renderer.draw_text(text, position);
+    }
+
+    fn fill_rect(&mut self, position: Position, width: u32, height: u32, color: (u8, u8, u8)) {
+        self.calls += 1;
+        self.renderer.fill_rect(position, width, height, color);
+    }
+
+    fn draw_rect(&mut self, position: Position, width: u32, height: u32, color: (u8, u8, u8)) {
+        self.calls += 1;
+        self.renderer.draw_rect(position, width, height, color);
+    }
+
+    fn draw_line(&mut self, from: Position, to: Position, color: (u8, u8, u8)) {
+        self.calls += 1;
+        self.renderer.draw_line(from, to, color);
+    }
+
+    fn draw_chunk(&mut self, texture_id: &str, position: Position, level: &Level, layer: usize, chunk: (u32, u32)) {
+        self.calls += 1;
+        self.renderer.draw_chunk(texture_id, position, level, layer, chunk);
+    }
+
+    fn prepare_level(&mut self, level: &Level) {
+        self.renderer.prepare_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::ProfiledSection;
+    use game::Profiler;
+    use game::profiler::HISTORY_FRAMES;
+    use std::time::Duration;
+
+    #[test]
+    fn test_keeping_the_last_frames() {
+        //given
+        let mut profiler = Profiler::default();
+
+        //when
+        for frame in 0..HISTORY_FRAMES as u64 + 10 {
+            profiler.add(ProfiledSection::Draw, Duration::from_micros(frame));
+            profiler.add(ProfiledSection::Draw, Duration::from_micros(1));
+            profiler.end_frame(3, 1, 20);
+        }
+
+        //then
+        assert_eq!(profiler.history().len(), HISTORY_FRAMES);
+        assert_eq!(profiler.history()[0].timings[ProfiledSection::Draw as usize], Duration::from_micros(11));
+        assert_eq!(profiler.average(ProfiledSection::Input), Duration::default());
+        assert_eq!(profiler.csv(), None);
+    }
+
+    #[test]
+    fn test_exporting_recorded_frames() {
+        //given
+        let mut profiler = Profiler::default();
+        profiler.end_frame(1, 0, 5);
+        profiler.start_recording();
+
+        //when
+        profiler.add(ProfiledSection::Input, Duration::from_micros(12));
+        profiler.add(ProfiledSection::CheckCollisions, Duration::from_micros(340));
+        profiler.end_frame(24, 10, 31);
+        profiler.end_frame(23, 9, 30);
+
+        //then
+        let csv = profiler.csv().unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("frame,input_us,"));
+        assert_eq!(lines[1], "0,12,0,340,0,0,24,10,31");
+        assert_eq!(lines[2], "1,0,0,0,0,0,23,9,30");
+    }
+}
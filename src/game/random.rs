@@ -0,0 +1,129 @@
+use game::Rng;
+use game::RngStream;
+use game::RngSystem;
+
+const SYSTEMS: [RngSystem; 4] = [RngSystem::Patterns, RngSystem::Drops, RngSystem::Effects, RngSystem::Enemies];
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            seed,
+            streams: SYSTEMS
+                .iter()
+                .map(|system| RngStream::new(seed ^ (*system as u64 + 1).wrapping_mul(0xA076_1D64_78BD_642F)))
+                .collect(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stream(&mut self, system: RngSystem) -> &mut RngStream {
+        &mut self.streams[system as usize]
+    }
+
+    // Position of every stream, enough to continue the same sequences after a restore
+    pub fn states(&self) -> Vec<u64> {
+        self.streams.iter().map(|stream| stream.state).collect()
+    }
+
+    pub fn restore(seed: u64, states: &[u64]) -> Result<Rng, String> {
+        if states.len() != SYSTEMS.len() {
+            return Err(format!("Expected {} random streams, got {}", SYSTEMS.len(), states.len()));
+        }
+        Ok(Rng {
+            seed,
+            streams: states.iter().map(|state| RngStream { state: *state }).collect(),
+        })
+    }
+}
+
+// SplitMix64, small and fast with a single word of state
+impl RngStream {
+    fn new(seed: u64) -> RngStream {
+        let mut stream = RngStream { state: seed };
+        stream.next_u64();
+        stream
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1), from the top 24 bits so every value is exact in an f32
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::Rng;
+    use game::RngSystem;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        //given
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+
+        //when
+        let first: Vec<u64> = (0..5).map(|_| a.stream(RngSystem::Patterns).next_u64()).collect();
+        let second: Vec<u64> = (0..5).map(|_| b.stream(RngSystem::Patterns).next_u64()).collect();
+
+        //then
+        assert_eq!(first, second);
+        assert_ne!(Rng::new(43).stream(RngSystem::Patterns).next_u64(), first[0]);
+    }
+
+    #[test]
+    fn test_streams_are_independent() {
+        //given
+        let mut quiet = Rng::new(7);
+        let mut busy = Rng::new(7);
+
+        //when
+        for _ in 0..10 {
+            busy.stream(RngSystem::Effects).next_u64();
+        }
+
+        //then
+        assert_eq!(quiet.stream(RngSystem::Drops).next_u64(), busy.stream(RngSystem::Drops).next_u64());
+    }
+
+    #[test]
+    fn test_range() {
+        //given
+        let mut rng = Rng::new(1);
+
+        //when
+        let values: Vec<f32> = (0..1000).map(|_| rng.stream(RngSystem::Patterns).range(-5.0, 5.0)).collect();
+
+        //then
+        assert!(values.iter().all(|value| *value >= -5.0 && *value < 5.0));
+        assert!(values.iter().any(|value| *value < 0.0) && values.iter().any(|value| *value > 0.0));
+    }
+
+    #[test]
+    fn test_restoring_states() {
+        //given
+        let mut rng = Rng::new(9);
+        rng.stream(RngSystem::Enemies).next_u64();
+
+        //when
+        let mut restored = Rng::restore(rng.seed(), &rng.states()).unwrap();
+
+        //then
+        assert_eq!(restored.stream(RngSystem::Enemies).next_u64(), rng.stream(RngSystem::Enemies).next_u64());
+        assert!(Rng::restore(9, &[1, 2]).is_err());
+    }
+}
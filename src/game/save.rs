@@ -0,0 +1,578 @@
+use game::AnimationState;
+use game::BossPhase;
+use game::BossState;
+use game::BulletKind;
+use game::BulletState;
+use game::Engine;
+use game::EnemyState;
+use game::GameObject;
+use game::GameState;
+use game::Id;
+use game::InputHandler;
+use game::ObjectType;
+use game::PlayerState;
+use game::PlayerStatus;
+use game::PowerUpKind;
+use game::PowerUpState;
+use game::Record;
+use game::Renderer;
+use game::Rng;
+use game::Velocity;
+use game::WeaponTier;
+use game::states;
+use std::fmt::Display;
+use std::str::FromStr;
+
+// Bumped whenever a record changes shape, older saves are refused instead of half-loaded
+pub const SAVE_VERSION: u32 = 4;
+
+impl Record {
+    pub fn new(name: &str) -> Record {
+        Record {
+            name: name.to_string(),
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn with<T: Display>(mut self, key: &str, value: T) -> Record {
+        self.attributes.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn text(&self, key: &str) -> Result<&str, String> {
+        self.attributes
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| format!("Missing {:?} in save record {:?}", key, self.name))
+    }
+
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<T, String> {
+        let value = self.text(key)?;
+        value
+            .parse()
+            .map_err(|_| format!("Incorrect {:?} in save record {:?}: {:?}", key, self.name, value))
+    }
+
+    fn with_vector(self, x_key: &str, y_key: &str, vector: Velocity) -> Record {
+        self.with(x_key, vector.x).with(y_key, vector.y)
+    }
+
+    fn vector(&self, x_key: &str, y_key: &str) -> Result<Velocity, String> {
+        Ok(Velocity::new(self.get(x_key)?, self.get(y_key)?))
+    }
+
+    fn with_animation(self, animation: &AnimationState) -> Record {
+        self.with("clip", &animation.clip).with("elapsed", animation.elapsed)
+    }
+
+    fn animation(&self) -> Result<AnimationState, String> {
+        Ok(AnimationState {
+            clip: self.get("clip")?,
+            elapsed: self.get("elapsed")?,
+        })
+    }
+}
+
+impl<R, I> Engine<R, I>
+    where
+        R: Renderer,
+        I: InputHandler,
+{
+    // Everything that changes while playing; tiles are not part of it, they come with the map
+    pub fn snapshot(&self) -> Vec<Record> {
+        let mut records = vec![
+            Record::new("campaign").with("stage", self.campaign.current),
+            Record::new("level")
+                .with_vector("x", "y", self.level.position)
+                .with("halted", self.level.is_halted)
+                .with("speed", self.level.scroll_speed),
+            Record::new("hud").with("bombs", self.hud.bombs),
+            Record::new("rng")
+                .with("seed", self.rng.seed())
+                .with("streams", self.rng.states().iter().map(u64::to_string).collect::<Vec<_>>().join(",")),
+        ];
+        records.extend(self.players.iter().enumerate().map(|(index, status)| status.to_record(index)));
+        records.extend(
+            self.game_objects
+                .iter()
+                .flatten()
+                .filter(|game_object| !game_object.is_destroyed())
+                .filter_map(GameObject::to_record),
+        );
+        records
+    }
+
+    // Puts the engine back to a snapshot, nothing changes unless every record could be read
+    pub fn restore(&mut self, records: &[Record]) -> Result<(), String> {
+        let mut stage = self.campaign.current;
+        let mut level = (self.level.position, self.level.is_halted, self.level.scroll_speed);
+        let mut players = self.players.clone();
+        let mut hud = self.hud.clone();
+        let mut rng = self.rng.clone();
+        let mut game_objects = Vec::new();
+
+        for record in records {
+            match record.name.as_str() {
+                "campaign" => stage = record.get("stage")?,
+                "level" => level = (record.vector("x", "y")?, record.get("halted")?, record.get("speed")?),
+                "player" => {
+                    let index: usize = record.get("index")?;
+                    // a co-op save continued alone leaves the second player behind
+                    if index < players.len() {
+                        players[index] = PlayerStatus::from_record(record)?;
+                    }
+                }
+                "hud" => hud.bombs = record.get("bombs")?,
+                "rng" => {
+                    let states = record
+                        .text("streams")?
+                        .split(',')
+                        .map(|state| state.parse().map_err(|_| format!("Incorrect random stream: {:?}", state)))
+                        .collect::<Result<Vec<u64>, String>>()?;
+                    rng = Rng::restore(record.get("seed")?, &states)?;
+                }
+                "object" => game_objects.push(Some(GameObject::from_record(record)?)),
+                _ => return Err(format!("Unknown save record: {:?}", record.name)),
+            }
+        }
+        if !self.campaign.stages.is_empty() && stage >= self.campaign.stages.len() {
+            return Err(format!("Saved stage {} is not part of the campaign", stage + 1));
+        }
+
+        if let Some(last_id) = game_objects.iter().flatten().map(|game_object| game_object.id).max() {
+            states::reserve_ids(last_id);
+        }
+        let player_count = self.player_count;
+        game_objects.retain(|game_object| match game_object {
+            Some(GameObject { player: Some(ref player), .. }) => player.index < player_count,
+            _ => true,
+        });
+        let (position, is_halted, scroll_speed) = level;
+        self.campaign.current = stage;
+        self.level.position = position;
+        self.level.is_halted = is_halted;
+        self.level.scroll_speed = scroll_speed;
+        self.level.checkpoints.retain(|checkpoint| *checkpoint < position.y);
+        self.players = players;
+        self.hud = hud;
+        self.hud.boss_health = None;
+        self.rng = rng;
+        self.game_objects = game_objects;
+        self.state = GameState::Playing;
+        Ok(())
+    }
+}
+
+impl GameObject {
+    pub fn to_record(&self) -> Option<Record> {
+        let record = Record::new("object")
+            .with("id", self.id)
+            .with("type", format!("{:?}", self.object_type));
+
+        match (&self.player, &self.enemy, &self.bullet, &self.power_up, &self.boss) {
+            (Some(ref player), _, _, _, _) => Some(player.to_record(record)),
+            (_, Some(ref enemy), _, _, _) => Some(enemy.to_record(record)),
+            (_, _, Some(ref bullet), _, _) => Some(bullet.to_record(record)),
+            (_, _, _, Some(ref power_up), _) => Some(power_up.to_record(record)),
+            (_, _, _, _, Some(ref boss)) => Some(boss.to_record(record)),
+            _ => None,
+        }
+    }
+
+    fn from_record(record: &Record) -> Result<GameObject, String> {
+        let id = record.get("id")?;
+        let object_type = ObjectType::parse(record.text("type")?);
+        let mut game_object = GameObject {
+            id,
+            object_type,
+            player: None,
+            enemy: None,
+            bullet: None,
+            power_up: None,
+            boss: None,
+        };
+
+        match object_type {
+            ObjectType::Player => game_object.player = Some(PlayerState::from_record(id, record)?),
+            ObjectType::Enemy => game_object.enemy = Some(EnemyState::from_record(id, record)?),
+            ObjectType::Bullet => game_object.bullet = Some(BulletState::from_record(record)?),
+            ObjectType::PowerUp => game_object.power_up = Some(PowerUpState::from_record(record)?),
+            ObjectType::Boss => game_object.boss = Some(BossState::from_record(id, record)?),
+            ObjectType::Unknown => return Err(format!("Unknown object type in save record: {:?}", record.text("type")?)),
+        }
+        Ok(game_object)
+    }
+}
+
+impl PlayerState {
+    fn to_record(&self, record: Record) -> Record {
+        record
+            .with_vector("x", "y", self.position)
+            .with("width", self.width)
+            .with("height", self.height)
+            .with_vector("vx", "vy", self.velocity)
+            .with_vector("thrustX", "thrustY", self.thrust)
+            .with("acceleration", self.acceleration)
+            .with("drag", self.drag)
+            .with("maxSpeed", self.max_speed)
+            .with("player", self.index)
+            .with("shooting", self.is_shooting)
+            .with("weapon", self.weapon.name())
+            .with("cooldown", self.cooldown)
+            .with_animation(&self.animation)
+    }
+
+    fn from_record(id: Id, record: &Record) -> Result<PlayerState, String> {
+        let mut player = PlayerState::new(id, record.vector("x", "y")?, record.get("width")?, record.get("height")?);
+        player.velocity = record.vector("vx", "vy")?;
+        player.thrust = record.vector("thrustX", "thrustY")?;
+        player.acceleration = record.get("acceleration")?;
+        player.drag = record.get("drag")?;
+        player.max_speed = record.get("maxSpeed")?;
+        player.index = record.get("player")?;
+        player.is_shooting = record.get("shooting")?;
+        player.weapon = WeaponTier::parse(record.text("weapon")?)?;
+        player.cooldown = record.get("cooldown")?;
+        player.animation = record.animation()?;
+        Ok(player)
+    }
+}
+
+impl PlayerStatus {
+    fn to_record(&self, index: usize) -> Record {
+        Record::new("player")
+            .with("index", index)
+            .with("id", self.id)
+            .with("points", self.score.points)
+            .with("multiplier", self.score.multiplier)
+            .with("maxMultiplier", self.score.max_multiplier)
+            .with("kills", self.score.kills)
+            .with("comboTicks", self.score.combo_ticks)
+            .with("lives", self.lives)
+            .with("weapon", self.weapon.name())
+    }
+
+    fn from_record(record: &Record) -> Result<PlayerStatus, String> {
+        let mut status = PlayerStatus::new(record.get("id")?);
+        status.score.points = record.get("points")?;
+        status.score.multiplier = record.get("multiplier")?;
+        status.score.max_multiplier = record.get("maxMultiplier")?;
+        status.score.kills = record.get("kills")?;
+        status.score.combo_ticks = record.get("comboTicks")?;
+        status.lives = record.get("lives")?;
+        status.weapon = WeaponTier::parse(record.text("weapon")?)?;
+        Ok(status)
+    }
+}
+
+impl EnemyState {
+    fn to_record(&self, record: Record) -> Record {
+        let mut record = record
+            .with_vector("x", "y", self.position)
+            .with("width", self.width)
+            .with("height", self.height)
+            .with_vector("vx", "vy", self.velocity)
+            .with("score", self.score)
+            .with("patternTicks", self.pattern_ticks)
+            .with("engaged", self.is_engaged)
+            .with_animation(&self.animation);
+        if let Some(drop) = self.drop {
+            record = record.with("drop", format!("{:?}", drop));
+        }
+        if let Some(ref pattern) = self.pattern {
+            record = record.with("pattern", pattern);
+        }
+        record
+    }
+
+    fn from_record(id: Id, record: &Record) -> Result<EnemyState, String> {
+        let mut enemy = EnemyState::new(id, record.vector("x", "y")?, record.get("width")?, record.get("height")?);
+        enemy.velocity = record.vector("vx", "vy")?;
+        enemy.score = record.get("score")?;
+        enemy.pattern_ticks = record.get("patternTicks")?;
+        enemy.is_engaged = record.get("engaged")?;
+        enemy.animation = record.animation()?;
+        enemy.drop = match record.text("drop") {
+            Ok(drop) => Some(PowerUpKind::parse(drop)?),
+            Err(_) => None,
+        };
+        enemy.pattern = record.text("pattern").ok().map(String::from);
+        Ok(enemy)
+    }
+}
+
+impl BulletState {
+    fn to_record(&self, record: Record) -> Record {
+        record
+            .with_vector("x", "y", self.position)
+            .with_vector("vx", "vy", self.velocity)
+            .with("kind", format!("{:?}", self.kind))
+            .with("shooterType", format!("{:?}", self.shooter_type))
+            .with("shooterId", self.shooter_id)
+    }
+
+    fn from_record(record: &Record) -> Result<BulletState, String> {
+        Ok(BulletState::new(
+            record.vector("x", "y")?,
+            record.vector("vx", "vy")?,
+            BulletKind::parse(record.text("kind")?)?,
+            ObjectType::parse(record.text("shooterType")?),
+            record.get("shooterId")?,
+        ))
+    }
+}
+
+impl PowerUpState {
+    fn to_record(&self, record: Record) -> Record {
+        record
+            .with_vector("x", "y", self.position)
+            .with("kind", format!("{:?}", self.kind))
+            .with_animation(&self.animation)
+    }
+
+    fn from_record(record: &Record) -> Result<PowerUpState, String> {
+        let mut power_up = PowerUpState::new(record.vector("x", "y")?, PowerUpKind::parse(record.text("kind")?)?);
+        power_up.animation = record.animation()?;
+        Ok(power_up)
+    }
+}
+
+impl BossState {
+    // Parts and phases use the same text as the map properties they were read from
+    fn to_record(&self, record: Record) -> Record {
+        let parts: Vec<String> = self
+            .parts
+            .iter()
+            .map(|part| format!("{},{},{},{}", part.x, part.y, part.width, part.height))
+            .collect();
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|phase| format!("{}:{}:{}", phase.threshold, phase.speed, phase.pattern.as_ref().map_or("", String::as_str)))
+            .collect();
+
+        record
+            .with_vector("x", "y", self.position)
+            .with("width", self.width)
+            .with("height", self.height)
+            .with_vector("vx", "vy", self.velocity)
+            .with("health", self.health)
+            .with("maxHealth", self.max_health)
+            .with("score", self.score)
+            .with("parts", parts.join(";"))
+            .with("phases", phases.join(";"))
+            .with("phase", self.phase)
+            .with("patternTicks", self.pattern_ticks)
+            .with_animation(&self.animation)
+    }
+
+    fn from_record(id: Id, record: &Record) -> Result<BossState, String> {
+        let mut boss = BossState::new(id, record.vector("x", "y")?, record.get("width")?, record.get("height")?);
+        boss.set_parts(record.text("parts")?)?;
+        boss.set_phases(record.text("phases")?.split(';').map(BossPhase::parse).collect::<Result<_, _>>()?);
+        boss.velocity = record.vector("vx", "vy")?;
+        boss.health = record.get("health")?;
+        boss.max_health = record.get("maxHealth")?;
+        boss.score = record.get("score")?;
+        boss.phase = record.get("phase")?;
+        boss.pattern_ticks = record.get("patternTicks")?;
+        boss.animation = record.animation()?;
+        if boss.phase >= boss.phases.len() {
+            return Err(format!("Boss phase {} out of range", boss.phase));
+        }
+        Ok(boss)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::AnimationState;
+    use game::BulletKind;
+    use game::BulletState;
+    use game::Engine;
+    use game::GameObject;
+    use game::GameState;
+    use game::InputHandler;
+    use game::InputState;
+    use game::Level;
+    use game::ObjectType;
+    use game::Position;
+    use game::PowerUpKind;
+    use game::Record;
+    use game::Renderer;
+    use game::RngSystem;
+    use game::Velocity;
+    use game::WeaponTier;
+
+    struct MockRenderer {}
+
+    struct MockInputHandler {}
+
+    impl Renderer for MockRenderer {
+        fn clear_scene(&mut self) {
+            unimplemented!()
+        }
+
+        fn draw_scene(&mut self) {
+            unimplemented!()
+        }
+
+        fn draw_texture(&mut self, _texture_id: &str, _position: Position, _level: &Level) {
+            unimplemented!()
+        }
+
+        fn draw_tile(&mut self, _texture_id: &str, _position: Position, _tile_id: u8) {
+            unimplemented!()
+        }
+
+        fn draw_frame(&mut self, _animation: &AnimationState, _position: Position, _level: &Level) {
+            unimplemented!()
+        }
+
+        fn draw_text(&mut self, _text: &str, _position: Position) {
+            unimplemented!()
+        }
+
+        fn fill_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+
+        fn draw_rect(&mut self, _position: Position, _width: u32, _height: u32, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+
+        fn draw_line(&mut self, _from: Position, _to: Position, _color: (u8, u8, u8)) {
+            unimplemented!()
+        }
+    }
+
+    impl InputHandler for MockInputHandler {
+        fn capture(&mut self) -> Vec<InputState> {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        //given
+        let mut player = GameObject::new(1, Position::new(200.5, 1400.25), ObjectType::Player, 65, 65);
+        player.player.as_mut().unwrap().weapon = WeaponTier::Homing;
+        let mut enemy = GameObject::new(2, Position::new(100.0, 1300.0), ObjectType::Enemy, 65, 65);
+        enemy.enemy.as_mut().unwrap().drop = Some(PowerUpKind::Bomb);
+        enemy.enemy.as_mut().unwrap().pattern = Some(String::from("spiral"));
+        let mut boss = GameObject::new(3, Position::new(320.0, 40.0), ObjectType::Boss, 160, 96);
+        boss.boss.as_mut().unwrap().damage(60);
+        let bullet = GameObject::from_bullet(
+            4,
+            BulletState::new(Position::new(10.0, 20.0), Velocity::new(0.1, -4.0), BulletKind::Orb, ObjectType::Enemy, 2),
+        );
+        let power_up = GameObject::new_power_up(5, Position::new(50.0, 60.0), PowerUpKind::Life);
+        let game_objects = vec![Some(player), None, Some(enemy), Some(boss), Some(bullet), Some(power_up)];
+
+        let mut engine = Engine::new(game_objects, Level::new(20, 60, vec![]), MockRenderer {}, MockInputHandler {});
+        engine.players[0].score.add_kill(100);
+        engine.players[0].score.add_kill(100);
+        engine.players[0].lives = 1;
+        engine.level.position.y -= 0.5;
+        engine.set_seed(1234);
+        engine.rng.stream(RngSystem::Patterns).next_u64();
+
+        //when
+        let records = engine.snapshot();
+        let mut restored = Engine::new(vec![], Level::new(20, 60, vec![]), MockRenderer {}, MockInputHandler {});
+        let result = restored.restore(&records);
+
+        //then
+        assert_eq!(result, Ok(()));
+        assert_eq!(restored.game_objects, engine.game_objects.into_iter().filter(Option::is_some).collect::<Vec<_>>());
+        assert_eq!(restored.players, engine.players);
+        assert_eq!(restored.hud, engine.hud);
+        assert_eq!(restored.level.position, engine.level.position);
+        assert_eq!(restored.rng, engine.rng);
+    }
+
+    #[test]
+    fn test_snapshot_keeps_scroll_speed() {
+        //given
+        let mut engine = Engine::new(vec![], Level::new(20, 60, vec![]), MockRenderer {}, MockInputHandler {});
+        engine.level.scroll_speed = 2.5;
+
+        //when
+        let records = engine.snapshot();
+        let mut restored = Engine::new(vec![], Level::new(20, 60, vec![]), MockRenderer {}, MockInputHandler {});
+        let result = restored.restore(&records);
+
+        //then
+        assert_eq!(result, Ok(()));
+        assert_eq!(restored.level.scroll_speed, 2.5);
+    }
+
+    #[test]
+    fn test_restoring_from_fixture() {
+        //given
+        let records = vec![
+            Record::new("level").with("x", 0).with("y", 320).with("halted", false).with("speed", 1),
+            Record::new("hud").with("bombs", 0),
+            Record::new("player")
+                .with("index", 0)
+                .with("id", 3)
+                .with("points", 1500)
+                .with("multiplier", 1)
+                .with("maxMultiplier", 4)
+                .with("kills", 12)
+                .with("comboTicks", 0)
+                .with("lives", 2)
+                .with("weapon", "LASER"),
+            Record::new("player")
+                .with("index", 1)
+                .with("id", 4)
+                .with("points", 900)
+                .with("multiplier", 1)
+                .with("maxMultiplier", 2)
+                .with("kills", 7)
+                .with("comboTicks", 0)
+                .with("lives", 1)
+                .with("weapon", "SINGLE"),
+            Record::new("object")
+                .with("id", 7)
+                .with("type", "PowerUp")
+                .with("x", 100)
+                .with("y", 400)
+                .with("kind", "Weapon")
+                .with("clip", "powerUpWeapon")
+                .with("elapsed", 0),
+        ];
+        let mut engine = Engine::new(vec![], Level::new(20, 60, vec![]), MockRenderer {}, MockInputHandler {});
+        engine.state = GameState::GameOver { name: String::new(), is_entering_name: false };
+
+        //when
+        let result = engine.restore(&records);
+
+        //then
+        assert_eq!(result, Ok(()));
+        assert_eq!(engine.state, GameState::Playing);
+        assert_eq!(engine.level.position, Position::new(0.0, 320.0));
+        assert_eq!(engine.players.len(), 1);
+        assert_eq!(engine.players[0].weapon, WeaponTier::Laser);
+        assert_eq!(engine.players[0].score.points, 1500);
+        assert_eq!(engine.game_objects, vec![Some(GameObject::new_power_up(7, Position::new(100.0, 400.0), PowerUpKind::Weapon))]);
+    }
+
+    #[test]
+    fn test_broken_records_change_nothing() {
+        //given
+        let records = vec![
+            Record::new("hud").with("bombs", 9),
+            Record::new("object").with("id", 7).with("type", "Player"),
+        ];
+        let mut engine = Engine::new(vec![], Level::new(20, 60, vec![]), MockRenderer {}, MockInputHandler {});
+
+        //when
+        let result = engine.restore(&records);
+
+        //then
+        assert!(result.is_err());
+        assert_eq!(engine.hud.bombs, 3);
+    }
+}
@@ -0,0 +1,198 @@
+use game::HighScore;
+use game::HighScores;
+use game::Id;
+use game::PlayerStatus;
+use game::Score;
+use game::Tally;
+use game::WeaponTier;
+use helpers::parsers::high_score_file;
+use std::cmp::Reverse;
+use FPS;
+
+// every kill within the window raises the multiplier, once it runs out the multiplier drops by one
+const COMBO_WINDOW: u32 = 2 * FPS as u32;
+const MAX_MULTIPLIER: u32 = 8;
+const LIFE_BONUS: u32 = 1000;
+const HIGH_SCORES_KEPT: usize = 10;
+const START_LIVES: u8 = 3;
+
+impl Score {
+    pub fn new() -> Score {
+        Score {
+            points: 0,
+            multiplier: 1,
+            max_multiplier: 1,
+            kills: 0,
+            combo_ticks: 0,
+        }
+    }
+
+    pub fn add_kill(&mut self, value: u32) {
+        if self.combo_ticks > 0 {
+            self.multiplier = (self.multiplier + 1).min(MAX_MULTIPLIER);
+            self.max_multiplier = self.max_multiplier.max(self.multiplier);
+        }
+        self.combo_ticks = COMBO_WINDOW;
+
+        self.points += value * self.multiplier;
+        self.kills += 1;
+    }
+
+    pub fn update(&mut self) {
+        if self.combo_ticks == 0 {
+            return;
+        }
+
+        self.combo_ticks -= 1;
+        if self.combo_ticks == 0 && self.multiplier > 1 {
+            self.multiplier -= 1;
+            self.combo_ticks = COMBO_WINDOW;
+        }
+    }
+
+    // Remaining lives are paid out once the level is cleared
+    pub fn tally(&mut self, lives: u8) -> Tally {
+        let lives_bonus = u32::from(lives) * LIFE_BONUS;
+        self.points += lives_bonus;
+        self.multiplier = 1;
+        self.combo_ticks = 0;
+
+        Tally {
+            points: self.points,
+            kills: self.kills,
+            max_multiplier: self.max_multiplier,
+            lives_bonus,
+        }
+    }
+}
+
+impl PlayerStatus {
+    pub fn new(id: Id) -> PlayerStatus {
+        PlayerStatus {
+            id,
+            score: Score::new(),
+            lives: START_LIVES,
+            weapon: WeaponTier::Single,
+        }
+    }
+
+    pub fn tally(&mut self) -> Tally {
+        self.score.tally(self.lives)
+    }
+}
+
+impl HighScores {
+    pub fn load(filename: &str) -> HighScores {
+        let entries = high_score_file::parse(filename)
+            .into_iter()
+            .map(|(name, score)| HighScore { name, score })
+            .collect();
+
+        let mut high_scores = HighScores {
+            filename: Some(filename.to_string()),
+            entries,
+        };
+        high_scores.sort();
+        high_scores
+    }
+
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.entries.len() < HIGH_SCORES_KEPT || self.entries.iter().any(|entry| entry.score < score))
+    }
+
+    pub fn insert(&mut self, name: &str, score: u32) {
+        self.entries.push(HighScore { name: name.to_string(), score });
+        self.sort();
+
+        if let Some(ref filename) = self.filename {
+            let entries: Vec<(String, u32)> =
+                self.entries.iter().map(|entry| (entry.name.clone(), entry.score)).collect();
+            if let Err(e) = high_score_file::write(filename, &entries) {
+                println!("Error saving high scores to {:?}: {}", filename, e);
+            }
+        }
+    }
+
+    // highest first, equal scores keep the order they were reached in
+    fn sort(&mut self) {
+        self.entries.sort_by_key(|entry| Reverse(entry.score));
+        self.entries.truncate(HIGH_SCORES_KEPT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::HighScores;
+    use game::Score;
+    use FPS;
+
+    #[test]
+    fn test_combo_multiplier() {
+        //given
+        let mut score = Score::new();
+
+        //when
+        score.add_kill(100);
+        score.update();
+        score.add_kill(100);
+        score.add_kill(100);
+
+        //then
+        assert_eq!(score.points, 100 + 200 + 300);
+        assert_eq!(score.multiplier, 3);
+        assert_eq!(score.kills, 3);
+    }
+
+    #[test]
+    fn test_combo_decay() {
+        //given
+        let mut score = Score::new();
+        score.add_kill(100);
+        score.add_kill(100);
+        score.add_kill(100);
+
+        //when
+        for _ in 0..2 * FPS as u32 {
+            score.update();
+        }
+
+        //then
+        assert_eq!(score.multiplier, 2);
+        assert_eq!(score.max_multiplier, 3);
+    }
+
+    #[test]
+    fn test_tally() {
+        //given
+        let mut score = Score::new();
+        score.add_kill(150);
+
+        //when
+        let tally = score.tally(2);
+
+        //then
+        assert_eq!(tally.lives_bonus, 2000);
+        assert_eq!(tally.points, 2150);
+        assert_eq!(score.multiplier, 1);
+    }
+
+    #[test]
+    fn test_high_scores() {
+        //given
+        let mut high_scores = HighScores::default();
+        for score in 1..=10 {
+            high_scores.insert("AAA", score * 100);
+        }
+
+        //when
+        let qualifies = high_scores.qualifies(150);
+        high_scores.insert("BOB", 150);
+
+        //then
+        assert!(qualifies);
+        assert!(!high_scores.qualifies(100));
+        assert_eq!(high_scores.entries.len(), 10);
+        assert_eq!(high_scores.entries[0].score, 1000);
+        assert_eq!(high_scores.entries[9].name, "BOB");
+    }
+}
@@ -1,3 +1,7 @@
+use game::AnimationState;
+use game::BossPhase;
+use game::BulletPattern;
+use game::BulletKind;
 use game::BulletState;
 use game::CollisionState;
 use game::EnemyState;
@@ -8,49 +12,144 @@ use game::Level;
 use game::ObjectType;
 use game::PlayerState;
 use game::Position;
+use game::PowerUpKind;
+use game::PowerUpState;
 use game::Renderer;
+use game::RngSystem;
+use game::Shot;
 use game::Velocity;
+use game::WeaponTier;
+use game::World;
 use SCREEN_SIZE;
+use cgmath::InnerSpace;
 use std::collections::HashMap;
+use std::num::ParseFloatError;
 use std::num::ParseIntError;
 use std::sync::atomic::{self, AtomicUsize};
-use std::time::Duration;
-use std::time::SystemTime;
 
 lazy_static! {
     static ref OBJECT_COUNTER: AtomicUsize = <AtomicUsize>::new(1);
-    static ref SHOOT_DELAY: Duration = Duration::new(0, 200_000_000);
 }
 
+const DEFAULT_ENEMY_SCORE: u32 = 100;
+const HOMING_TURN_RATE: f32 = 0.25;
+const HOMING_MAX_SPEED: f32 = 3.0;
+const ORB_SIZE: u32 = 12;
+// the sprites of a white plane and of the boss
+const ENEMY_SIZE: u32 = 65;
+const BOSS_SIZE: (u32, u32) = (160, 96);
+const PLAYER_SIZE: u32 = 65;
+// how the player's plane handles, per tick
+const PLAYER_ACCELERATION: f32 = 0.6;
+const PLAYER_DRAG: f32 = 0.15;
+const PLAYER_MAX_SPEED: f32 = 3.0;
+// anything slower is treated as standing still, so drag settles on exactly zero
+const MIN_SPEED: f32 = 0.01;
+
 pub fn create_game_object(properties: &HashMap<String, String>) -> Result<GameObject, String> {
-    let object_type = match properties
-        .get("type")
-        .unwrap_or_else(|| panic!("Unknown type"))
-        .as_str()
-        {
-            "Player" => ObjectType::Player,
-            "Enemy" => ObjectType::Enemy,
-            _ => ObjectType::Unknown,
-        };
+    let object_type = match ObjectType::parse(properties.get("type").unwrap_or_else(|| panic!("Unknown type"))) {
+        // bullets are only ever fired, never placed on a map
+        ObjectType::Bullet => ObjectType::Unknown,
+        object_type => object_type,
+    };
 
     let height = parse_int(properties, "height")?;
     let width = parse_int(properties, "width")?;
-    let x = parse_int(properties, "x")? as i32;
-    let y = parse_int(properties, "y")? as i32;
+    let x = parse_float(properties, "x")?;
+    let y = parse_float(properties, "y")?;
 
-    Ok(GameObject::new(
+    let mut game_object = GameObject::new(
         next_id(),
         Position::new(x, y),
         object_type,
         height,
         width,
-    ))
+    );
+
+    if let Some(ref mut player) = game_object.player {
+        // "player" is 1 for the first player, 2 for the second
+        if properties.contains_key("player") {
+            let number = parse_int(properties, "player")?;
+            player.index = (number as usize).checked_sub(1).ok_or_else(|| String::from("Players are numbered from 1"))?;
+        }
+        if properties.contains_key("acceleration") {
+            player.acceleration = parse_float(properties, "acceleration")?;
+        }
+        if properties.contains_key("drag") {
+            player.drag = parse_float(properties, "drag")?;
+        }
+        if properties.contains_key("maxSpeed") {
+            player.max_speed = parse_float(properties, "maxSpeed")?;
+        }
+    }
+    if let Some(ref mut enemy) = game_object.enemy {
+        if properties.contains_key("score") {
+            enemy.score = parse_int(properties, "score")?;
+        }
+        if let Some(drop) = properties.get("drop") {
+            enemy.drop = Some(PowerUpKind::parse(drop)?);
+        }
+        enemy.pattern = properties.get("pattern").cloned();
+    }
+    if let Some(ref mut boss) = game_object.boss {
+        if properties.contains_key("health") {
+            boss.health = parse_int(properties, "health")?;
+            boss.max_health = boss.health;
+        }
+        if properties.contains_key("score") {
+            boss.score = parse_int(properties, "score")?;
+        }
+        if let Some(parts) = properties.get("parts") {
+            boss.set_parts(parts)?;
+        }
+        if let Some(phases) = properties.get("phases") {
+            boss.set_phases(phases.split(';').map(BossPhase::parse).collect::<Result<_, _>>()?);
+        }
+    }
+    if let Some(ref mut power_up) = game_object.power_up {
+        if let Some(kind) = properties.get("kind") {
+            *power_up = PowerUpState::new(power_up.position, PowerUpKind::parse(kind)?);
+        }
+    }
+    Ok(game_object)
+}
+
+pub fn spawn_bullet(bullet: BulletState) -> GameObject {
+    GameObject::from_bullet(next_id(), bullet)
+}
+
+pub fn spawn_power_up(position: Position, kind: PowerUpKind) -> GameObject {
+    GameObject::new_power_up(next_id(), position, kind)
+}
+
+// A player's plane coming back after losing a life, it keeps the ID its status is tracked by
+pub fn spawn_player(id: Id, index: usize, position: Position) -> GameObject {
+    let mut plane = GameObject::new(id, position, ObjectType::Player, PLAYER_SIZE, PLAYER_SIZE);
+    if let Some(ref mut player) = plane.player {
+        player.index = index;
+    }
+    plane
+}
+
+// Anything but a plane or a bullet, sized like the sprites it is drawn with
+pub fn spawn(object_type: ObjectType, position: Position) -> Result<GameObject, String> {
+    match object_type {
+        ObjectType::Enemy => Ok(GameObject::new(next_id(), position, object_type, ENEMY_SIZE, ENEMY_SIZE)),
+        ObjectType::Boss => Ok(GameObject::new(next_id(), position, object_type, BOSS_SIZE.0, BOSS_SIZE.1)),
+        ObjectType::PowerUp => Ok(spawn_power_up(position, PowerUpKind::Weapon)),
+        _ => Err(format!("{:?} can not be spawned", object_type)),
+    }
 }
 
 fn next_id() -> Id {
     OBJECT_COUNTER.fetch_add(1, atomic::Ordering::SeqCst)
 }
 
+// Objects restored from a save keep their ids, new ones must not reuse them
+pub fn reserve_ids(last_id: Id) {
+    OBJECT_COUNTER.fetch_max(last_id + 1, atomic::Ordering::SeqCst);
+}
+
 fn parse_int(properties: &HashMap<String, String>, attribute_name: &str) -> Result<u32, String> {
     properties
         .get(attribute_name)
@@ -59,98 +158,113 @@ fn parse_int(properties: &HashMap<String, String>, attribute_name: &str) -> Resu
         .map_err(|e: ParseIntError| e.to_string())
 }
 
+fn parse_float(properties: &HashMap<String, String>, attribute_name: &str) -> Result<f32, String> {
+    properties
+        .get(attribute_name)
+        .unwrap_or_else(|| panic!("Missing: {:?}", attribute_name))
+        .parse()
+        .map_err(|e: ParseFloatError| e.to_string())
+}
+
 impl PlayerState {
     pub fn new(id: Id, position: Position, width: u32, height: u32) -> Self {
         PlayerState {
             id,
+            index: 0,
             position,
             is_shooting: false,
-            last_shot_date: SystemTime::now(),
             is_destroyed: false,
-            velocity: Velocity::new(0, 0),
+            weapon: WeaponTier::Single,
+            cooldown: 0,
+            velocity: Velocity::new(0.0, 0.0),
+            thrust: Velocity::new(0.0, 0.0),
+            acceleration: PLAYER_ACCELERATION,
+            drag: PLAYER_DRAG,
+            max_speed: PLAYER_MAX_SPEED,
             width,
             height,
+            animation: AnimationState::new("plane"),
+            is_invulnerable: false,
+            is_noclip: false,
         }
     }
 
+    // Every plane sees all inputs and only follows the ones for its own player
     pub fn input(&mut self, input_state: &[InputState]) {
-        let mut new_velocity = Velocity::new(0, -1);
+        let mut thrust = Velocity::new(0.0, 0.0);
         for input in input_state {
-            match input {
-                InputState::Up => new_velocity += Velocity::new(0, -2),
-                InputState::Down => new_velocity += Velocity::new(0, 2),
-                InputState::Left => new_velocity += Velocity::new(-2, 0),
-                InputState::Right => new_velocity += Velocity::new(2, 0),
-                InputState::Shoot => self.is_shooting = true,
+            match *input {
+                InputState::Up(index) if index == self.index => thrust.y -= 1.0,
+                InputState::Down(index) if index == self.index => thrust.y += 1.0,
+                InputState::Left(index) if index == self.index => thrust.x -= 1.0,
+                InputState::Right(index) if index == self.index => thrust.x += 1.0,
+                InputState::Shoot(index) if index == self.index => self.is_shooting = true,
                 _ => {}
             }
         }
-        self.velocity = new_velocity;
+        // diagonals are not faster than straight lines
+        self.thrust = if thrust.magnitude2() > 0.0 { thrust.normalize() } else { thrust };
     }
 
     pub fn draw(&mut self, renderer: &mut Renderer, level: &Level) {
-        renderer.draw_frame("plane", self.position, level);
-    }
-
-    pub fn update(&mut self, level: &Level) -> Option<GameObject> {
-        self.position += self.calculate_velocity(level);
-
-        if self.is_shooting && self.is_allowed_to_shoot() { Some(self.shoots()) } else { None }
+        renderer.draw_frame(&self.animation, self.position, level);
     }
 
-    fn calculate_velocity(&self, level: &Level) -> Velocity {
-        let mut fixed_velocity = self.velocity;
-        let new_position = self.position + fixed_velocity;
-        let collision_padding = self.collision_padding();
-
-        if self.is_on_right_border(&new_position, &collision_padding) ||
-            self.is_on_left_border(&new_position, &collision_padding) {
-            fixed_velocity.x = 0;
-        }
+    pub fn update(&mut self, level: &Level) -> Vec<GameObject> {
+        self.accelerate();
+        // the plane drifts along with the scrolling view
+        self.position += self.velocity + level.scroll_velocity();
+        self.keep_on_screen(level);
+        self.animation.update();
 
-        if self.is_on_upper_border(&new_position, &collision_padding, &level) ||
-            self.is_on_lower_border(&new_position, &collision_padding, &level) {
-            fixed_velocity.y = -1;
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
         }
-
-        fixed_velocity
+        if self.is_shooting && self.cooldown == 0 { self.shoots() } else { Vec::new() }
     }
 
-    #[inline]
-    fn is_on_upper_border(&self, new_position: &Position, _collision_padding: &(u32, u32), level: &Level) -> bool {
-//        new_position.y + _collision_padding.0 as i32 <= level.position.y
-        new_position.y <= level.position.y
+    pub fn collect(&mut self, kind: PowerUpKind) {
+        if kind == PowerUpKind::Weapon {
+            self.weapon = self.weapon.upgrade();
+        }
     }
 
-    #[inline]
-    fn is_on_lower_border(&self, new_position: &Position, _collision_padding: &(u32, u32), level: &Level) -> bool {
-//        new_position.y + collision_padding.1 as i32 + self.height as i32 >= level.position.y + SCREEN_SIZE.1 as i32
-        new_position.y + self.height as i32 >= level.position.y + SCREEN_SIZE.1 as i32
-    }
+    fn accelerate(&mut self) {
+        self.velocity += self.thrust * self.acceleration;
+        self.velocity *= 1.0 - self.drag;
 
-    #[inline]
-    fn is_on_right_border(&self, new_position: &Position, _collision_padding: &(u32, u32)) -> bool {
-//        new_position.x + collision_padding.0 as i32 <= 0
-        new_position.x <= 0
+        let speed = self.velocity.magnitude();
+        if speed > self.max_speed {
+            self.velocity *= self.max_speed / speed;
+        } else if speed < MIN_SPEED {
+            self.velocity = Velocity::new(0.0, 0.0);
+        }
     }
 
-    #[inline]
-    fn is_on_left_border(&self, new_position: &Position, _collision_padding: &(u32, u32)) -> bool {
-//        new_position.x + collision_padding.0 as i32 + self.width as i32 >= SCREEN_SIZE.0 as i32
-        new_position.x + self.width as i32 >= SCREEN_SIZE.0 as i32
-    }
+    // Stops the plane at the edges of the view instead of letting it fly off
+    fn keep_on_screen(&mut self, level: &Level) {
+        if self.is_noclip {
+            return;
+        }
+        let max_x = SCREEN_SIZE.0 as f32 - self.width as f32;
+        let min_y = level.position.y;
+        let max_y = level.position.y + SCREEN_SIZE.1 as f32 - self.height as f32;
 
-    fn is_allowed_to_shoot(&self) -> bool {
-        let now = SystemTime::now();
-        let duration = now.duration_since(self.last_shot_date).unwrap();
-        duration.ge(&SHOOT_DELAY)
+        if self.position.x < 0.0 || self.position.x > max_x {
+            self.position.x = self.position.x.clamp(0.0, max_x);
+            self.velocity.x = 0.0;
+        }
+        if self.position.y < min_y || self.position.y > max_y {
+            self.position.y = self.position.y.clamp(min_y, max_y);
+            self.velocity.y = 0.0;
+        }
     }
 
-    fn shoots(&mut self) -> GameObject {
+    fn shoots(&mut self) -> Vec<GameObject> {
         self.is_shooting = false;
-        self.last_shot_date = SystemTime::now();
+        self.cooldown = self.weapon.cooldown();
 
-        GameObject::new_bullet(next_id(), self.position, ObjectType::Player, self.id)
+        self.weapon.fire(self.position, self.id).into_iter().map(spawn_bullet).collect()
     }
 }
 
@@ -162,56 +276,187 @@ impl EnemyState {
             is_destroyed: false,
             width,
             height,
-            velocity: Velocity::new(1, -1),
+            velocity: Velocity::new(1.0, -1.0),
+            animation: AnimationState::new("whitePlane"),
+            score: DEFAULT_ENEMY_SCORE,
+            drop: None,
+            pattern: None,
+            pattern_ticks: 0,
+            is_engaged: false,
         }
     }
 
     pub fn input(&mut self, _input_state: &[InputState]) {}
 
     pub fn draw(&mut self, renderer: &mut Renderer, level: &Level) {
-        renderer.draw_texture("whitePlane", self.position, level);
+        renderer.draw_frame(&self.animation, self.position, level);
     }
 
-    pub fn update(&mut self) -> Option<GameObject> {
-        if self.position.x == 0 as i32 {
-            self.velocity.x = 1;
-        } else if self.position.x + self.width as i32 == SCREEN_SIZE.0 as i32 {
-            self.velocity.x = -1;
+    // Does nothing until the view reaches it, from then on it flies along with the view
+    pub fn update(&mut self, world: &mut World) -> Vec<GameObject> {
+        self.animation.update();
+        if !self.is_engaged && !world.level.is_visible(self.position, (self.width, self.height)) {
+            return Vec::new();
+        }
+        self.is_engaged = true;
+
+        if self.position.x <= 0.0 {
+            self.velocity.x = 1.0;
+        } else if self.position.x + self.width as f32 >= SCREEN_SIZE.0 as f32 {
+            self.velocity.x = -1.0;
         }
         self.position += self.velocity;
-        None
+
+        if self.has_left_the_view(world.level) { Vec::new() } else { self.fire(world) }
+    }
+
+    // Once the view stops scrolling at the top of the map the enemies fly out of it
+    pub fn has_left_the_view(&self, level: &Level) -> bool {
+        self.is_engaged && !level.is_visible(self.position, (self.width, self.height))
+    }
+
+    // Runs one tick of the enemy's bullet pattern
+    fn fire(&mut self, world: &mut World) -> Vec<GameObject> {
+        let patterns = world.patterns;
+        let pattern = match self.pattern.as_ref().and_then(|pattern| patterns.get(pattern)) {
+            Some(pattern) => pattern,
+            None => return Vec::new(),
+        };
+
+        let center = self.position + Velocity::new(self.width as f32 / 2.0, self.height as f32 / 2.0);
+        let bullets = fire_pattern(pattern, self.pattern_ticks, center, world, self.id);
+
+        match pattern.next_tick(self.pattern_ticks) {
+            Some(tick) => self.pattern_ticks = tick,
+            None => self.pattern = None,
+        }
+        bullets
     }
 }
 
+// Orbs of one tick of `pattern` leaving from `center`, aimed shots go for the closest player.
+// Only patterns with jitter draw from the random stream.
+pub fn fire_pattern(pattern: &BulletPattern, tick: u32, center: Position, world: &mut World, shooter_id: Id) -> Vec<GameObject> {
+    let target = world
+        .players
+        .iter()
+        .cloned()
+        .min_by(|a, b| distance(*a, center).partial_cmp(&distance(*b, center)).unwrap());
+    let origin = center - Velocity::new(ORB_SIZE as f32 / 2.0, ORB_SIZE as f32 / 2.0);
+
+    let rng = world.rng.stream(RngSystem::Patterns);
+
+    pattern
+        .shots_at(tick)
+        .map(|shot| {
+            let angle = if pattern.jitter > 0.0 { shot.angle + rng.range(-pattern.jitter, pattern.jitter) } else { shot.angle };
+            let velocity = Shot { angle, ..*shot }.velocity(center, target);
+            BulletState::new(origin, velocity, BulletKind::Orb, ObjectType::Enemy, shooter_id)
+        })
+        .map(spawn_bullet)
+        .collect()
+}
+
+fn distance(a: Position, b: Position) -> f32 {
+    (a - b).magnitude()
+}
+
 impl BulletState {
-    pub fn _enemy_bullet(enemy: &EnemyState) -> BulletState {
+    pub fn new(position: Position, velocity: Velocity, kind: BulletKind, shooter_type: ObjectType, shooter_id: Id) -> BulletState {
         BulletState {
-            shooter_type: ObjectType::Enemy,
-            shooter_id: enemy.id,
-            position: enemy.position,
-            velocity: Velocity::new(0, 8),
+            position,
+            velocity,
+            kind,
+            shooter_type,
+            shooter_id,
             is_destroyed: false,
         }
     }
 
     pub fn draw(&mut self, renderer: &mut Renderer, level: &Level) {
-        renderer.draw_texture("bullet", self.position, level);
+        let texture_id = match self.kind {
+            BulletKind::Laser => "laser",
+            BulletKind::Orb => "orb",
+            _ => "bullet",
+        };
+        renderer.draw_texture(texture_id, self.position, level);
     }
 
-    pub fn update(&mut self) -> Option<GameObject> {
+    // Homing bullets turn towards the closest target still ahead of them
+    pub fn update(&mut self, targets: &[Position]) -> Vec<GameObject> {
+        if self.kind == BulletKind::Homing {
+            let position = self.position;
+            let steps_to = |target: &Position| (target.x - position.x).abs() + (position.y - target.y);
+            let target = targets
+                .iter()
+                .filter(|target| target.y < position.y)
+                .min_by(|a, b| steps_to(a).partial_cmp(&steps_to(b)).unwrap());
+
+            if let Some(target) = target {
+                // signum is never 0 for floats, a bullet already in line must not wobble
+                let dx = target.x - position.x;
+                let turn = if dx.abs() < HOMING_TURN_RATE { 0.0 } else { dx.signum() * HOMING_TURN_RATE };
+                self.velocity.x = (self.velocity.x + turn).clamp(-HOMING_MAX_SPEED, HOMING_MAX_SPEED);
+            }
+        }
+
         self.position += self.velocity;
-        None
+        Vec::new()
+    }
+
+    // Lasers keep going through everything they hit
+    pub fn is_piercing(&self) -> bool {
+        self.kind == BulletKind::Laser
+    }
+
+    // Bullets only hurt the other side, so enemies do not shoot each other down
+    pub fn can_hit(&self, target: &GameObject) -> bool {
+        self.shooter_id != target.id && self.shooter_type.is_hostile() != target.object_type.is_hostile()
+    }
+}
+
+impl PowerUpState {
+    pub fn new(position: Position, kind: PowerUpKind) -> PowerUpState {
+        PowerUpState {
+            position,
+            kind,
+            is_destroyed: false,
+            animation: AnimationState::new(kind.clip()),
+        }
     }
 
-    pub fn is_fired_by(&self, shooter: &GameObject) -> bool {
-        self.shooter_id == shooter.id
+    pub fn draw(&mut self, renderer: &mut Renderer, level: &Level) {
+        renderer.draw_frame(&self.animation, self.position, level);
+    }
+
+    pub fn update(&mut self) -> Vec<GameObject> {
+        self.animation.update();
+        Vec::new()
     }
 }
 
 impl CollisionState for BulletState {
     fn position(&self) -> Position { self.position }
-    fn size(&self) -> (u32, u32) { (32, 32) }
-    fn collision_padding(&self) -> (u32, u32) { (5, 25) }
+    fn size(&self) -> (u32, u32) {
+        match self.kind {
+            BulletKind::Laser => (6, 32),
+            BulletKind::Orb => (ORB_SIZE, ORB_SIZE),
+            _ => (32, 32),
+        }
+    }
+    fn collision_padding(&self) -> (u32, u32) {
+        match self.kind {
+            BulletKind::Laser => (0, 0),
+            BulletKind::Orb => (2, 2),
+            _ => (5, 25),
+        }
+    }
+}
+
+impl CollisionState for PowerUpState {
+    fn position(&self) -> Position { self.position }
+    fn size(&self) -> (u32, u32) { (24, 24) }
+    fn collision_padding(&self) -> (u32, u32) { (2, 2) }
 }
 
 impl CollisionState for PlayerState {
@@ -225,3 +470,84 @@ impl CollisionState for EnemyState {
     fn size(&self) -> (u32, u32) { (self.width, self.height) }
     fn collision_padding(&self) -> (u32, u32) { (5, 10) }
 }
+
+#[cfg(test)]
+mod tests {
+    use game::InputState;
+    use game::Level;
+    use game::PlayerState;
+    use game::Position;
+
+    #[test]
+    fn test_player_accelerates_up_to_max_speed() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let start = Position::new(200.0, level.position.y + 400.0);
+        let mut player = PlayerState::new(1, start, 64, 64);
+
+        //when
+        for _ in 0..30 {
+            player.input(&[InputState::Right(0)]);
+            player.update(&level);
+        }
+
+        //then
+        assert!((player.velocity.x - player.max_speed).abs() < 0.001);
+        assert_eq!(player.velocity.y, 0.0);
+        assert!(player.position.x > start.x);
+    }
+
+    #[test]
+    fn test_player_drag_stops_the_plane() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let mut player = PlayerState::new(1, Position::new(200.0, level.position.y + 400.0), 64, 64);
+        player.input(&[InputState::Left(0), InputState::Up(0)]);
+        player.update(&level);
+
+        //when
+        player.input(&[]);
+        for _ in 0..100 {
+            player.update(&level);
+        }
+
+        //then
+        assert_eq!(player.velocity.x, 0.0);
+        assert_eq!(player.velocity.y, 0.0);
+    }
+
+    #[test]
+    fn test_player_ignores_other_players_input() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let start = Position::new(200.0, level.position.y + 400.0);
+        let mut second = PlayerState::new(2, start, 64, 64);
+        second.index = 1;
+
+        //when
+        second.input(&[InputState::Right(0), InputState::Shoot(0), InputState::Up(1)]);
+        second.update(&level);
+
+        //then
+        assert_eq!(second.position.x, start.x);
+        assert!(second.velocity.y < 0.0);
+        assert!(!second.is_shooting);
+    }
+
+    #[test]
+    fn test_player_stays_on_screen() {
+        //given
+        let level = Level::new(20, 60, vec![]);
+        let mut player = PlayerState::new(1, Position::new(2.0, level.position.y + 400.0), 64, 64);
+
+        //when
+        for _ in 0..10 {
+            player.input(&[InputState::Left(0)]);
+            player.update(&level);
+        }
+
+        //then
+        assert_eq!(player.position.x, 0.0);
+        assert_eq!(player.velocity.x, 0.0);
+    }
+}
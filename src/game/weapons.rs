@@ -0,0 +1,161 @@
+use game::BulletKind;
+use game::BulletState;
+use game::Id;
+use game::ObjectType;
+use game::Position;
+use game::PowerUpKind;
+use game::Velocity;
+use game::WeaponTier;
+
+const BULLET_SPEED: f32 = 4.0;
+const LASER_SPEED: f32 = 12.0;
+
+impl WeaponTier {
+    pub fn upgrade(self) -> WeaponTier {
+        match self {
+            WeaponTier::Single => WeaponTier::Double,
+            WeaponTier::Double => WeaponTier::Spread,
+            WeaponTier::Spread => WeaponTier::Homing,
+            WeaponTier::Homing | WeaponTier::Laser => WeaponTier::Laser,
+        }
+    }
+
+    // Ticks between two shots
+    pub fn cooldown(self) -> u32 {
+        match self {
+            WeaponTier::Single | WeaponTier::Double => 12,
+            WeaponTier::Spread => 15,
+            WeaponTier::Homing => 18,
+            WeaponTier::Laser => 6,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WeaponTier::Single => "SINGLE",
+            WeaponTier::Double => "DOUBLE",
+            WeaponTier::Spread => "SPREAD",
+            WeaponTier::Homing => "HOMING",
+            WeaponTier::Laser => "LASER",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<WeaponTier, String> {
+        [WeaponTier::Single, WeaponTier::Double, WeaponTier::Spread, WeaponTier::Homing, WeaponTier::Laser]
+            .iter()
+            .cloned()
+            .find(|tier| tier.name() == name)
+            .ok_or_else(|| format!("Unknown weapon: {:?}", name))
+    }
+
+    // Bullets fired by the player at `position`, leaving from the nose of the plane
+    pub fn fire(self, position: Position, shooter_id: Id) -> Vec<BulletState> {
+        let bullet = |offset_x: f32, velocity_x: f32, kind: BulletKind| {
+            let speed = if kind == BulletKind::Laser { LASER_SPEED } else { BULLET_SPEED };
+            BulletState::new(
+                Position::new(position.x + offset_x, position.y - 35.0),
+                Velocity::new(velocity_x, -speed),
+                kind,
+                ObjectType::Player,
+                shooter_id,
+            )
+        };
+
+        match self {
+            WeaponTier::Single => vec![bullet(0.0, 0.0, BulletKind::Normal)],
+            WeaponTier::Double => vec![bullet(-12.0, 0.0, BulletKind::Normal), bullet(12.0, 0.0, BulletKind::Normal)],
+            WeaponTier::Spread => vec![
+                bullet(-12.0, -1.0, BulletKind::Normal),
+                bullet(0.0, 0.0, BulletKind::Normal),
+                bullet(12.0, 1.0, BulletKind::Normal),
+            ],
+            WeaponTier::Homing => vec![
+                bullet(-12.0, 0.0, BulletKind::Homing),
+                bullet(0.0, 0.0, BulletKind::Normal),
+                bullet(12.0, 0.0, BulletKind::Homing),
+            ],
+            WeaponTier::Laser => vec![bullet(29.0, 0.0, BulletKind::Laser)],
+        }
+    }
+}
+
+impl PowerUpKind {
+    pub fn parse(name: &str) -> Result<PowerUpKind, String> {
+        match name {
+            "Weapon" => Ok(PowerUpKind::Weapon),
+            "Bomb" => Ok(PowerUpKind::Bomb),
+            "Life" => Ok(PowerUpKind::Life),
+            _ => Err(format!("Unknown power-up: {:?}", name)),
+        }
+    }
+
+    pub fn clip(self) -> &'static str {
+        match self {
+            PowerUpKind::Weapon => "powerUpWeapon",
+            PowerUpKind::Bomb => "powerUpBomb",
+            PowerUpKind::Life => "powerUpLife",
+        }
+    }
+}
+
+impl BulletKind {
+    pub fn parse(name: &str) -> Result<BulletKind, String> {
+        match name {
+            "Normal" => Ok(BulletKind::Normal),
+            "Homing" => Ok(BulletKind::Homing),
+            "Laser" => Ok(BulletKind::Laser),
+            "Orb" => Ok(BulletKind::Orb),
+            _ => Err(format!("Unknown bullet: {:?}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::Position;
+    use game::PowerUpKind;
+    use game::WeaponTier;
+
+    #[test]
+    fn test_upgrading() {
+        //given
+        let mut weapon = WeaponTier::Single;
+
+        //when
+        for _ in 0..10 {
+            weapon = weapon.upgrade();
+        }
+
+        //then
+        assert_eq!(weapon, WeaponTier::Laser);
+        assert_eq!(WeaponTier::Single.upgrade(), WeaponTier::Double);
+    }
+
+    #[test]
+    fn test_bullet_patterns() {
+        //given
+        let position = Position::new(100.0, 100.0);
+
+        //when
+        let single = WeaponTier::Single.fire(position, 1);
+        let spread = WeaponTier::Spread.fire(position, 1);
+        let laser = WeaponTier::Laser.fire(position, 1);
+
+        //then
+        assert_eq!(single.len(), 1);
+        assert_eq!(spread.len(), 3);
+        assert_eq!(laser.len(), 1);
+        assert!(WeaponTier::Laser.cooldown() < WeaponTier::Single.cooldown());
+    }
+
+    #[test]
+    fn test_parsing_power_ups() {
+        //when
+        let kind = PowerUpKind::parse("Life");
+        let unknown = PowerUpKind::parse("Shield");
+
+        //then
+        assert_eq!(kind, Ok(PowerUpKind::Life));
+        assert!(unknown.is_err());
+    }
+}
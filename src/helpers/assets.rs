@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AssetKind {
+    Texture,
+    Sound,
+    Music,
+    Font,
+    Map,
+    Patterns,
+    Campaign,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Asset {
+    pub kind: AssetKind,
+    pub filename: String,
+    pub size: u16,
+}
+
+// Every asset declared in game.xml, looked up by its string ID
+#[derive(Debug, Default)]
+pub struct AssetRegistry {
+    assets: HashMap<String, Asset>,
+}
+
+impl Asset {
+    pub fn new(kind: AssetKind, filename: String, size: u16) -> Asset {
+        Asset { kind, filename, size }
+    }
+}
+
+impl AssetRegistry {
+    pub fn new() -> AssetRegistry {
+        AssetRegistry::default()
+    }
+
+    pub fn insert(&mut self, id: String, asset: Asset) {
+        if let Some(previous) = self.assets.insert(id.clone(), asset) {
+            println!("Asset {:?} declared twice, replacing {:?}", id, previous.filename);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Asset> {
+        self.assets.get(id)
+    }
+
+    pub fn of_kind(&self, kind: AssetKind) -> Vec<(&str, &Asset)> {
+        let mut assets: Vec<(&str, &Asset)> = self
+            .assets
+            .iter()
+            .filter(|(_, asset)| asset.kind == kind)
+            .map(|(id, asset)| (id.as_str(), asset))
+            .collect();
+        assets.sort_by_key(|(id, _)| *id);
+        assets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use helpers::assets::Asset;
+    use helpers::assets::AssetKind;
+    use helpers::assets::AssetRegistry;
+
+    #[test]
+    fn test_lookups() {
+        //given
+        let mut registry = AssetRegistry::new();
+        registry.insert(String::from("map2"), Asset::new(AssetKind::Map, String::from("assets/map2.tmx"), 0));
+        registry.insert(String::from("map1"), Asset::new(AssetKind::Map, String::from("assets/map1.tmx"), 0));
+        registry.insert(String::from("hud"), Asset::new(AssetKind::Font, String::from("assets/font.ttf"), 16));
+
+        //when
+        let maps = registry.of_kind(AssetKind::Map);
+        let fonts = registry.of_kind(AssetKind::Font);
+
+        //then
+        assert_eq!(maps.iter().map(|(id, _)| *id).collect::<Vec<&str>>(), vec!["map1", "map2"]);
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(fonts[0].1.size, 16);
+    }
+}
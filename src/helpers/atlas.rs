@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AtlasRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AtlasDefinition {
+    pub max_width: u32,
+    pub padding: u32,
+    pub textures: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AtlasLayout {
+    pub width: u32,
+    pub height: u32,
+    pub regions: HashMap<String, AtlasRegion>,
+}
+
+impl AtlasDefinition {
+    pub fn new(max_width: u32, padding: u32) -> AtlasDefinition {
+        AtlasDefinition {
+            max_width,
+            padding,
+            textures: Vec::new(),
+        }
+    }
+}
+
+// Shelf packing: tallest images first, rows filled left to right until max_width is reached
+pub fn pack(sizes: &[(String, u32, u32)], max_width: u32, padding: u32) -> Result<AtlasLayout, String> {
+    let mut sorted: Vec<&(String, u32, u32)> = sizes.iter().collect();
+    sorted.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+
+    let mut regions = HashMap::new();
+    let mut width = 0;
+    let mut shelf_x = padding;
+    let mut shelf_y = padding;
+    let mut shelf_height = 0;
+
+    for (key, image_width, image_height) in sorted {
+        if image_width + 2 * padding > max_width {
+            return Err(format!("Texture {:?} is wider than the atlas ({} > {})", key, image_width, max_width));
+        }
+
+        if shelf_x + image_width + padding > max_width {
+            shelf_y += shelf_height + padding;
+            shelf_x = padding;
+            shelf_height = 0;
+        }
+
+        regions.insert(
+            key.clone(),
+            AtlasRegion {
+                x: shelf_x as i32,
+                y: shelf_y as i32,
+                width: *image_width,
+                height: *image_height,
+            },
+        );
+
+        shelf_x += image_width + padding;
+        shelf_height = shelf_height.max(*image_height);
+        width = width.max(shelf_x);
+    }
+
+    let height = shelf_y + shelf_height + padding;
+    Ok(AtlasLayout { width, height, regions })
+}
+
+#[cfg(test)]
+mod tests {
+    use helpers::atlas;
+
+    #[test]
+    fn test_packing_single_row() {
+        //given
+        let sizes = vec![(String::from("a"), 10, 10), (String::from("b"), 20, 5)];
+
+        //when
+        let layout = atlas::pack(&sizes, 100, 1).unwrap();
+
+        //then
+        assert_eq!(layout.width, 33);
+        assert_eq!(layout.height, 12);
+        assert_eq!((layout.regions["a"].x, layout.regions["a"].y), (1, 1));
+        assert_eq!((layout.regions["b"].x, layout.regions["b"].y), (12, 1));
+    }
+
+    #[test]
+    fn test_packing_wraps_rows() {
+        //given
+        let sizes = vec![
+            (String::from("plane"), 199, 67),
+            (String::from("whitePlane"), 65, 65),
+            (String::from("bullet"), 32, 32),
+        ];
+
+        //when
+        let layout = atlas::pack(&sizes, 256, 1).unwrap();
+
+        //then
+        assert_eq!((layout.regions["plane"].x, layout.regions["plane"].y), (1, 1));
+        assert_eq!((layout.regions["whitePlane"].x, layout.regions["whitePlane"].y), (1, 69));
+        assert_eq!((layout.regions["bullet"].x, layout.regions["bullet"].y), (67, 69));
+        assert_eq!(layout.height, 135);
+    }
+
+    #[test]
+    fn test_packing_too_wide() {
+        //given
+        let sizes = vec![(String::from("huge"), 300, 10)];
+
+        //when
+        let layout = atlas::pack(&sizes, 256, 1);
+
+        //then
+        assert!(layout.is_err());
+    }
+}
@@ -1 +1,4 @@
+pub mod assets;
+pub mod atlas;
 pub mod parsers;
+pub mod watcher;
@@ -0,0 +1,48 @@
+use game::LevelGoal;
+use game::Stage;
+use helpers::parsers::find_attribute;
+use helpers::parsers::parser;
+use helpers::parsers::xml::reader::XmlEvent;
+
+// Stages in the order they are played, each one names a map declared in game.xml
+pub fn parse(filename: &str) -> Vec<Stage> {
+    let mut stages = Vec::new();
+
+    for e in parser(filename) {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) if name.local_name.eq_ignore_ascii_case("stage") => {
+                let map: String = find_attribute(&attributes, "map").expect("Missing stage map");
+                let title: String = find_attribute(&attributes, "name").unwrap_or_else(|| map.clone());
+                let goal: String = find_attribute(&attributes, "goal").unwrap_or_else(|| String::from("top"));
+                let goal = LevelGoal::parse(&goal).unwrap_or_else(|e| panic!("{}", e));
+
+                stages.push(Stage::new(&map, &title, goal));
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use game::LevelGoal;
+    use game::Stage;
+    use helpers::parsers;
+
+    #[test]
+    fn test_parsing() {
+        //when
+        let stages = parsers::campaign_file::parse("assets/campaign.xml");
+
+        //then
+        assert_eq!(stages, vec![
+            Stage::new("map1", "COASTLINE", LevelGoal::DefeatBoss),
+            Stage::new("map2", "OPEN SEA", LevelGoal::ReachTop),
+        ]);
+    }
+}
@@ -1,5 +1,13 @@
+use game::Animation;
+use game::AnimationFrame;
+use game::PlaybackMode;
+use helpers::assets::Asset;
+use helpers::assets::AssetKind;
+use helpers::assets::AssetRegistry;
+use helpers::atlas::AtlasDefinition;
 use helpers::parsers::find_attribute;
 use helpers::parsers::parser;
+use helpers::parsers::sprite_sheet_file;
 use helpers::parsers::xml::reader::XmlEvent;
 use sdl::TextureWrapper;
 use std::collections::HashMap;
@@ -8,11 +16,21 @@ use std::collections::HashMap;
 enum XmlReadingState {
     Root,
     InPlayTextures,
+    InPlayTexturesAtlas,
+    InPlayAnimations,
+    InPlayAnimation,
     InPlay,
+    InAssets,
 }
 
-pub fn parse(filename: &str, textures: &mut Vec<(String, String)>, texture_wrappers: &mut HashMap<String, TextureWrapper>) {
+pub fn parse(filename: &str,
+             registry: &mut AssetRegistry,
+             texture_wrappers: &mut HashMap<String, TextureWrapper>,
+             animations: &mut HashMap<String, Animation>,
+             atlases: &mut HashMap<String, AtlasDefinition>) {
     let mut state = XmlReadingState::Root;
+    let mut animation: Option<Animation> = None;
+    let mut atlas: Option<String> = None;
 
     let parser = parser(filename);
 
@@ -23,7 +41,16 @@ pub fn parse(filename: &str, textures: &mut Vec<(String, String)>, texture_wrapp
                 state = match (state, local_name.as_str()) {
                     (XmlReadingState::Root, "play") => XmlReadingState::InPlay,
                     (XmlReadingState::InPlay, "textures") => XmlReadingState::InPlayTextures,
-                    (XmlReadingState::InPlayTextures, "texture") => {
+                    (XmlReadingState::InPlayTextures, "atlas") => {
+                        let key: String = find_attribute(&attributes, "id").expect("Missing atlas id");
+                        let max_width = find_attribute(&attributes, "width").unwrap_or(1024);
+                        let padding = find_attribute(&attributes, "padding").unwrap_or(1);
+
+                        atlases.insert(key.clone(), AtlasDefinition::new(max_width, padding));
+                        atlas = Some(key);
+                        XmlReadingState::InPlayTexturesAtlas
+                    }
+                    (XmlReadingState::InPlayTextures, "texture") | (XmlReadingState::InPlayTexturesAtlas, "texture") => {
                         let key: String = find_attribute(&attributes, "id").unwrap();
                         let filename = find_attribute(&attributes, "filename").unwrap();
 
@@ -33,17 +60,77 @@ pub fn parse(filename: &str, textures: &mut Vec<(String, String)>, texture_wrapp
                         if let (Some(width), Some(height)) = (width, height) {
                             let padding = find_attribute(&attributes, "padding").unwrap_or(0);
                             let frames = find_attribute(&attributes, "frames").unwrap_or(0);
+                            let columns = find_attribute(&attributes, "columns").unwrap_or(frames);
                             let width: u32 = width.parse().unwrap();
                             let height: u32 = height.parse().unwrap();
 
                             texture_wrappers.insert(
                                 key.clone(),
-                                TextureWrapper::new(key.clone(), width, height, padding, frames),
+                                TextureWrapper::new(key.clone(), width, height, padding, frames, columns),
                             );
                         }
 
-                        textures.push((key.clone(), filename));
-                        XmlReadingState::InPlayTextures
+                        add_to_atlas(atlases, &atlas, &key);
+                        registry.insert(key, Asset::new(AssetKind::Texture, filename, 0));
+                        state
+                    }
+                    (XmlReadingState::InPlayTextures, "sheet") | (XmlReadingState::InPlayTexturesAtlas, "sheet") => {
+                        let key: String = find_attribute(&attributes, "id").unwrap();
+                        let filename: String = find_attribute(&attributes, "filename").unwrap();
+
+                        let (image, frames, sheet_animations) = sprite_sheet_file::parse(&filename, &key);
+                        texture_wrappers.insert(key.clone(), TextureWrapper::from_frames(key.clone(), &frames));
+                        animations.extend(sheet_animations.into_iter().map(|animation| (animation.id.clone(), animation)));
+
+                        // the sheet's image is what gets loaded, not the JSON next to it
+                        add_to_atlas(atlases, &atlas, &key);
+                        registry.insert(key, Asset::new(AssetKind::Texture, image, 0));
+                        state
+                    }
+                    (XmlReadingState::InPlay, "animations") => XmlReadingState::InPlayAnimations,
+                    (XmlReadingState::InPlayAnimations, "animation") => {
+                        let id: String = find_attribute(&attributes, "id").expect("Missing animation id");
+                        let texture_id = find_attribute(&attributes, "textureid").expect("Missing animation textureID");
+                        let mode: String = find_attribute(&attributes, "mode").unwrap_or_else(|| String::from("loop"));
+                        let mode = match mode.to_ascii_lowercase().as_str() {
+                            "loop" => PlaybackMode::Loop,
+                            "once" => PlaybackMode::Once,
+                            "pingpong" => PlaybackMode::PingPong,
+                            _ => panic!("Unknown playback mode: {:?}", mode),
+                        };
+
+                        animation = Some(Animation::new(id, texture_id, mode, Vec::new()));
+                        XmlReadingState::InPlayAnimation
+                    }
+                    (XmlReadingState::InPlayAnimation, "frame") => {
+                        let x = find_attribute(&attributes, "x").expect("Missing frame x");
+                        let y = find_attribute(&attributes, "y").expect("Missing frame y");
+                        let width = find_attribute(&attributes, "width").expect("Missing frame width");
+                        let height = find_attribute(&attributes, "height").expect("Missing frame height");
+                        let duration = find_attribute(&attributes, "duration").unwrap_or(100);
+
+                        if let Some(ref mut animation) = animation {
+                            animation.frames.push(AnimationFrame::new(x, y, width, height, duration));
+                        }
+                        XmlReadingState::InPlayAnimation
+                    }
+                    (XmlReadingState::InPlayAnimation, "frames") => {
+                        let width: u32 = find_attribute(&attributes, "width").expect("Missing frames width");
+                        let height: u32 = find_attribute(&attributes, "height").expect("Missing frames height");
+                        let count: u32 = find_attribute(&attributes, "count").expect("Missing frames count");
+                        let padding: u32 = find_attribute(&attributes, "padding").unwrap_or(0);
+                        let columns: u32 = find_attribute(&attributes, "columns").unwrap_or(count).max(1);
+                        let start: u32 = find_attribute(&attributes, "start").unwrap_or(0);
+                        let duration = find_attribute(&attributes, "duration").unwrap_or(100);
+
+                        if let Some(ref mut animation) = animation {
+                            animation.frames.extend((start..start + count).map(|frame| {
+                                let x = (frame % columns) * (width + padding) + padding;
+                                let y = (frame / columns) * (height + padding) + padding;
+                                AnimationFrame::new(x as i32, y as i32, width, height, duration)
+                            }));
+                        }
+                        XmlReadingState::InPlayAnimation
                     }
                     _ => state,
                 }
@@ -53,7 +140,64 @@ pub fn parse(filename: &str, textures: &mut Vec<(String, String)>, texture_wrapp
                 state = match (state, local_name.as_str()) {
                     (XmlReadingState::InPlay, "play") => XmlReadingState::Root,
                     (XmlReadingState::InPlayTextures, "textures") => XmlReadingState::InPlay,
-                    (XmlReadingState::InPlayTextures, "texture") => XmlReadingState::InPlayTextures,
+                    (XmlReadingState::InPlayTexturesAtlas, "atlas") => {
+                        atlas = None;
+                        XmlReadingState::InPlayTextures
+                    }
+                    (XmlReadingState::InPlayAnimations, "animations") => XmlReadingState::InPlay,
+                    (XmlReadingState::InPlayAnimation, "animation") => {
+                        if let Some(animation) = animation.take() {
+                            animations.insert(animation.id.clone(), animation);
+                        }
+                        XmlReadingState::InPlayAnimations
+                    }
+                    _ => state,
+                }
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Collects every asset ID declared in the <ASSETS> of game.xml, the PLAY textures are registered by `parse`
+pub fn parse_assets(filename: &str, registry: &mut AssetRegistry) {
+    let mut state = XmlReadingState::Root;
+
+    for e in parser(filename) {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                let local_name = name.local_name.to_ascii_lowercase();
+                let kind = match local_name.as_str() {
+                    "sound" => Some(AssetKind::Sound),
+                    "music" => Some(AssetKind::Music),
+                    "font" => Some(AssetKind::Font),
+                    "map" => Some(AssetKind::Map),
+                    "patterns" => Some(AssetKind::Patterns),
+                    "campaign" => Some(AssetKind::Campaign),
+                    _ => None,
+                };
+
+                state = match (state, local_name.as_str(), kind) {
+                    (XmlReadingState::Root, "assets", _) => XmlReadingState::InAssets,
+                    (XmlReadingState::InAssets, _, Some(kind)) => {
+                        let id: String = find_attribute(&attributes, "id").expect("Missing asset id");
+                        let filename = find_attribute(&attributes, "filename").expect("Missing asset filename");
+                        let size = find_attribute(&attributes, "size").unwrap_or(0);
+
+                        registry.insert(id, Asset::new(kind, filename, size));
+                        state
+                    }
+                    _ => state,
+                }
+            }
+            Ok(XmlEvent::EndElement { name }) => {
+                let local_name = name.local_name.to_ascii_lowercase();
+                state = match (state, local_name.as_str()) {
+                    (XmlReadingState::InAssets, "assets") => XmlReadingState::Root,
                     _ => state,
                 }
             }
@@ -66,24 +210,96 @@ pub fn parse(filename: &str, textures: &mut Vec<(String, String)>, texture_wrapp
     }
 }
 
+fn add_to_atlas(atlases: &mut HashMap<String, AtlasDefinition>, atlas: &Option<String>, key: &str) {
+    if let Some(atlas) = atlas {
+        atlases.get_mut(atlas).expect("Missing atlas").textures.push(key.to_string());
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use game::AnimationFrame;
+    use game::PlaybackMode;
+    use helpers::assets::AssetKind;
+    use helpers::assets::AssetRegistry;
     use helpers::parsers;
     use std::collections::HashMap;
 
     #[test]
     fn test_parsing() {
         //given
-        let mut textures = Vec::new();
+        let mut registry = AssetRegistry::new();
         let mut texture_wrappers = HashMap::new();
+        let mut animations = HashMap::new();
+        let mut atlases = HashMap::new();
+
+        //when
+        parsers::game_file::parse("assets/game.xml", &mut registry, &mut texture_wrappers, &mut animations, &mut atlases);
+
+        //then
+        let textures = registry.of_kind(AssetKind::Texture);
+        let ids: Vec<&str> = textures.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec!["boss", "bullet", "font", "laser", "orb", "plane", "powerUps", "whitePlane"]);
+        let filename = |id| registry.get(id).map(|asset| asset.filename.as_str());
+        assert_eq!(filename("plane"), Some("assets/plane.png"));
+        assert_eq!(filename("whitePlane"), Some("assets/whitePlane.png"));
+        assert_eq!(filename("bullet"), Some("assets/bullet.png"));
+        assert_eq!(filename("font"), Some("assets/font.png"));
+        assert_eq!(filename("powerUps"), Some("assets/powerUps.png"));
+    }
+
+    #[test]
+    fn test_parsing_animations() {
+        //given
+        let mut registry = AssetRegistry::new();
+        let mut texture_wrappers = HashMap::new();
+        let mut animations = HashMap::new();
+        let mut atlases = HashMap::new();
+
+        //when
+        parsers::game_file::parse("assets/game.xml", &mut registry, &mut texture_wrappers, &mut animations, &mut atlases);
+
+        //then
+        let plane = animations.get("plane").expect("Missing plane animation");
+        assert_eq!(plane.texture_id, "plane");
+        assert_eq!(plane.mode, PlaybackMode::PingPong);
+        assert_eq!(plane.frames.len(), 3);
+        assert_eq!(plane.frames[0], AnimationFrame::new(1, 1, 65, 65, 100));
+        assert_eq!(plane.frames[2], AnimationFrame::new(133, 1, 65, 65, 100));
+    }
+
+    #[test]
+    fn test_parsing_atlases() {
+        //given
+        let mut registry = AssetRegistry::new();
+        let mut texture_wrappers = HashMap::new();
+        let mut animations = HashMap::new();
+        let mut atlases = HashMap::new();
+
+        //when
+        parsers::game_file::parse("assets/game.xml", &mut registry, &mut texture_wrappers, &mut animations, &mut atlases);
+
+        //then
+        let sprites = atlases.get("sprites").expect("Missing sprites atlas");
+        assert_eq!(sprites.max_width, 512);
+        assert_eq!(sprites.textures, vec!["plane", "whitePlane", "laser", "orb", "powerUps"]);
+    }
+
+    #[test]
+    fn test_parsing_assets() {
+        //given
+        let mut registry = AssetRegistry::new();
 
         //when
-        parsers::game_file::parse("assets/game.xml", &mut textures, &mut texture_wrappers);
+        parsers::game_file::parse_assets("assets/game.xml", &mut registry);
 
         //then
-        assert_eq!(textures.len(), 3);
-        assert!(textures.contains(&(String::from("plane"), String::from("assets/plane.png"))));
-        assert!(textures.contains(&(String::from("whitePlane"), String::from("assets/whitePlane.png"))));
-        assert!(textures.contains(&(String::from("bullet"), String::from("assets/bullet.png"))));
+        let maps = registry.of_kind(AssetKind::Map);
+        assert_eq!(maps.len(), 2);
+        assert_eq!(maps[0].0, "map1");
+        assert_eq!(maps[0].1.filename, "assets/map1.tmx");
+        assert_eq!(registry.of_kind(AssetKind::Campaign).len(), 1);
+        assert_eq!(registry.of_kind(AssetKind::Font).len(), 1);
+        assert!(registry.of_kind(AssetKind::Texture).is_empty());
     }
 }
@@ -0,0 +1,78 @@
+use helpers::parsers::find_attribute;
+use helpers::parsers::parser;
+use helpers::parsers::xml::reader::XmlEvent;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// A missing file is an empty table, it gets created with the first entry
+pub fn parse(filename: &str) -> Vec<(String, u32)> {
+    let mut entries = Vec::new();
+    if !Path::new(filename).is_file() {
+        return entries;
+    }
+
+    for e in parser(filename) {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) if name.local_name.eq_ignore_ascii_case("score") => {
+                let name = find_attribute(&attributes, "name").unwrap_or_default();
+                let value = find_attribute(&attributes, "value").unwrap_or(0);
+                entries.push((name, value));
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+pub fn write(filename: &str, entries: &[(String, u32)]) -> io::Result<()> {
+    let mut content = String::from("<?xml version=\"1.0\" ?>\n<HIGHSCORES>\n");
+    for (name, value) in entries {
+        content.push_str(&format!("    <score name=\"{}\" value=\"{}\"/>\n", escape(name), value));
+    }
+    content.push_str("</HIGHSCORES>\n");
+
+    fs::write(filename, content)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use helpers::parsers;
+    use std::env;
+
+    #[test]
+    fn test_writing_and_parsing() {
+        //given
+        let filename = env::temp_dir().join("rusty-pew-pew-highscores.xml");
+        let filename = filename.to_str().unwrap();
+        let entries = vec![(String::from("ACE"), 12000), (String::from("<&>"), 50)];
+
+        //when
+        parsers::high_score_file::write(filename, &entries).unwrap();
+        let parsed = parsers::high_score_file::parse(filename);
+
+        //then
+        assert_eq!(parsed, entries);
+    }
+
+    #[test]
+    fn test_parsing_missing_file() {
+        //when
+        let parsed = parsers::high_score_file::parse("assets/missing-highscores.xml");
+
+        //then
+        assert!(parsed.is_empty());
+    }
+}
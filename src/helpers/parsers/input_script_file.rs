@@ -0,0 +1,77 @@
+use game::InputScript;
+use game::InputState;
+use game::ScriptedPress;
+use helpers::parsers::find_attribute;
+use helpers::parsers::parser;
+use helpers::parsers::xml::reader::XmlEvent;
+use std::path::Path;
+
+// <script map seed players frames> with a <press key player from to/> for every held key,
+// players are numbered from 1 like they are on the map
+pub fn parse(filename: &str) -> Result<InputScript, String> {
+    if !Path::new(filename).is_file() {
+        return Err(format!("Missing input script: {:?}", filename));
+    }
+
+    let mut script = None;
+    let mut presses = Vec::new();
+    for e in parser(filename) {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => match name.local_name.to_ascii_lowercase().as_str() {
+                "script" => {
+                    let map: String = find_attribute(&attributes, "map").ok_or("Input script without a map")?;
+                    let seed = find_attribute(&attributes, "seed").ok_or("Input script without a seed")?;
+                    let frames = find_attribute(&attributes, "frames").ok_or("Input script without a frame count")?;
+                    let players = find_attribute(&attributes, "players").unwrap_or(1);
+                    script = Some((map, seed, players, frames));
+                }
+                "press" => {
+                    let key: String = find_attribute(&attributes, "key").ok_or("Press without a key")?;
+                    let player: usize = find_attribute(&attributes, "player").unwrap_or(1);
+                    let from = find_attribute(&attributes, "from").ok_or("Press without a first frame")?;
+                    let to = find_attribute(&attributes, "to").unwrap_or(from);
+                    let index = player.checked_sub(1).ok_or("Players are numbered from 1")?;
+                    presses.push(ScriptedPress { from, to, input: input(&key, index)? });
+                }
+                _ => {}
+            },
+            Err(e) => return Err(format!("Error reading input script {:?}: {}", filename, e)),
+            _ => {}
+        }
+    }
+
+    let (map, seed, players, frames) = script.ok_or_else(|| format!("No <script> in {:?}", filename))?;
+    Ok(InputScript { map, seed, players, frames, presses })
+}
+
+fn input(key: &str, player: usize) -> Result<InputState, String> {
+    match key.to_ascii_lowercase().as_str() {
+        "up" => Ok(InputState::Up(player)),
+        "down" => Ok(InputState::Down(player)),
+        "left" => Ok(InputState::Left(player)),
+        "right" => Ok(InputState::Right(player)),
+        "shoot" => Ok(InputState::Shoot(player)),
+        "confirm" => Ok(InputState::Confirm),
+        _ => Err(format!("Unknown key in input script: {:?}", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::InputState;
+    use game::ScriptedPress;
+    use helpers::parsers;
+
+    #[test]
+    fn test_parsing() {
+        //when
+        let script = parsers::input_script_file::parse("assets/lockstep.xml").unwrap();
+
+        //then
+        assert_eq!(script.map, "assets/map1.tmx");
+        assert_eq!(script.players, 2);
+        assert_eq!(script.frames, 1800);
+        assert_eq!(script.presses[0], ScriptedPress { from: 0, to: 1799, input: InputState::Shoot(0) });
+        assert!(script.presses.contains(&ScriptedPress { from: 60, to: 180, input: InputState::Right(1) }));
+    }
+}
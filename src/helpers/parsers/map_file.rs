@@ -1,13 +1,19 @@
 use base64;
 use game::GameObject;
+use game::ImageLayer;
 use game::Level;
+use game::LevelLayer;
+use game::Position;
+use game::TileLayer;
 use game::states;
 use helpers::parsers::find_attribute;
 use helpers::parsers::inflate;
 use helpers::parsers::parser;
+use helpers::parsers::xml::attribute::OwnedAttribute;
 use helpers::parsers::xml::reader::XmlEvent;
 use sdl::TextureWrapper;
 use std::collections::HashMap;
+use std::path::Path;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum XmlReadingState {
@@ -18,20 +24,25 @@ enum XmlReadingState {
     InMapLayerData,
     InMapObjectgroup,
     InMapObjectgroupObject,
+    InMapImagelayer,
 }
 
 
-pub fn parse(filename: &str) -> (Vec<Option<GameObject>>, Level, HashMap<String, TextureWrapper>, (u8, u8, u8), String) {
+pub type MapData = (Vec<Option<GameObject>>, Level, HashMap<String, TextureWrapper>, (u8, u8, u8), String);
+
+pub fn parse(filename: &str) -> MapData {
     let mut state = XmlReadingState::Root;
     let mut properties: HashMap<String, String> = HashMap::new();
 
     let mut game_objects = Vec::new();
     let mut texture_wrappers = HashMap::new();
-    let mut tiles = Vec::new();
     let mut width = 0;
     let mut height = 0;
     let mut color = (0, 0, 0);
     let mut tiles_filename: String = String::new();
+    let mut checkpoints = Vec::new();
+    let mut layers = Vec::new();
+    let mut image_layer = None;
     for e in parser(filename) {
         match e {
             Ok(XmlEvent::StartElement { name, attributes, .. }) => {
@@ -50,7 +61,39 @@ pub fn parse(filename: &str) -> (Vec<Option<GameObject>>, Level, HashMap<String,
 
                         XmlReadingState::InMap
                     }
-                    (XmlReadingState::InMap, "layer") => XmlReadingState::InMapLayer,
+                    (XmlReadingState::InMap, "layer") => {
+                        // each layer keeps its own tiles, they are filled in from its data
+                        layers.push(LevelLayer::Tiles(TileLayer {
+                            offset: offset(&attributes),
+                            parallax: parallax(&attributes),
+                            ..TileLayer::new(width, height, Vec::new())
+                        }));
+                        XmlReadingState::InMapLayer
+                    }
+                    (XmlReadingState::InMap, "imagelayer") => {
+                        image_layer = Some(ImageLayer {
+                            texture_id: String::new(),
+                            source: String::new(),
+                            width: 0,
+                            height: 0,
+                            offset: offset(&attributes),
+                            parallax: parallax(&attributes),
+                            repeat: (is_set(&attributes, "repeatx"), is_set(&attributes, "repeaty")),
+                        });
+                        XmlReadingState::InMapImagelayer
+                    }
+                    (XmlReadingState::InMapImagelayer, "image") => {
+                        if let Some(ref mut image_layer) = image_layer {
+                            let source: String = find_attribute(&attributes, "source").expect("Missing image source");
+                            let directory = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+                            image_layer.source = directory.join(&source).to_string_lossy().into_owned();
+                            image_layer.width = find_attribute(&attributes, "width").expect("Missing image width");
+                            image_layer.height = find_attribute(&attributes, "height").expect("Missing image height");
+                            // known by the path it is loaded from, the same file used twice is loaded once
+                            image_layer.texture_id = image_layer.source.clone();
+                        }
+                        XmlReadingState::InMapImagelayer
+                    }
                     (XmlReadingState::InMap, "tileset") => {
                         tiles_filename = find_attribute(&attributes, "source").expect("Missing tiles.tsx");
                         XmlReadingState::InMapTileset
@@ -91,7 +134,9 @@ pub fn parse(filename: &str) -> (Vec<Option<GameObject>>, Level, HashMap<String,
                         .map(|(_, value)| value)
                         .into_iter();
 
-                    tiles.extend(every_fourth);
+                    if let Some(LevelLayer::Tiles(tile_layer)) = layers.last_mut() {
+                        tile_layer.tiles.extend(every_fourth);
+                    }
                 }
             }
             Ok(XmlEvent::EndElement { name, .. }) => {
@@ -99,8 +144,32 @@ pub fn parse(filename: &str) -> (Vec<Option<GameObject>>, Level, HashMap<String,
                 state = match (state, local_name.as_str()) {
                     (XmlReadingState::InMap, "map") => XmlReadingState::Root,
                     (XmlReadingState::InMapLayer, "layer") => XmlReadingState::InMap,
+                    (XmlReadingState::InMapImagelayer, "image") => XmlReadingState::InMapImagelayer,
+                    (XmlReadingState::InMapImagelayer, "imagelayer") => {
+                        match image_layer.take() {
+                            Some(ref image_layer) if image_layer.source.is_empty() => {
+                                println!("Skipping an image layer without an image")
+                            }
+                            Some(image_layer) => {
+                                let texture_id = image_layer.texture_id.clone();
+                                let (width, height) = (image_layer.width, image_layer.height);
+                                let texture_wrapper = TextureWrapper::new(texture_id.clone(), width, height, 0, 1, 1);
+                                texture_wrappers.insert(texture_id, texture_wrapper);
+                                layers.push(LevelLayer::Image(image_layer));
+                            }
+                            None => {}
+                        }
+                        XmlReadingState::InMap
+                    }
                     (XmlReadingState::InMapTileset, "tileset") => XmlReadingState::InMap,
                     (XmlReadingState::InMapObjectgroup, "objectgroup") => XmlReadingState::InMap,
+                    // checkpoints are not objects, only the height the view has to reach
+                    (XmlReadingState::InMapObjectgroupObject, "object") if properties.get("type").map(String::as_str) == Some("Checkpoint") => {
+                        let y: f32 = properties.get("y").expect("Missing y").parse().unwrap();
+                        checkpoints.push(y);
+                        properties.clear();
+                        XmlReadingState::InMapObjectgroup
+                    }
                     (XmlReadingState::InMapObjectgroupObject, "object") => {
                         let result = states::create_game_object(&properties).unwrap();
                         game_objects.push(Some(result));
@@ -109,13 +178,14 @@ pub fn parse(filename: &str) -> (Vec<Option<GameObject>>, Level, HashMap<String,
                         let width = properties.get("width").expect("Missing width").parse().unwrap();
                         let height = properties.get("height").expect("Missing height").parse().unwrap();
                         let frames = properties.get("numFrames").map_or(1, |s| s.parse().unwrap());
+                        let columns = properties.get("columns").map_or(frames, |s| s.parse().unwrap());
                         let padding = properties
                             .get("padding")
                             .map_or(if frames == 1 { 0 } else { 1 }, |s| s.parse().unwrap());
 
                         texture_wrappers.insert(
                             texture_id.clone(),
-                            TextureWrapper::new(texture_id, width, height, padding, frames),
+                            TextureWrapper::new(texture_id, width, height, padding, frames, columns),
                         );
                         properties.clear();
                         XmlReadingState::InMapObjectgroup
@@ -137,18 +207,41 @@ pub fn parse(filename: &str) -> (Vec<Option<GameObject>>, Level, HashMap<String,
         }
     }
 
-    (game_objects, Level::new(width, height, tiles), texture_wrappers, color, tiles_filename)
+    let mut level = Level::new(width, height, Vec::new());
+    level.checkpoints = checkpoints;
+    level.layers = layers;
+
+    (game_objects, level, texture_wrappers, color, tiles_filename)
+}
+
+fn offset(attributes: &[OwnedAttribute]) -> Position {
+    let x = find_attribute(attributes, "offsetx").unwrap_or(0.0);
+    let y = find_attribute(attributes, "offsety").unwrap_or(0.0);
+    Position::new(x, y)
+}
+
+fn parallax(attributes: &[OwnedAttribute]) -> (f32, f32) {
+    (find_attribute(attributes, "parallaxx").unwrap_or(1.0), find_attribute(attributes, "parallaxy").unwrap_or(1.0))
+}
+
+// Tiled writes flags as 1 and leaves them out when they are not set
+fn is_set(attributes: &[OwnedAttribute], name: &str) -> bool {
+    find_attribute(attributes, name) == Some(1)
 }
 
 #[cfg(test)]
 mod tests {
     use game::Id;
+    use game::ImageLayer;
+    use game::LevelLayer;
+    use game::Position;
     use helpers::parsers;
 
     #[test]
     fn test_parsing() {
         let (game_objects, level, texture_wrappers, color, tiles_filename) = parsers::map_file::parse("assets/map1.tmx");
-        assert_eq!(game_objects.len(), 3);
+        assert_eq!(game_objects.len(), 6);
+        assert_eq!(level.checkpoints, vec![992.0]);
 
         let ids: Vec<Id> = game_objects
             .into_iter()
@@ -157,19 +250,57 @@ mod tests {
             .map(|game_object| game_object.id)
             .collect();
 
-        assert_eq!(ids[0], 1);
-        assert_eq!(ids[1], 2);
-        assert_eq!(ids[2], 3);
+        // ids come from a counter shared with every other test, only their order is known
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
 
-        assert_eq!(texture_wrappers.len(), 2);
+        assert_eq!(texture_wrappers.len(), 4);
 
-        assert_eq!(level.tiles.len(), (level.width * level.height) as usize);
-        assert_eq!(&level.tiles[0..20], &vec![3; 20][..]);
         assert_eq!(level.width, 20);
         assert_eq!(level.height, 60);
+        assert_eq!(level.layers.len(), 1);
+        let tiles = level.tile_layer(0).unwrap();
+        assert_eq!(tiles.tiles.len(), (level.width * level.height) as usize);
+        assert_eq!(&tiles.tiles[0..20], &vec![3; 20][..]);
+        assert_eq!((tiles.offset, tiles.parallax), (Position::new(0.0, 0.0), (1.0, 1.0)));
 
         assert_eq!(color, (2, 45, 155));
 
         assert_eq!(tiles_filename, "tiles.tsx".to_string());
     }
+
+    #[test]
+    fn test_parsing_tile_layers() {
+        //when
+        let (_, level, _, _, _) = parsers::map_file::parse("assets/layers.tmx");
+
+        //then
+        assert_eq!(level.layers.len(), 2);
+        let ground = level.tile_layer(0).unwrap();
+        let clouds = level.tile_layer(1).unwrap();
+        assert_eq!(ground.tiles, vec![3; 20 * 20]);
+        assert_eq!(clouds.tiles.len(), 20 * 20);
+        assert_eq!((clouds.offset, clouds.parallax), (Position::new(16.0, 0.0), (1.0, 0.5)));
+        let cloud_tiles: Vec<(u32, u32, u8)> = level.chunk_tiles(1, (0, 0)).collect();
+        assert_eq!(cloud_tiles, vec![(0, 10, 6), (1, 10, 6), (2, 10, 6), (3, 10, 6)]);
+    }
+
+    #[test]
+    fn test_parsing_image_layers() {
+        //when
+        let (_, level, texture_wrappers, _, _) = parsers::map_file::parse("assets/map2.tmx");
+
+        //then
+        let clouds = ImageLayer {
+            texture_id: String::from("assets/clouds.png"),
+            source: String::from("assets/clouds.png"),
+            width: 256,
+            height: 256,
+            offset: Position::new(0.0, 0.0),
+            parallax: (1.0, 1.5),
+            repeat: (true, true),
+        };
+        assert_eq!(level.layers.len(), 2);
+        assert_eq!(level.layers[1], LevelLayer::Image(clouds));
+        assert!(texture_wrappers.contains_key("assets/clouds.png"));
+    }
 }
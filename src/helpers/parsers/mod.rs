@@ -1,4 +1,5 @@
 extern crate inflate;
+extern crate serde_json;
 extern crate xml;
 
 use helpers::parsers::xml::attribute::OwnedAttribute;
@@ -7,8 +8,14 @@ use std::fs::File;
 use std::io::BufReader;
 use std::str::FromStr;
 
+pub mod campaign_file;
 pub mod game_file;
+pub mod high_score_file;
+pub mod input_script_file;
 pub mod map_file;
+pub mod pattern_file;
+pub mod save_file;
+pub mod sprite_sheet_file;
 pub mod tiles_file;
 
 pub fn parser(filename: &str) -> EventReader<BufReader<File>> {
@@ -0,0 +1,129 @@
+use game::BulletPattern;
+use game::PatternStep;
+use helpers::parsers::find_attribute;
+use helpers::parsers::parser;
+use helpers::parsers::xml::attribute::OwnedAttribute;
+use helpers::parsers::xml::reader::XmlEvent;
+use std::collections::HashMap;
+
+// Steps of the <repeat> or <emitter> currently being read, closed into a PatternStep by its end tag
+struct OpenBlock {
+    step: PatternStep,
+    steps: Vec<PatternStep>,
+}
+
+pub fn parse(filename: &str, patterns: &mut HashMap<String, BulletPattern>) {
+    let mut pattern: Option<(String, bool, f32)> = None;
+    let mut steps = Vec::new();
+    let mut blocks: Vec<OpenBlock> = Vec::new();
+
+    for e in parser(filename) {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                let local_name = name.local_name.to_ascii_lowercase();
+                if local_name == "pattern" {
+                    let id: String = find_attribute(&attributes, "id").expect("Missing pattern id");
+                    let looping = find_attribute(&attributes, "loop").unwrap_or(false);
+                    let jitter = find_attribute(&attributes, "jitter").unwrap_or(0.0);
+                    pattern = Some((id, looping, jitter));
+                    steps.clear();
+                    continue;
+                }
+                if pattern.is_none() {
+                    continue;
+                }
+
+                let step = parse_step(&local_name, &attributes);
+                match step {
+                    Some(step @ PatternStep::Repeat { .. }) | Some(step @ PatternStep::Emitter { .. }) => {
+                        blocks.push(OpenBlock { step, steps: Vec::new() })
+                    }
+                    Some(step) => blocks.last_mut().map_or(&mut steps, |block| &mut block.steps).push(step),
+                    None => println!("unknown pattern element: {:?}", local_name),
+                }
+            }
+            Ok(XmlEvent::EndElement { name }) => {
+                let local_name = name.local_name.to_ascii_lowercase();
+                match local_name.as_str() {
+                    "repeat" | "emitter" => {
+                        let block = blocks.pop().expect("Unbalanced pattern block");
+                        let step = match block.step {
+                            PatternStep::Repeat { times, delay, .. } => PatternStep::Repeat { times, delay, steps: block.steps },
+                            PatternStep::Emitter { spin, .. } => PatternStep::Emitter { spin, steps: block.steps },
+                            step => step,
+                        };
+                        blocks.last_mut().map_or(&mut steps, |block| &mut block.steps).push(step);
+                    }
+                    "pattern" => {
+                        if let Some((id, looping, jitter)) = pattern.take() {
+                            let mut bullet_pattern = BulletPattern::new(&steps, looping);
+                            bullet_pattern.jitter = jitter;
+                            patterns.insert(id, bullet_pattern);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_step(name: &str, attributes: &[OwnedAttribute]) -> Option<PatternStep> {
+    let count = find_attribute(attributes, "count").unwrap_or(1);
+    let angle = find_attribute(attributes, "angle").unwrap_or(0.0);
+    let speed = find_attribute(attributes, "speed").unwrap_or(2.0);
+
+    match name {
+        "ring" => Some(PatternStep::Ring { count, angle, speed }),
+        "aimed" => {
+            let spread = find_attribute(attributes, "spread").unwrap_or(0.0);
+            Some(PatternStep::Aimed { count, spread, angle, speed })
+        }
+        "wait" => Some(PatternStep::Wait(find_attribute(attributes, "ticks").expect("Missing wait ticks"))),
+        "repeat" => {
+            let times = find_attribute(attributes, "times").expect("Missing repeat times");
+            let delay = find_attribute(attributes, "delay").unwrap_or(0);
+            Some(PatternStep::Repeat { times, delay, steps: Vec::new() })
+        }
+        "emitter" => {
+            let spin = find_attribute(attributes, "spin").unwrap_or(0.0);
+            Some(PatternStep::Emitter { spin, steps: Vec::new() })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use helpers::parsers;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parsing() {
+        //given
+        let mut patterns = HashMap::new();
+
+        //when
+        parsers::pattern_file::parse("assets/patterns.xml", &mut patterns);
+
+        //then
+        let ring = &patterns["ring"];
+        assert!(ring.looping);
+        assert_eq!(ring.shots.len(), 12);
+
+        let spiral = &patterns["spiral"];
+        assert_eq!(spiral.shots.len(), 90);
+        assert_eq!(spiral.shots[3].angle, 21.0);
+        assert_eq!(spiral.duration, 150);
+
+        let burst = &patterns["aimedBurst"];
+        assert!(burst.shots.iter().all(|shot| shot.aimed));
+        assert_eq!(burst.jitter, 4.0);
+        assert_eq!(ring.jitter, 0.0);
+    }
+}
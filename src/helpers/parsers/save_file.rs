@@ -0,0 +1,92 @@
+use game::Record;
+use helpers::parsers::find_attribute;
+use helpers::parsers::parser;
+use helpers::parsers::xml::reader::XmlEvent;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// The version written on the <SAVE> element and every record inside it
+pub fn parse(filename: &str) -> Result<(u32, Vec<Record>), String> {
+    if !Path::new(filename).is_file() {
+        return Err(format!("Missing save file: {:?}", filename));
+    }
+
+    let mut version = None;
+    let mut records = Vec::new();
+    for e in parser(filename) {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                if name.local_name.eq_ignore_ascii_case("save") {
+                    version = find_attribute(&attributes, "version");
+                    continue;
+                }
+                records.push(Record {
+                    name: name.local_name,
+                    attributes: attributes.into_iter().map(|attr| (attr.name.local_name, attr.value)).collect(),
+                });
+            }
+            Err(e) => return Err(format!("Error reading save file {:?}: {}", filename, e)),
+            _ => {}
+        }
+    }
+
+    let version = version.ok_or_else(|| format!("Save file without a version: {:?}", filename))?;
+    Ok((version, records))
+}
+
+pub fn write(filename: &str, version: u32, records: &[Record]) -> io::Result<()> {
+    let mut content = format!("<?xml version=\"1.0\" ?>\n<SAVE version=\"{}\">\n", version);
+    for record in records {
+        content.push_str(&format!("    <{}", record.name));
+        for (key, value) in &record.attributes {
+            content.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+        content.push_str("/>\n");
+    }
+    content.push_str("</SAVE>\n");
+
+    fs::write(filename, content)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use game::Record;
+    use helpers::parsers;
+    use std::env;
+
+    #[test]
+    fn test_writing_and_parsing() {
+        //given
+        let filename = env::temp_dir().join("rusty-pew-pew-save.xml");
+        let filename = filename.to_str().unwrap();
+        let records = vec![
+            Record { name: String::from("level"), attributes: vec![(String::from("y"), String::from("1320.5"))] },
+            Record { name: String::from("object"), attributes: vec![(String::from("pattern"), String::from("<&>"))] },
+        ];
+
+        //when
+        parsers::save_file::write(filename, 1, &records).unwrap();
+        let parsed = parsers::save_file::parse(filename);
+
+        //then
+        assert_eq!(parsed, Ok((1, records)));
+    }
+
+    #[test]
+    fn test_parsing_missing_file() {
+        //when
+        let parsed = parsers::save_file::parse("assets/missing-save.xml");
+
+        //then
+        assert!(parsed.is_err());
+    }
+}
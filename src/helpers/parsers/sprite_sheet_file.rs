@@ -0,0 +1,183 @@
+use game::Animation;
+use game::AnimationFrame;
+use game::PlaybackMode;
+use helpers::parsers::serde_json;
+use helpers::parsers::serde_json::Value;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Reads TexturePacker (hash or array) and Aseprite JSON exports.
+// Returns the sheet image path, every frame in the order the file lists them and the clips: one looping over
+// all frames under `texture_id` plus one per Aseprite frame tag named `<texture_id>_<tag>`.
+// Rotated and trimmed frames are not supported, sheets have to be exported without either.
+pub fn parse(filename: &str, texture_id: &str) -> (String, Vec<AnimationFrame>, Vec<Animation>) {
+    let mut content = String::new();
+    File::open(filename)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .unwrap_or_else(|e| panic!("Error reading {:?}: {}", filename, e));
+
+    let directory = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+    parse_str(&content, directory, texture_id).unwrap_or_else(|e| panic!("Error parsing {:?}: {}", filename, e))
+}
+
+fn parse_str(content: &str, directory: &Path, texture_id: &str) -> Result<(String, Vec<AnimationFrame>, Vec<Animation>), String> {
+    let json: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+
+    let image = json["meta"]["image"].as_str().ok_or("Missing meta.image")?;
+    let image = directory.join(image).to_string_lossy().replace('\\', "/");
+
+    let frames = match json["frames"] {
+        Value::Array(ref frames) => frames.iter().map(parse_frame).collect::<Result<Vec<_>, _>>()?,
+        Value::Object(ref frames) => frames.values().map(parse_frame).collect::<Result<Vec<_>, _>>()?,
+        _ => return Err(String::from("Missing frames")),
+    };
+
+    let mut animations = vec![Animation::new(
+        texture_id.to_string(),
+        texture_id.to_string(),
+        PlaybackMode::Loop,
+        frames.clone(),
+    )];
+
+    if let Some(tags) = json["meta"]["frameTags"].as_array() {
+        for tag in tags {
+            animations.push(parse_tag(tag, &frames, texture_id)?);
+        }
+    }
+
+    Ok((image, frames, animations))
+}
+
+fn parse_frame(frame: &Value) -> Result<AnimationFrame, String> {
+    if frame["rotated"].as_bool() == Some(true) {
+        return Err(String::from("Rotated frames are not supported"));
+    }
+    if frame["trimmed"].as_bool() == Some(true) {
+        return Err(String::from("Trimmed frames are not supported"));
+    }
+
+    let rect = &frame["frame"];
+    let field = |name: &str| rect[name].as_u64().ok_or(format!("Missing frame.{}", name));
+
+    let duration = frame["duration"].as_u64().unwrap_or(100);
+    Ok(AnimationFrame::new(
+        field("x")? as i32,
+        field("y")? as i32,
+        field("w")? as u32,
+        field("h")? as u32,
+        duration as u32,
+    ))
+}
+
+fn parse_tag(tag: &Value, frames: &[AnimationFrame], texture_id: &str) -> Result<Animation, String> {
+    let name = tag["name"].as_str().ok_or("Missing frameTags.name")?;
+    let from = tag["from"].as_u64().ok_or("Missing frameTags.from")? as usize;
+    let to = tag["to"].as_u64().ok_or("Missing frameTags.to")? as usize;
+    if from > to || to >= frames.len() {
+        return Err(format!("Frame tag {:?} out of range", name));
+    }
+
+    let mut tag_frames = frames[from..=to].to_vec();
+    let mode = match tag["direction"].as_str().unwrap_or("forward") {
+        "forward" => PlaybackMode::Loop,
+        "reverse" => {
+            tag_frames.reverse();
+            PlaybackMode::Loop
+        }
+        "pingpong" => PlaybackMode::PingPong,
+        direction => return Err(format!("Unknown direction: {:?}", direction)),
+    };
+
+    Ok(Animation::new(
+        format!("{}_{}", texture_id, name),
+        texture_id.to_string(),
+        mode,
+        tag_frames,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use game::AnimationFrame;
+    use game::PlaybackMode;
+    use helpers::parsers;
+    use std::path::Path;
+
+    #[test]
+    fn test_parsing_aseprite() {
+        //when
+        let (image, frames, animations) = parsers::sprite_sheet_file::parse("assets/plane.json", "plane");
+
+        //then
+        assert_eq!(image, "assets/plane.png");
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1], AnimationFrame::new(67, 1, 65, 65, 100));
+
+        assert_eq!(animations.len(), 2);
+        assert_eq!(animations[0].id, "plane");
+        assert_eq!(animations[1].id, "plane_idle");
+        assert_eq!(animations[1].mode, PlaybackMode::PingPong);
+        assert_eq!(animations[1].frames.len(), 3);
+    }
+
+    #[test]
+    fn test_parsing_texture_packer_hash() {
+        //given
+        let frame = |i: i32| format!(r#""walk_{}.png": {{ "frame": {{ "x": {}, "y": 0, "w": 32, "h": 32 }} }}"#, i, i * 32);
+        let frames: Vec<String> = (1..=12).map(frame).collect();
+        let content = format!(
+            r#"{{ "frames": {{ {} }}, "meta": {{ "image": "walk.png", "size": {{ "w": 416, "h": 32 }} }} }}"#,
+            frames.join(",")
+        );
+
+        //when
+        let (image, frames, animations) = super::parse_str(&content, Path::new("assets"), "walk").unwrap();
+
+        //then
+        // in file order, not walk_1, walk_10, walk_11, walk_12, walk_2...
+        assert_eq!(image, "assets/walk.png");
+        let xs: Vec<i32> = frames.iter().map(|frame| frame.x).collect();
+        assert_eq!(xs, (1..=12).map(|i| i * 32).collect::<Vec<_>>());
+        assert_eq!(frames[0], AnimationFrame::new(32, 0, 32, 32, 100));
+        assert_eq!(animations.len(), 1);
+    }
+
+    #[test]
+    fn test_rotated_and_trimmed_frames_are_rejected() {
+        //given
+        let frame = |flags: &str| {
+            let frame = format!(r#"{{ "frame": {{ "x": 0, "y": 0, "w": 8, "h": 8 }}, {} }}"#, flags);
+            format!(r#"{{ "frames": [ {} ], "meta": {{ "image": "a.png" }} }}"#, frame)
+        };
+
+        //when
+        let rotated = super::parse_str(&frame(r#""rotated": true, "trimmed": false"#), Path::new(""), "a");
+        let trimmed = super::parse_str(&frame(r#""rotated": false, "trimmed": true"#), Path::new(""), "a");
+        let neither = super::parse_str(&frame(r#""rotated": false, "trimmed": false"#), Path::new(""), "a");
+
+        //then
+        assert!(rotated.is_err());
+        assert!(trimmed.is_err());
+        assert!(neither.is_ok());
+    }
+
+    #[test]
+    fn test_parsing_reverse_tag() {
+        //given
+        let content = r#"{
+            "frames": [
+                { "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 50 },
+                { "frame": { "x": 8, "y": 0, "w": 8, "h": 8 }, "duration": 50 }
+            ],
+            "meta": { "image": "spark.png", "frameTags": [ { "name": "fade", "from": 0, "to": 1, "direction": "reverse" } ] }
+        }"#;
+
+        //when
+        let (_, _, animations) = super::parse_str(content, Path::new(""), "spark").unwrap();
+
+        //then
+        assert_eq!(animations[1].id, "spark_fade");
+        assert_eq!(animations[1].frames[0].x, 8);
+    }
+}
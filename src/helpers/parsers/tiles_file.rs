@@ -17,6 +17,7 @@ pub fn parse(filename: &str, textures: &mut Vec<(String, String)>, texture_wrapp
     let mut tile_height = 0;
     let mut tile_width = 0;
     let mut tile_count = 0;
+    let mut columns = 0;
 //    let mut margin = 0;
     let mut spacing = 0;
 
@@ -30,6 +31,7 @@ pub fn parse(filename: &str, textures: &mut Vec<(String, String)>, texture_wrapp
                         tile_width = find_attribute(&attributes, "tilewidth").expect("Missing tiles width");
                         tile_count = find_attribute(&attributes, "tilecount").expect("Missing tilecount width");
                         spacing = find_attribute(&attributes, "spacing").expect("Missing spacing width");
+                        columns = find_attribute(&attributes, "columns").unwrap_or(tile_count);
 //                        margin = find_attribute(&attributes, "margin").expect("Missing margin width");
                         XmlReadingState::InTileset
                     }
@@ -38,7 +40,7 @@ pub fn parse(filename: &str, textures: &mut Vec<(String, String)>, texture_wrapp
                         let key = String::from("tiles");
                         texture_wrappers.insert(
                             key.clone(),
-                            TextureWrapper::new(key.clone(), tile_width, tile_height, spacing, tile_count),
+                            TextureWrapper::new(key.clone(), tile_width, tile_height, spacing, tile_count, columns),
                         );
                         textures.push((key, format!("assets/{}", source)));
 
@@ -91,6 +93,6 @@ mod tests {
         assert_eq!(textures[0], (String::from("tiles"), String::from("assets/tiles.png")));
 
         assert_eq!(texture_wrappers.len(), 1);
-        assert_eq!(texture_wrappers.get("tiles"), Some(&TextureWrapper::new(String::from("tiles"), 32, 32, 1, 3)));
+        assert_eq!(texture_wrappers.get("tiles"), Some(&TextureWrapper::new(String::from("tiles"), 32, 32, 1, 3, 3)));
     }
 }
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Polls a directory for added or modified files. Size is tracked next to the modification time
+// because some filesystems only store whole seconds.
+pub struct AssetWatcher {
+    directory: PathBuf,
+    interval: u32,
+    ticks: u32,
+    files: HashMap<PathBuf, (SystemTime, u64)>,
+}
+
+impl AssetWatcher {
+    pub fn new<P: AsRef<Path>>(directory: P, interval: u32) -> AssetWatcher {
+        let mut watcher = AssetWatcher {
+            directory: directory.as_ref().to_path_buf(),
+            interval,
+            ticks: 0,
+            files: HashMap::new(),
+        };
+        watcher.files = watcher.scan();
+        watcher
+    }
+
+    // Call once per frame; the directory is only scanned every `interval` ticks
+    pub fn tick(&mut self) -> Vec<PathBuf> {
+        self.ticks += 1;
+        if self.ticks < self.interval {
+            return Vec::new();
+        }
+        self.ticks = 0;
+        self.poll()
+    }
+
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let files = self.scan();
+        let mut changed: Vec<PathBuf> = files
+            .iter()
+            .filter(|(path, stamp)| self.files.get(*path) != Some(stamp))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+
+        self.files = files;
+        changed
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, (SystemTime, u64)> {
+        let mut files = HashMap::new();
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("Error watching {:?}: {}", self.directory, e);
+                return files;
+            }
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if let Ok(metadata) = entry.metadata() {
+                if metadata.is_file() {
+                    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    files.insert(entry.path(), (modified, metadata.len()));
+                }
+            }
+        }
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use helpers::watcher::AssetWatcher;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_no_changes() {
+        //given
+        let directory = create_temp_directory("no_changes");
+        fs::write(directory.join("map.tmx"), "<map/>").unwrap();
+        let mut watcher = AssetWatcher::new(&directory, 1);
+
+        //when
+        let changed = watcher.poll();
+
+        //then
+        assert!(changed.is_empty());
+    }
+
+    #[test]
+    fn test_added_and_modified_files() {
+        //given
+        let directory = create_temp_directory("modified");
+        fs::write(directory.join("map.tmx"), "<map/>").unwrap();
+        let mut watcher = AssetWatcher::new(&directory, 1);
+
+        //when
+        fs::write(directory.join("map.tmx"), "<map></map>").unwrap();
+        fs::write(directory.join("tiles.tsx"), "<tileset/>").unwrap();
+        let changed = watcher.poll();
+
+        //then
+        assert_eq!(changed, vec![directory.join("map.tmx"), directory.join("tiles.tsx")]);
+        assert!(watcher.poll().is_empty());
+    }
+
+    #[test]
+    fn test_tick_interval() {
+        //given
+        let directory = create_temp_directory("interval");
+        let mut watcher = AssetWatcher::new(&directory, 3);
+        fs::write(directory.join("game.xml"), "<STATES/>").unwrap();
+
+        //when
+        let first = watcher.tick();
+        let second = watcher.tick();
+        let third = watcher.tick();
+
+        //then
+        assert!(first.is_empty());
+        assert!(second.is_empty());
+        assert_eq!(third, vec![directory.join("game.xml")]);
+    }
+
+    fn create_temp_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("rusty-pew-pew-watcher-{}", name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+}
@@ -5,55 +5,462 @@ extern crate sdl2;
 #[macro_use]
 extern crate lazy_static;
 
+use game::Renderer;
+use helpers::assets::AssetKind;
+use helpers::assets::AssetRegistry;
 use helpers::parsers;
+use helpers::watcher::AssetWatcher;
+use net::NetSession;
+use net::UdpPeer;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 mod game;
 mod helpers;
+mod net;
 mod sdl;
 
 const FPS: u8 = 60;
 
-lazy_static! {
-    static ref DELAY: Duration = Duration::new(0, 1_000_000_000 / FPS as u32);
-}
-
 const SCREEN_SIZE: (u32, u32) = (800, 600);
 
+const GAME_FILENAME: &str = "assets/game.xml";
+// played on its own when game.xml declares no campaign
+const MAP_ID: &str = "map1";
+const HIGH_SCORES_FILENAME: &str = "highscores.xml";
+const SAVE_FILENAME: &str = "savegame.xml";
+const CAPTURE_DIRECTORY: &str = "screenshots";
+// how long a networked game waits for the other player to start theirs
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+#[cfg(feature = "ttf")]
+const HUD_FONT_ID: &str = "hud";
+
 pub fn main() {
     println!("Starting up");
 
+    if let Some(arg) = env::args().find(|arg| arg.starts_with("--check-determinism=")) {
+        let passed = check_determinism(&arg["--check-determinism=".len()..]);
+        process::exit(if passed { 0 } else { 1 });
+    }
+    let capture = capture_from_args();
+    let profile = env::args().find(|arg| arg.starts_with("--profile=")).map(|arg| arg["--profile=".len()..].to_string());
+    if let Some(arg) = env::args().find(|arg| arg.starts_with("--replay=")) {
+        let passed = replay(&arg["--replay=".len()..], &capture, profile.as_deref());
+        process::exit(if passed { 0 } else { 1 });
+    }
+    if let Some(arg) = env::args().find(|arg| arg.starts_with("--benchmark-tiles=")) {
+        let passed = benchmark_tiles(&arg["--benchmark-tiles=".len()..]);
+        process::exit(if passed { 0 } else { 1 });
+    }
+
     let sdl_context = sdl::SDLEngine::init();
+    #[cfg(feature = "ttf")]
+    let ttf_context = sdl2::ttf::init().expect("Error initializing SDL2 TTF");
+
+    let dev_mode = env::args().any(|arg| arg == "--dev");
+    let mut net_session = net_session_from_args(seed_from_args());
+    let co_op = net_session.is_some() || env::args().any(|arg| arg == "--coop");
+    let seed = net_session.as_ref().map_or_else(seed_from_args, NetSession::seed);
+    println!("Random seed: {} (replay with --seed={})", seed, seed);
+    let mut watcher = if dev_mode { Some(AssetWatcher::new("assets", u32::from(FPS))) } else { None };
+
+    let mut registry = AssetRegistry::new();
+    parsers::game_file::parse_assets(GAME_FILENAME, &mut registry);
+
+    let map_loader = sdl::MapLoader;
+    let mut maps = sdl::MapManager::new(&map_loader);
+    for (id, asset) in registry.of_kind(AssetKind::Map) {
+        maps.register(id, &asset.filename, "game");
+    }
 
-    let (game_objects, level, texture_wrappers, background_color, tiles_filename) = parsers::map_file::parse("assets/map1.tmx");
+    let campaign = load_campaign(&registry);
+    let map_id = campaign.current().map_or(MAP_ID, |stage| stage.map.as_str());
+    let map = maps.load(map_id).unwrap_or_else(|e| panic!("{}", e));
+    let (game_objects, level, texture_wrappers, background_color, tiles_filename) = (*map).clone();
 
     let input_handler = sdl::SDLInputHandler::new(&sdl_context);
 
-    let (canvas, texture_creator, timer) = sdl::SDLRenderer::init(&sdl_context, background_color);
+    let (canvas, texture_creator) = sdl::SDLRenderer::init(&sdl_context, background_color);
     let texture_manager = sdl::TextureManager::new(&texture_creator);
-    let renderer = sdl::SDLRenderer::new(canvas, texture_manager, texture_wrappers, &tiles_filename, timer);
+    #[allow(unused_mut)]
+    let mut renderer = sdl::SDLRenderer::new(canvas, texture_manager, texture_wrappers, &tiles_filename);
+    #[cfg(feature = "ttf")]
+    {
+        let mut fonts = sdl::FontManager::new(&ttf_context);
+        for (id, asset) in registry.of_kind(AssetKind::Font) {
+            let details = sdl::FontDetails { path: asset.filename.clone(), size: asset.size };
+            fonts.register(id, &details, "game");
+        }
+        renderer.use_font(fonts, HUD_FONT_ID);
+    }
+    #[cfg(feature = "audio")]
+    let audio_loader = sdl::AudioLoader;
+    #[cfg(feature = "audio")]
+    let _audio = load_audio(&audio_loader, &registry);
 
     let mut engine = game::Engine::new(game_objects, level, renderer, input_handler);
+    engine.set_high_scores(game::HighScores::load(HIGH_SCORES_FILENAME));
+    engine.set_patterns(load_patterns(&registry));
+    engine.set_campaign(campaign);
+    // a save would only ever be loaded on one side of a networked game
+    if net_session.is_none() {
+        engine.set_save_file(SAVE_FILENAME);
+    }
+    engine.set_seed(seed);
+    if co_op {
+        // the engine starts out single-player and leaves the second plane out, it is put back with the map
+        engine.set_player_count(2);
+        engine.restart(map.0.clone(), map.1.clone());
+    }
+
+    if profile.is_some() {
+        engine.profiler_mut().start_recording();
+    }
+
+    let mut fps_start = SystemTime::now();
+    let mut frames = 0;
+    let mut frame = 0;
 
     while engine.is_running {
         let frame_start = SystemTime::now();
 
-        engine.handle_input();
-        engine.update();
+        if let Some(ref mut watcher) = watcher {
+            reload_changed_assets(&watcher.tick(), &registry, &mut maps, &mut engine);
+        }
+
+        if let Some(map_id) = engine.take_pending_map() {
+            start_stage(&map_id, &mut maps, &mut engine);
+        }
+
+        let mut inputs = engine.capture_input();
+        if inputs.contains(&game::InputState::ToggleConsole) {
+            engine.toggle_console();
+        }
+        // the planes stand still while the console takes the keys
+        if engine.is_console_open() {
+            engine.type_into_console(&inputs);
+            inputs.retain(|input| *input == game::InputState::Quit);
+        }
+        match net_session {
+            Some(ref mut net_session) => {
+                if inputs.contains(&game::InputState::Quit) {
+                    engine.is_running = false;
+                }
+                net_session.tick(&mut engine, &inputs);
+            }
+            None => engine.step(&inputs),
+        }
+        if inputs.contains(&game::InputState::ToggleDebugOverlay) {
+            engine.toggle_debug_overlay();
+        }
+        if inputs.contains(&game::InputState::Screenshot) || capture.is_wanted(frame) {
+            engine.renderer_mut().capture_next_frame(capture.path(frame));
+        }
         engine.draw();
+        frame += 1;
+
+        frame_sync_wait(frame_start, engine.target_fps());
 
-        frame_sync_wait(frame_start);
+        frames += 1;
+        if fps_start.elapsed().map(|elapsed| elapsed.as_secs() >= 1).unwrap_or(true) {
+            engine.set_fps(frames);
+            fps_start = SystemTime::now();
+            frames = 0;
+        }
     }
 
+    if let Some(ref profile) = profile {
+        write_profile(&engine, profile);
+    }
     println!("Shutting down. Goodbye!");
 }
 
-fn frame_sync_wait(frame_start: SystemTime) {
+// Any asset change re-creates the textures; a changed map also restarts the level from its beginning
+fn reload_changed_assets<I: game::InputHandler>(changed: &[PathBuf],
+                                                registry: &AssetRegistry,
+                                                maps: &mut sdl::MapManager,
+                                                engine: &mut game::Engine<sdl::SDLRenderer, I>) {
+    if changed.is_empty() {
+        return;
+    }
+    println!("Reloading assets, changed: {:?}", changed);
+
+    let patterns = registry.of_kind(AssetKind::Patterns);
+    if patterns.iter().any(|(_, asset)| changed.iter().any(|path| path == Path::new(&asset.filename))) {
+        engine.set_patterns(load_patterns(registry));
+    }
+
+    let map_id = engine.current_map().unwrap_or(MAP_ID).to_string();
+    let mut restart = false;
+    for (id, asset) in registry.of_kind(AssetKind::Map) {
+        if changed.iter().any(|path| path == Path::new(&asset.filename)) {
+            maps.register(id, &asset.filename, "game");
+            restart |= id == map_id;
+        }
+    }
+
+    let map = match maps.load(&map_id) {
+        Ok(map) => map,
+        Err(e) => return println!("{}", e),
+    };
+    let (game_objects, level, texture_wrappers, _, tiles_filename) = (*map).clone();
+    engine.renderer_mut().reload(texture_wrappers, &tiles_filename);
+
+    if restart {
+        engine.restart(game_objects, level);
+    }
+}
+
+// Swaps the engine over to the map of the stage it just reached
+fn start_stage<I: game::InputHandler>(map_id: &str, maps: &mut sdl::MapManager, engine: &mut game::Engine<sdl::SDLRenderer, I>) {
+    // the console can ask for any map, a mistyped one keeps the current stage going
+    let map = match maps.load(map_id) {
+        Ok(map) => map,
+        Err(e) => return println!("{}", e),
+    };
+    let (game_objects, level, texture_wrappers, background_color, tiles_filename) = (*map).clone();
+
+    engine.renderer_mut().reload(texture_wrappers, &tiles_filename);
+    engine.renderer_mut().set_background(background_color);
+    engine.start_level(game_objects, level);
+}
+
+fn load_campaign(registry: &AssetRegistry) -> game::Campaign {
+    let stages = registry
+        .of_kind(AssetKind::Campaign)
+        .iter()
+        .flat_map(|(_, asset)| parsers::campaign_file::parse(&asset.filename))
+        .collect();
+    game::Campaign::new(stages)
+}
+
+// Sounds and music are loaded up front, a file that is missing or can not be decoded shows at start
+#[cfg(feature = "audio")]
+fn load_audio<'l>(loader: &'l sdl::AudioLoader,
+                  registry: &AssetRegistry) -> (sdl::SoundManager<'l>, sdl::MusicManager<'l>) {
+    use sdl2::mixer;
+    if let Err(e) = mixer::open_audio(mixer::DEFAULT_FREQUENCY, mixer::DEFAULT_FORMAT, mixer::DEFAULT_CHANNELS, 1024) {
+        println!("Error opening audio: {}", e);
+    }
+
+    let mut sounds = sdl::SoundManager::new(loader);
+    for (id, asset) in registry.of_kind(AssetKind::Sound) {
+        sounds.register(id, &asset.filename, "game");
+        if let Err(e) = sounds.load(id) {
+            println!("{}", e);
+        }
+    }
+    let mut music = sdl::MusicManager::new(loader);
+    for (id, asset) in registry.of_kind(AssetKind::Music) {
+        music.register(id, &asset.filename, "game");
+        if let Err(e) = music.load(id) {
+            println!("{}", e);
+        }
+    }
+    (sounds, music)
+}
+
+fn load_patterns(registry: &AssetRegistry) -> HashMap<String, game::BulletPattern> {
+    let mut patterns = HashMap::new();
+    for (_, asset) in registry.of_kind(AssetKind::Patterns) {
+        parsers::pattern_file::parse(&asset.filename, &mut patterns);
+    }
+    patterns
+}
+
+// --seed=<number> replays a run, without it every start gets a new seed from the clock
+fn seed_from_args() -> u64 {
+    let seed = env::args().find(|arg| arg.starts_with("--seed=")).map(|arg| arg["--seed=".len()..].to_string());
+    match seed {
+        Some(seed) => seed.parse().unwrap_or_else(|_| panic!("Incorrect seed: {:?}", seed)),
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0),
+    }
+}
+
+// --net=<local address>,<other player's address> plays co-op with another instance, --player=2 on one side
+fn net_session_from_args<S: net::Simulation>(seed: u64) -> Option<NetSession<S>> {
+    let addresses = env::args().find(|arg| arg.starts_with("--net=")).map(|arg| arg["--net=".len()..].to_string())?;
+    let (local, remote) = match addresses.find(',') {
+        Some(comma) => (&addresses[..comma], &addresses[comma + 1..]),
+        None => panic!("Incorrect --net addresses: {:?}", addresses),
+    };
+    let player = if env::args().any(|arg| arg == "--player=2") { 1 } else { 0 };
+
+    println!("Waiting for the other player at {}", remote);
+    let peer = UdpPeer::connect(local, remote).unwrap_or_else(|e| panic!("Error opening socket on {}: {}", local, e));
+    Some(NetSession::connect(peer, player, seed, CONNECT_TIMEOUT).unwrap_or_else(|e| panic!("{}", e)))
+}
+
+// Plays an input script on two engines side by side without a window, they have to match every frame
+fn check_determinism(filename: &str) -> bool {
+    let script = match parsers::input_script_file::parse(filename) {
+        Ok(script) => script,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+    let mut registry = AssetRegistry::new();
+    parsers::game_file::parse_assets(GAME_FILENAME, &mut registry);
+    let patterns = load_patterns(&registry);
+    let (game_objects, level, _, _, _) = parsers::map_file::parse(&script.map);
+
+    // both get the objects of one parse, a second one would hand out other ids
+    let create_engine = || {
+        let mut engine = game::Engine::new(Vec::new(), level.clone(), game::Headless, game::Headless);
+        engine.set_patterns(patterns.clone());
+        engine.set_seed(script.seed);
+        engine.set_player_count(script.players);
+        engine.restart(game_objects.clone(), level.clone());
+        engine
+    };
+    let (mut first, mut second) = (create_engine(), create_engine());
+
+    match game::lockstep::run_lockstep(&mut first, &mut second, &script) {
+        Ok(()) => {
+            println!("Both runs of {:?} match for {} frames", filename, script.frames);
+            true
+        }
+        Err(divergence) => {
+            match divergence.object {
+                Some((id, object_type)) => {
+                    println!("Runs diverge at frame {}, first at object {} ({:?})", divergence.frame, id, object_type)
+                }
+                None => println!("Runs diverge at frame {}, first at the level's position", divergence.frame),
+            }
+            false
+        }
+    }
+}
+
+// F12 or --screenshot=<frame> saves a single frame, --capture-every=<n> every n-th one.
+// They go to --capture-dir=<directory>, screenshots/ without it.
+fn capture_from_args() -> sdl::FrameCapture {
+    let value = |flag: &str| env::args().find(|arg| arg.starts_with(flag)).map(|arg| arg[flag.len()..].to_string());
+    let frame = |flag: &str| {
+        value(flag).map(|frame| frame.parse().unwrap_or_else(|_| panic!("Incorrect {}{:?}", flag, frame)))
+    };
+    let directory = value("--capture-dir=").unwrap_or_else(|| String::from(CAPTURE_DIRECTORY));
+    sdl::FrameCapture::new(&directory, frame("--capture-every="), frame("--screenshot="))
+}
+
+// Plays an input script on a software canvas without a window, to capture its frames or benchmark them.
+// With --profile=<file> every frame's timings and counters are written to it as CSV
+fn replay(filename: &str, capture: &sdl::FrameCapture, profile: Option<&str>) -> bool {
+    let script = match parsers::input_script_file::parse(filename) {
+        Ok(script) => script,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+    let mut registry = AssetRegistry::new();
+    parsers::game_file::parse_assets(GAME_FILENAME, &mut registry);
+    let (game_objects, level, texture_wrappers, background_color, tiles_filename) = parsers::map_file::parse(&script.map);
+
+    let _sdl_context = sdl::SDLEngine::init();
+    let (screen_width, screen_height) = SCREEN_SIZE;
+    let (canvas, texture_creator) = match sdl::SDLRenderer::init_software(screen_width, screen_height, background_color) {
+        Ok(software) => software,
+        Err(e) => {
+            println!("Error creating a software canvas: {}", e);
+            return false;
+        }
+    };
+    let texture_manager = sdl::TextureManager::new(&texture_creator);
+    let renderer = sdl::SDLRenderer::new(canvas, texture_manager, texture_wrappers, &tiles_filename);
+
+    let mut engine = game::Engine::new(Vec::new(), level.clone(), renderer, game::Headless);
+    engine.set_patterns(load_patterns(&registry));
+    engine.set_seed(script.seed);
+    engine.set_player_count(script.players);
+    engine.restart(game_objects, level);
+    if profile.is_some() {
+        engine.profiler_mut().start_recording();
+    }
+
+    for frame in 0..script.frames {
+        engine.step(&script.inputs(frame));
+        if capture.is_wanted(frame) {
+            engine.renderer_mut().capture_next_frame(capture.path(frame));
+        }
+        engine.draw();
+    }
+    println!("Replayed {} frames of {:?}", script.frames, filename);
+    if let Some(profile) = profile {
+        write_profile(&engine, profile);
+    }
+    true
+}
+
+// Scrolls through a whole map on a software canvas, once drawing tile by tile and once in baked chunks
+fn benchmark_tiles(filename: &str) -> bool {
+    let (_, level, texture_wrappers, background_color, tiles_filename) = parsers::map_file::parse(filename);
+
+    let _sdl_context = sdl::SDLEngine::init();
+    let (screen_width, screen_height) = SCREEN_SIZE;
+    let (canvas, texture_creator) = match sdl::SDLRenderer::init_software(screen_width, screen_height, background_color) {
+        Ok(software) => software,
+        Err(e) => {
+            println!("Error creating a software canvas: {}", e);
+            return false;
+        }
+    };
+    let texture_manager = sdl::TextureManager::new(&texture_creator);
+    let mut renderer = sdl::SDLRenderer::new(canvas, texture_manager, texture_wrappers, &tiles_filename);
+
+    let start = Instant::now();
+    renderer.prepare_level(&level);
+    println!("Baked the chunks of {:?} in {:.3}ms", filename, start.elapsed().as_micros() as f64 / 1000.0);
+
+    let frames = level.position.y as u32 + 1;
+    for &(name, is_per_tile) in &[("tile by tile", true), ("in chunks", false)] {
+        let mut level = level.clone();
+        let mut draw_calls = 0;
+        let start = Instant::now();
+        for _ in 0..frames {
+            let mut renderer = game::DrawCounter::new(&mut renderer);
+            renderer.clear_scene();
+            if is_per_tile {
+                level.draw_tiles(&mut renderer);
+            } else {
+                level.draw(&mut renderer);
+            }
+            renderer.draw_scene();
+            draw_calls += renderer.calls();
+            level.update();
+        }
+        let milliseconds = start.elapsed().as_micros() as f64 / 1000.0 / f64::from(frames);
+        println!("{}: {:.3}ms and {} draw calls per frame over {} frames", name, milliseconds, draw_calls / frames, frames);
+    }
+    true
+}
+
+fn write_profile<R: game::Renderer, I: game::InputHandler>(engine: &game::Engine<R, I>, filename: &str) {
+    let csv = engine.profiler().csv().unwrap_or_default();
+    match fs::write(filename, csv) {
+        Ok(()) => println!("Wrote the frame profile to {:?}", filename),
+        Err(e) => println!("Error writing the frame profile to {:?}: {}", filename, e),
+    }
+}
+
+fn frame_sync_wait(frame_start: SystemTime, fps: u32) {
+    let delay = Duration::new(0, 1_000_000_000 / fps);
     let now = SystemTime::now();
     let duration = now.duration_since(frame_start).unwrap();
-    if duration.le(&DELAY) {
-        thread::sleep(*DELAY - duration)
+    if duration.le(&delay) {
+        thread::sleep(delay - duration)
     }
 }
@@ -0,0 +1,75 @@
+use game::Engine;
+use game::EngineState;
+use game::InputHandler;
+use game::InputState;
+use game::Renderer;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::net::UdpSocket;
+
+mod rollback;
+mod session;
+mod udp;
+
+// What the rollback session needs from the game; the engine in the real thing
+pub trait Simulation {
+    type State;
+    fn save_state(&self) -> Self::State;
+    fn load_state(&mut self, state: &Self::State);
+    fn step(&mut self, inputs: &[InputState]);
+}
+
+impl<R: Renderer, I: InputHandler> Simulation for Engine<R, I> {
+    type State = EngineState;
+
+    fn save_state(&self) -> EngineState {
+        Engine::save_state(self)
+    }
+
+    fn load_state(&mut self, state: &EngineState) {
+        Engine::load_state(self, state)
+    }
+
+    fn step(&mut self, inputs: &[InputState]) {
+        Engine::step(self, inputs)
+    }
+}
+
+// Plays every frame right away with a guess of the other player's inputs,
+// once their real inputs arrive and differ it goes back and plays the frames again
+pub struct Rollback<S: Simulation> {
+    local_player: usize,
+    // the next frame to be played
+    frame: u32,
+    // the first frame the other player's inputs have not arrived for
+    confirmed: u32,
+    // the first frame the other side has not acknowledged this side's inputs for
+    acknowledged: u32,
+    local_inputs: HashMap<u32, Vec<InputState>>,
+    remote_inputs: HashMap<u32, Vec<InputState>>,
+    // remote inputs that were guessed for frames played before they arrived
+    predictions: HashMap<u32, Vec<InputState>>,
+    // the state every unconfirmed frame started from
+    states: HashMap<u32, S::State>,
+    replay_from: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Packet {
+    // sent until the other side answers, the first player's seed is the one both games use
+    Hello { seed: u64 },
+    // local inputs from `first_frame` on, everything the other side has not acknowledged yet
+    Inputs { ack: u32, first_frame: u32, inputs: Vec<Vec<InputState>> },
+}
+
+pub struct UdpPeer {
+    socket: UdpSocket,
+    remote: SocketAddr,
+}
+
+// A networked co-op game: one player on each side of a UDP socket
+pub struct NetSession<S: Simulation> {
+    peer: UdpPeer,
+    rollback: Rollback<S>,
+    seed: u64,
+}
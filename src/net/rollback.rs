@@ -0,0 +1,257 @@
+use game::InputState;
+use net::Rollback;
+use net::Simulation;
+use std::collections::HashMap;
+
+// how far the game may run ahead of the other player's inputs before it waits for them
+const MAX_ROLLBACK_FRAMES: u32 = 8;
+
+impl<S: Simulation> Rollback<S> {
+    pub fn new(local_player: usize) -> Rollback<S> {
+        Rollback {
+            local_player,
+            frame: 0,
+            confirmed: 0,
+            acknowledged: 0,
+            local_inputs: HashMap::new(),
+            remote_inputs: HashMap::new(),
+            predictions: HashMap::new(),
+            states: HashMap::new(),
+            replay_from: None,
+        }
+    }
+
+    // Acknowledged to the other side, their inputs before it are not needed any more
+    pub fn confirmed(&self) -> u32 {
+        self.confirmed
+    }
+
+    // Keyboard inputs of this side's player, the first player's keys fly whichever plane is ours.
+    // Quitting and loading stay local, the rest has to be played on both sides.
+    pub fn claim(&self, inputs: &[InputState]) -> Vec<InputState> {
+        let player = self.local_player;
+        inputs
+            .iter()
+            .filter_map(|input| match *input {
+                InputState::Up(0) => Some(InputState::Up(player)),
+                InputState::Down(0) => Some(InputState::Down(player)),
+                InputState::Left(0) => Some(InputState::Left(player)),
+                InputState::Right(0) => Some(InputState::Right(player)),
+                InputState::Shoot(0) => Some(InputState::Shoot(player)),
+                InputState::Character(_) | InputState::Backspace | InputState::Confirm => Some(*input),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn add_remote_input(&mut self, frame: u32, inputs: Vec<InputState>) {
+        if frame < self.confirmed || self.remote_inputs.contains_key(&frame) {
+            return;
+        }
+        if let Some(predicted) = self.predictions.remove(&frame) {
+            if predicted != inputs {
+                self.replay_from = Some(self.replay_from.map_or(frame, |replay_from| replay_from.min(frame)));
+            }
+        }
+        self.remote_inputs.insert(frame, inputs);
+        while self.remote_inputs.contains_key(&self.confirmed) {
+            self.confirmed += 1;
+        }
+    }
+
+    // The first frame the other side is still missing this side's inputs for
+    pub fn acknowledged(&self) -> u32 {
+        self.acknowledged
+    }
+
+    // Local inputs the other side has not acknowledged yet, starting with the oldest
+    pub fn unacknowledged(&self) -> Vec<Vec<InputState>> {
+        (self.acknowledged..self.frame).map(|frame| self.local_inputs.get(&frame).cloned().unwrap_or_default()).collect()
+    }
+
+    // Acknowledgements arrive out of order, an older one changes nothing
+    pub fn acknowledge(&mut self, ack: u32) {
+        self.acknowledged = self.acknowledged.max(ack);
+    }
+
+    // Replays mispredicted frames, then plays the next one unless the other side is too far behind.
+    // Returns whether the game moved on.
+    pub fn advance(&mut self, simulation: &mut S, local: Vec<InputState>) -> bool {
+        if let Some(replay_from) = self.replay_from.take() {
+            simulation.load_state(&self.states[&replay_from]);
+            for frame in replay_from..self.frame {
+                self.play(simulation, frame);
+            }
+        }
+        self.prune();
+
+        if self.frame >= self.confirmed + MAX_ROLLBACK_FRAMES {
+            return false;
+        }
+        self.local_inputs.insert(self.frame, local);
+        let frame = self.frame;
+        self.play(simulation, frame);
+        self.frame += 1;
+        true
+    }
+
+    fn play(&mut self, simulation: &mut S, frame: u32) {
+        self.states.insert(frame, simulation.save_state());
+
+        let remote = match self.remote_inputs.get(&frame) {
+            Some(remote) => remote.clone(),
+            None => {
+                let predicted = self.predict();
+                self.predictions.insert(frame, predicted.clone());
+                predicted
+            }
+        };
+        let local = self.local_inputs.get(&frame).cloned().unwrap_or_default();
+
+        // both sides have to see the inputs in the same order
+        let inputs: Vec<InputState> = if self.local_player == 0 {
+            local.into_iter().chain(remote).collect()
+        } else {
+            remote.into_iter().chain(local).collect()
+        };
+        simulation.step(&inputs);
+    }
+
+    // The other player most likely still holds what they held last, a key press is not repeated
+    fn predict(&self) -> Vec<InputState> {
+        let last = match self.confirmed.checked_sub(1).and_then(|frame| self.remote_inputs.get(&frame)) {
+            Some(last) => last,
+            None => return Vec::new(),
+        };
+        last.iter()
+            .filter(|input| {
+                matches!(input, InputState::Up(_) | InputState::Down(_) | InputState::Left(_) | InputState::Right(_) | InputState::Shoot(_))
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Confirmed frames are never played again, the last confirmed inputs are kept for predicting.
+    // Local inputs go once they are not needed for replaying and the other side has them.
+    fn prune(&mut self) {
+        let confirmed = self.confirmed;
+        let needed = confirmed.min(self.acknowledged);
+        self.local_inputs.retain(|frame, _| *frame >= needed);
+        self.states.retain(|frame, _| *frame >= confirmed);
+        self.predictions.retain(|frame, _| *frame >= confirmed);
+        self.remote_inputs.retain(|frame, _| *frame + 1 >= confirmed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::InputState;
+    use net::Rollback;
+    use net::Simulation;
+
+    // two players on a line, one step to the right per frame they hold right
+    #[derive(Debug, PartialEq)]
+    struct MockSimulation {
+        positions: Vec<u32>,
+        steps: u32,
+    }
+
+    impl Simulation for MockSimulation {
+        type State = Vec<u32>;
+
+        fn save_state(&self) -> Vec<u32> {
+            self.positions.clone()
+        }
+
+        fn load_state(&mut self, state: &Vec<u32>) {
+            self.positions = state.clone();
+        }
+
+        fn step(&mut self, inputs: &[InputState]) {
+            self.steps += 1;
+            for input in inputs {
+                if let InputState::Right(player) = *input {
+                    self.positions[player] += 1;
+                }
+            }
+        }
+    }
+
+    fn simulation() -> MockSimulation {
+        MockSimulation { positions: vec![0, 0], steps: 0 }
+    }
+
+    #[test]
+    fn test_replaying_mispredicted_frames() {
+        //given
+        let mut rollback = Rollback::new(0);
+        let mut game = simulation();
+        rollback.add_remote_input(0, vec![InputState::Right(1)]);
+        for _ in 0..3 {
+            rollback.advance(&mut game, vec![InputState::Right(0)]);
+        }
+        let predicted = game.positions.clone();
+
+        //when
+        rollback.add_remote_input(1, vec![]);
+        rollback.add_remote_input(2, vec![]);
+        rollback.advance(&mut game, vec![]);
+
+        //then
+        assert_eq!(predicted, vec![3, 3]);
+        assert_eq!(game.positions, vec![3, 1]);
+        assert_eq!(game.steps, 3 + 2 + 1);
+        assert_eq!(rollback.confirmed(), 3);
+    }
+
+    #[test]
+    fn test_correct_prediction_is_not_replayed() {
+        //given
+        let mut rollback = Rollback::new(1);
+        let mut game = simulation();
+        rollback.add_remote_input(0, vec![InputState::Right(0)]);
+        rollback.advance(&mut game, vec![]);
+        rollback.advance(&mut game, vec![]);
+
+        //when
+        rollback.add_remote_input(1, vec![InputState::Right(0)]);
+        rollback.advance(&mut game, vec![]);
+
+        //then
+        assert_eq!(game.positions, vec![3, 0]);
+        assert_eq!(game.steps, 3);
+    }
+
+    #[test]
+    fn test_waiting_for_the_other_side() {
+        //given
+        let mut rollback = Rollback::new(0);
+        let mut game = simulation();
+
+        //when
+        let advanced: Vec<bool> = (0..10).map(|_| rollback.advance(&mut game, vec![])).collect();
+
+        //then
+        assert_eq!(advanced.iter().filter(|advanced| **advanced).count(), 8);
+        assert_eq!(rollback.frame, 8);
+    }
+
+    #[test]
+    fn test_claiming_keyboard_inputs() {
+        //given
+        let rollback: Rollback<MockSimulation> = Rollback::new(1);
+
+        //when
+        let claimed = rollback.claim(&[
+            InputState::Up(0),
+            InputState::Shoot(0),
+            InputState::Left(1),
+            InputState::Quit,
+            InputState::Load,
+            InputState::Confirm,
+        ]);
+
+        //then
+        assert_eq!(claimed, vec![InputState::Up(1), InputState::Shoot(1), InputState::Confirm]);
+    }
+}
@@ -0,0 +1,141 @@
+use game::InputState;
+use net::NetSession;
+use net::Packet;
+use net::Rollback;
+use net::Simulation;
+use net::UdpPeer;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+const HELLO_INTERVAL: u64 = 100;
+// the oldest unacknowledged frames go first, the rest follows once they got through
+const MAX_FRAMES_PER_PACKET: u32 = 64;
+
+impl<S: Simulation> NetSession<S> {
+    // Waits until the other side is up; the first player's seed is the one both games are played with
+    pub fn connect(peer: UdpPeer, local_player: usize, seed: u64, timeout: Duration) -> Result<NetSession<S>, String> {
+        let start = SystemTime::now();
+        loop {
+            peer.send(&Packet::Hello { seed });
+            for packet in peer.receive() {
+                if let Packet::Hello { seed: remote_seed } = packet {
+                    let seed = if local_player == 0 { seed } else { remote_seed };
+                    // answered once more, our earlier hellos may have been sent before the other side was listening
+                    peer.send(&Packet::Hello { seed });
+                    return Ok(NetSession { peer, rollback: Rollback::new(local_player), seed });
+                }
+            }
+            if start.elapsed().map(|elapsed| elapsed >= timeout).unwrap_or(true) {
+                return Err(String::from("The other player did not show up"));
+            }
+            thread::sleep(Duration::from_millis(HELLO_INTERVAL));
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Called once per frame with this side's keyboard inputs, returns whether the game moved on
+    pub fn tick(&mut self, simulation: &mut S, inputs: &[InputState]) -> bool {
+        let mut ack = None;
+        for packet in self.peer.receive() {
+            match packet {
+                Packet::Hello { .. } => self.peer.send(&Packet::Hello { seed: self.seed }),
+                Packet::Inputs { ack: remote_ack, first_frame, inputs } => {
+                    ack = ack.max(Some(remote_ack));
+                    for (frame, inputs) in (first_frame..).zip(inputs) {
+                        self.rollback.add_remote_input(frame, inputs);
+                    }
+                }
+            }
+        }
+        if let Some(ack) = ack {
+            self.rollback.acknowledge(ack);
+        }
+
+        let local = self.rollback.claim(inputs);
+        let advanced = self.rollback.advance(simulation, local);
+
+        let first_frame = self.rollback.acknowledged();
+        let mut inputs = self.rollback.unacknowledged();
+        inputs.truncate(MAX_FRAMES_PER_PACKET as usize);
+        self.peer.send(&Packet::Inputs { ack: self.rollback.confirmed(), first_frame, inputs });
+        advanced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::InputState;
+    use net::NetSession;
+    use net::Simulation;
+    use net::UdpPeer;
+    use std::net::UdpSocket;
+    use std::thread;
+    use std::time::Duration;
+
+    // two players on a line, one step to the right per frame they hold right
+    struct MockSimulation {
+        positions: Vec<u32>,
+    }
+
+    impl Simulation for MockSimulation {
+        type State = Vec<u32>;
+
+        fn save_state(&self) -> Vec<u32> {
+            self.positions.clone()
+        }
+
+        fn load_state(&mut self, state: &Vec<u32>) {
+            self.positions = state.clone();
+        }
+
+        fn step(&mut self, inputs: &[InputState]) {
+            for input in inputs {
+                if let InputState::Right(player) = *input {
+                    self.positions[player] += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_both_sides_end_up_in_the_same_state() {
+        //given
+        let first = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (first_address, second_address) = (first.local_addr().unwrap(), second.local_addr().unwrap());
+        let joining = thread::spawn(move || {
+            let peer = UdpPeer::new(second, first_address).unwrap();
+            NetSession::<MockSimulation>::connect(peer, 1, 2, Duration::from_secs(5)).unwrap()
+        });
+        let peer = UdpPeer::new(first, second_address).unwrap();
+        let mut host: NetSession<MockSimulation> = NetSession::connect(peer, 0, 1, Duration::from_secs(5)).unwrap();
+        let mut guest = joining.join().unwrap();
+        let mut host_game = MockSimulation { positions: vec![0, 0] };
+        let mut guest_game = MockSimulation { positions: vec![0, 0] };
+
+        //when
+        for _ in 0..1000 {
+            let pressed = |frame: u32, from: u32| if frame >= from && frame < 20 { vec![InputState::Right(0)] } else { vec![] };
+            let host_inputs = pressed(host.rollback.frame, 0);
+            let guest_inputs = pressed(guest.rollback.frame, 5);
+            host.tick(&mut host_game, &host_inputs);
+            guest.tick(&mut guest_game, &guest_inputs);
+            if host.rollback.confirmed() >= 30 && guest.rollback.confirmed() >= 30 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        host.tick(&mut host_game, &[]);
+        guest.tick(&mut guest_game, &[]);
+
+        //then
+        assert_eq!(host.seed(), 1);
+        assert_eq!(guest.seed(), 1);
+        assert_eq!(host_game.positions, vec![20, 15]);
+        assert_eq!(guest_game.positions, vec![20, 15]);
+    }
+}
@@ -0,0 +1,194 @@
+use game::InputState;
+use net::Packet;
+use net::UdpPeer;
+use std::io;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use std::net::UdpSocket;
+
+// more than enough for the inputs of a few seconds of frames
+const MAX_PACKET_SIZE: usize = 4096;
+
+impl UdpPeer {
+    pub fn connect(local: &str, remote: &str) -> io::Result<UdpPeer> {
+        let remote = remote
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown address: {:?}", remote)))?;
+        UdpPeer::new(UdpSocket::bind(local)?, remote)
+    }
+
+    pub fn new(socket: UdpSocket, remote: SocketAddr) -> io::Result<UdpPeer> {
+        socket.set_nonblocking(true)?;
+        Ok(UdpPeer { socket, remote })
+    }
+
+    // Packets get lost, whatever matters is sent again until the other side acknowledges it
+    pub fn send(&self, packet: &Packet) {
+        if let Err(e) = self.socket.send_to(packet.encode().as_bytes(), self.remote) {
+            println!("Error sending to {}: {}", self.remote, e);
+        }
+    }
+
+    // Everything that arrived since the last call, never waits
+    pub fn receive(&self) -> Vec<Packet> {
+        let mut packets = Vec::new();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((size, from)) if from == self.remote => match Packet::decode(&String::from_utf8_lossy(&buffer[..size])) {
+                    Ok(packet) => packets.push(packet),
+                    Err(e) => println!("{}", e),
+                },
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                // an unreachable peer shows up as an error on the next receive, they may still come up
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+                Err(e) => {
+                    println!("Error receiving from {}: {}", self.remote, e);
+                    break;
+                }
+            }
+        }
+        packets
+    }
+}
+
+// One line of text: "HELLO <seed>" or "INPUTS <ack> <first frame> <frame count> <frame>;<frame>;..."
+// where every frame is a comma separated list of inputs
+impl Packet {
+    pub fn encode(&self) -> String {
+        match self {
+            Packet::Hello { seed } => format!("HELLO {}", seed),
+            Packet::Inputs { ack, first_frame, inputs } => {
+                let frames: Vec<String> = inputs
+                    .iter()
+                    .map(|frame| frame.iter().filter_map(encode_input).collect::<Vec<_>>().join(","))
+                    .collect();
+                format!("INPUTS {} {} {} {}", ack, first_frame, frames.len(), frames.join(";"))
+            }
+        }
+    }
+
+    pub fn decode(text: &str) -> Result<Packet, String> {
+        let error = || format!("Incorrect packet: {:?}", text);
+        let mut parts = text.splitn(5, ' ');
+        match parts.next() {
+            Some("HELLO") => {
+                let seed = parts.next().and_then(|seed| seed.parse().ok()).ok_or_else(error)?;
+                Ok(Packet::Hello { seed })
+            }
+            Some("INPUTS") => {
+                let ack = parts.next().and_then(|ack| ack.parse().ok()).ok_or_else(error)?;
+                let first_frame = parts.next().and_then(|frame| frame.parse().ok()).ok_or_else(error)?;
+                let count: usize = parts.next().and_then(|count| count.parse().ok()).ok_or_else(error)?;
+                let inputs = match parts.next() {
+                    Some(frames) if count > 0 => frames
+                        .split(';')
+                        .map(|frame| frame.split(',').filter(|input| !input.is_empty()).map(decode_input).collect())
+                        .collect::<Result<Vec<Vec<InputState>>, String>>()?,
+                    _ => Vec::new(),
+                };
+                if inputs.len() != count {
+                    return Err(error());
+                }
+                Ok(Packet::Inputs { ack, first_frame, inputs })
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+fn encode_input(input: &InputState) -> Option<String> {
+    match *input {
+        InputState::Up(player) => Some(format!("U{}", player)),
+        InputState::Down(player) => Some(format!("D{}", player)),
+        InputState::Left(player) => Some(format!("L{}", player)),
+        InputState::Right(player) => Some(format!("R{}", player)),
+        InputState::Shoot(player) => Some(format!("S{}", player)),
+        InputState::Character(character) if character.is_ascii_alphanumeric() => Some(format!("K{}", character)),
+        InputState::Backspace => Some(String::from("B")),
+        InputState::Confirm => Some(String::from("C")),
+        _ => None,
+    }
+}
+
+fn decode_input(text: &str) -> Result<InputState, String> {
+    let mut chars = text.chars();
+    let kind = chars.next();
+    let rest = chars.as_str();
+    let player = || rest.parse().map_err(|_| format!("Incorrect input: {:?}", text));
+    match kind {
+        Some('U') => Ok(InputState::Up(player()?)),
+        Some('D') => Ok(InputState::Down(player()?)),
+        Some('L') => Ok(InputState::Left(player()?)),
+        Some('R') => Ok(InputState::Right(player()?)),
+        Some('S') => Ok(InputState::Shoot(player()?)),
+        Some('K') if rest.chars().count() == 1 => Ok(InputState::Character(rest.chars().next().unwrap())),
+        Some('B') if rest.is_empty() => Ok(InputState::Backspace),
+        Some('C') if rest.is_empty() => Ok(InputState::Confirm),
+        _ => Err(format!("Incorrect input: {:?}", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::InputState;
+    use net::Packet;
+    use net::UdpPeer;
+    use std::net::UdpSocket;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_encoding_and_decoding() {
+        //given
+        let packets = vec![
+            Packet::Hello { seed: 24301 },
+            Packet::Inputs {
+                ack: 12,
+                first_frame: 10,
+                inputs: vec![
+                    vec![InputState::Up(1), InputState::Shoot(1)],
+                    vec![],
+                    vec![InputState::Character('a'), InputState::Backspace, InputState::Confirm],
+                ],
+            },
+        ];
+
+        //when
+        let decoded: Vec<Result<Packet, String>> = packets.iter().map(|packet| Packet::decode(&packet.encode())).collect();
+
+        //then
+        assert_eq!(decoded, packets.into_iter().map(Ok).collect::<Vec<_>>());
+        assert_eq!(Packet::decode("INPUTS 3 3 0 "), Ok(Packet::Inputs { ack: 3, first_frame: 3, inputs: vec![] }));
+        assert!(Packet::decode("INPUTS 1 0 1 X9").is_err());
+        assert!(Packet::decode("INPUTS 1 0 2 U0").is_err());
+        assert!(Packet::decode("PING").is_err());
+    }
+
+    #[test]
+    fn test_exchanging_packets_on_loopback() {
+        //given
+        let first = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (first_address, second_address) = (first.local_addr().unwrap(), second.local_addr().unwrap());
+        let first = UdpPeer::new(first, second_address).unwrap();
+        let second = UdpPeer::new(second, first_address).unwrap();
+
+        //when
+        first.send(&Packet::Hello { seed: 7 });
+        let mut received = Vec::new();
+        for _ in 0..100 {
+            received.extend(second.receive());
+            if !received.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        //then
+        assert_eq!(received, vec![Packet::Hello { seed: 7 }]);
+        assert!(first.receive().is_empty());
+    }
+}
@@ -0,0 +1,38 @@
+use sdl::FrameCapture;
+use std::path::PathBuf;
+
+impl FrameCapture {
+    pub fn new(directory: &str, every: Option<u32>, at: Option<u32>) -> FrameCapture {
+        FrameCapture { directory: PathBuf::from(directory), every, at }
+    }
+
+    // Capturing every 0th frame captures none
+    pub fn is_wanted(&self, frame: u32) -> bool {
+        self.every.and_then(|every| frame.checked_rem(every)) == Some(0) || self.at == Some(frame)
+    }
+
+    // Numbered so that the files sort in the order they were drawn in
+    pub fn path(&self, frame: u32) -> PathBuf {
+        self.directory.join(format!("frame-{:06}.png", frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl::FrameCapture;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_capturing_every_nth_frame() {
+        //given
+        let capture = FrameCapture::new("frames", Some(30), Some(45));
+
+        //when
+        let wanted: Vec<u32> = (0..100).filter(|frame| capture.is_wanted(*frame)).collect();
+
+        //then
+        assert_eq!(wanted, vec![0, 30, 45, 60, 90]);
+        assert_eq!(capture.path(45), PathBuf::from("frames/frame-000045.png"));
+        assert!(!FrameCapture::new("frames", Some(0), None).is_wanted(0));
+    }
+}
@@ -0,0 +1,64 @@
+use helpers::parsers::map_file;
+use helpers::parsers::map_file::MapData;
+use sdl::resource_manager::ResourceLoader;
+#[cfg(feature = "audio")]
+use sdl::sdl2::mixer::Chunk;
+#[cfg(feature = "audio")]
+use sdl::sdl2::mixer::Music;
+#[cfg(feature = "ttf")]
+use sdl::sdl2::ttf::Font;
+#[cfg(feature = "ttf")]
+use sdl::sdl2::ttf::Sdl2TtfContext;
+use std::path::Path;
+
+// Parsed TMX maps are kept around so a level can be restarted without reading the file again
+pub struct MapLoader;
+
+#[cfg(feature = "ttf")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct FontDetails {
+    pub path: String,
+    pub size: u16,
+}
+
+// Loads both the sound chunks and the music SDL_mixer plays
+#[cfg(feature = "audio")]
+pub struct AudioLoader;
+
+impl<'l> ResourceLoader<'l, MapData> for MapLoader {
+    type Args = str;
+    fn load(&'l self, path: &str) -> Result<MapData, String> {
+        println!("Loading a map from path: {:?}", path);
+        if !Path::new(path).is_file() {
+            return Err(format!("Missing map file: {:?}", path));
+        }
+        Ok(map_file::parse(path))
+    }
+}
+
+#[cfg(feature = "ttf")]
+impl<'l> ResourceLoader<'l, Font<'l, 'static>> for Sdl2TtfContext {
+    type Args = FontDetails;
+    fn load(&'l self, details: &FontDetails) -> Result<Font<'l, 'static>, String> {
+        println!("Loading a font from path: {:?}", details.path);
+        self.load_font(&details.path, details.size)
+    }
+}
+
+#[cfg(feature = "audio")]
+impl<'l> ResourceLoader<'l, Chunk> for AudioLoader {
+    type Args = str;
+    fn load(&'l self, path: &str) -> Result<Chunk, String> {
+        println!("Loading a sound from path: {:?}", path);
+        Chunk::from_file(path)
+    }
+}
+
+#[cfg(feature = "audio")]
+impl<'l> ResourceLoader<'l, Music<'static>> for AudioLoader {
+    type Args = str;
+    fn load(&'l self, path: &str) -> Result<Music<'static>, String> {
+        println!("Loading music from path: {:?}", path);
+        Music::from_file(path)
+    }
+}
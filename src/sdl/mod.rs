@@ -1,47 +1,112 @@
 extern crate sdl2;
 
+use self::sdl2::rect::Rect;
 use self::sdl2::render::Canvas;
+use self::sdl2::render::RenderTarget;
 use self::sdl2::render::Texture;
 use self::sdl2::render::TextureCreator;
 use self::sdl2::video::Window;
-use self::sdl2::video::WindowContext;
 use self::sdl2::EventPump;
 use self::sdl2::Sdl;
+#[cfg(feature = "audio")]
+use self::sdl2::mixer::Chunk;
+#[cfg(feature = "audio")]
+use self::sdl2::mixer::Music;
+#[cfg(feature = "ttf")]
+use self::sdl2::ttf::Font;
+#[cfg(feature = "ttf")]
+use self::sdl2::ttf::Sdl2TtfContext;
+use game::Animation;
+use helpers::parsers::map_file::MapData;
 use sdl::resource_manager::ResourceManager;
 use sdl::sdl2::image::init as sdl2_image_init;
 use sdl::sdl2::image::{INIT_JPG, INIT_PNG};
 use sdl::sdl2::init as sdl2_init;
-use sdl::sdl2::TimerSubsystem;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
+mod capture;
+mod loaders;
 mod resource_manager;
 mod sdl_input_handler;
 mod sdl_video;
+// only the golden image tests load and compare snapshots so far, the game just saves them
+#[allow(dead_code)]
+mod snapshot;
+
+#[cfg(feature = "audio")]
+pub use sdl::loaders::AudioLoader;
+#[cfg(feature = "ttf")]
+pub use sdl::loaders::FontDetails;
+pub use sdl::loaders::MapLoader;
 
 pub type TextureManager<'l, T> = ResourceManager<'l, String, Texture<'l>, TextureCreator<T>>;
+pub type MapManager<'l> = ResourceManager<'l, String, MapData, MapLoader>;
+#[cfg(feature = "ttf")]
+pub type FontManager<'l> = ResourceManager<'l, String, Font<'l, 'static>, Sdl2TtfContext>;
+// font ID, text and color of a rendered string
+#[cfg(feature = "ttf")]
+type TextKey = (String, String, (u8, u8, u8));
+#[cfg(feature = "audio")]
+pub type SoundManager<'l> = ResourceManager<'l, String, Chunk, AudioLoader>;
+#[cfg(feature = "audio")]
+pub type MusicManager<'l> = ResourceManager<'l, String, Music<'static>, AudioLoader>;
 
 pub struct SDLEngine {
     pub context: Sdl
 }
 
-pub struct SDLRenderer<'a> {
-    canvas: Canvas<Window>,
-    texture_manager: TextureManager<'a, WindowContext>,
+// Draws into a window, or into a software surface where there is no display
+pub struct SDLRenderer<'a, T: RenderTarget = Window> {
+    canvas: Canvas<T>,
+    texture_manager: TextureManager<'a, T::Context>,
     texture_wrappers: HashMap<String, TextureWrapper>,
-    timer: TimerSubsystem
+    animations: HashMap<String, Animation>,
+    #[cfg(feature = "ttf")]
+    fonts: Option<(FontManager<'a>, String)>,
+    // rendered strings by font, text and color, and whether they were drawn since the last frame was presented
+    #[cfg(feature = "ttf")]
+    texts: HashMap<TextKey, (bool, Texture<'a>)>,
+    // saved to this file right before the next frame is presented
+    capture: Option<PathBuf>,
+    // tilesets drawn without blending, only for baking chunks
+    tilesets: HashMap<String, Texture<'a>>,
+    // baked chunks of the current level's tile layers with the revision they were baked at
+    chunks: HashMap<(usize, (u32, u32)), (u32, Texture<'a>)>,
+}
+
+// What the renderer drew, tightly packed RGB rows
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+// Which frames of a run end up as numbered PNGs in `directory`
+#[derive(Debug, PartialEq, Clone)]
+pub struct FrameCapture {
+    directory: PathBuf,
+    // every n-th frame, for a sequence of them
+    every: Option<u32>,
+    // a single frame asked for up front
+    at: Option<u32>,
 }
 
 pub struct SDLInputHandler {
     event_pump: EventPump,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub struct TextureWrapper {
     texture_id: String,
     width: u32,
     height: u32,
     padding: u8,
     frames: u8,
+    columns: u8,
+    origin: (i32, i32),
+    regions: Vec<Rect>,
 }
 
 impl SDLEngine {
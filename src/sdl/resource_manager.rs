@@ -1,69 +1,343 @@
 use sdl::sdl2::image::LoadTexture;
+use sdl::sdl2::pixels::PixelFormatEnum;
 use sdl::sdl2::render::Texture;
 use sdl::sdl2::render::TextureCreator;
 use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
 use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
 use std::hash::Hash;
 use std::rc::Rc;
 
+const PLACEHOLDER_SIZE: u32 = 32;
+
 pub struct ResourceManager<'l, K, R, L>
 where
     K: Hash + Eq,
     L: 'l + ResourceLoader<'l, R>,
+    L::Args: ToOwned,
 {
     loader: &'l L,
     cache: HashMap<K, Rc<R>>,
+    sources: HashMap<K, <L::Args as ToOwned>::Owned>,
+    groups: HashMap<K, String>,
+    // keys that failed to load, they resolve to the placeholder without it being cached under them
+    failed: HashSet<K>,
+    placeholder: Option<Rc<R>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ResourceError {
+    Unknown(String),
+    LoadFailed(String, String),
 }
 
 impl<'l, K, R, L> ResourceManager<'l, K, R, L>
 where
-    K: Hash + Eq,
+    K: Hash + Eq + Clone,
     L: ResourceLoader<'l, R>,
+    L::Args: ToOwned,
 {
     pub fn new(loader: &'l L) -> Self {
-        let cache = HashMap::new();
-        ResourceManager { cache, loader }
+        ResourceManager {
+            loader,
+            cache: HashMap::new(),
+            sources: HashMap::new(),
+            groups: HashMap::new(),
+            failed: HashSet::new(),
+            placeholder: None,
+        }
+    }
+
+    pub fn loader(&self) -> &'l L {
+        self.loader
+    }
+
+    // Remembers where a resource comes from; it is loaded on first use
+    pub fn register<D>(&mut self, details: &D, source: &L::Args, group: &str)
+    where
+        D: Eq + Hash + ?Sized,
+        K: Borrow<D> + for<'a> From<&'a D>,
+    {
+        self.cache.remove(details);
+        self.failed.remove(details);
+        self.sources.insert(details.into(), source.to_owned());
+        self.groups.insert(details.into(), group.to_string());
     }
 
-    pub fn preload<D>(&mut self, details: &D, filename: &D) -> Result<(), String>
+    // Resources created in code have no source to reload from, so they stay until their group is unloaded
+    pub fn insert<D>(&mut self, details: &D, resource: R, group: &str)
     where
-        L: ResourceLoader<'l, R, Args = D>,
         D: Eq + Hash + ?Sized,
         K: Borrow<D> + for<'a> From<&'a D>,
     {
-        let texture = self.loader.load(filename)?;
-        self.cache.insert(details.into(), Rc::new(texture));
-        Ok(())
+        self.sources.remove(details);
+        self.failed.remove(details);
+        self.cache.insert(details.into(), Rc::new(resource));
+        self.groups.insert(details.into(), group.to_string());
     }
 
     // Generics magic to allow a HashMap to use String as a key
     // while allowing it to use &str for gets
-    pub fn load<D>(&mut self, details: &D) -> Result<Rc<R>, String>
+    pub fn load<D>(&mut self, details: &D) -> Result<Rc<R>, ResourceError>
     where
-        L: ResourceLoader<'l, R, Args = D>,
         D: Eq + Hash + ?Sized + Debug,
         K: Borrow<D> + for<'a> From<&'a D>,
     {
+        if let Some(resource) = self.cache.get(details) {
+            return Ok(resource.clone());
+        }
+
+        let resource = {
+            let source = self
+                .sources
+                .get(details)
+                .ok_or_else(|| ResourceError::Unknown(format!("{:?}", details)))?;
+            self.loader
+                .load(source.borrow())
+                .map_err(|e| ResourceError::LoadFailed(format!("{:?}", details), e))?
+        };
+
+        let resource = Rc::new(resource);
+        self.cache.insert(details.into(), resource.clone());
+        Ok(resource)
+    }
+
+    // Failed lookups are reported once, afterwards the key resolves straight to the placeholder
+    // until it is registered again or its group is unloaded
+    pub fn load_or_placeholder<D>(&mut self, details: &D) -> Rc<R>
+    where
+        D: Eq + Hash + ?Sized + Debug,
+        K: Borrow<D> + for<'a> From<&'a D>,
+    {
+        if self.failed.contains(details) {
+            return self.placeholder();
+        }
+        match self.load(details) {
+            Ok(resource) => resource,
+            Err(e) => {
+                println!("Using placeholder: {}", e);
+                self.failed.insert(details.into());
+                self.placeholder()
+            }
+        }
+    }
+
+    // Drops cached resources of a group; anything still holding an Rc keeps its copy alive
+    pub fn unload_group(&mut self, group: &str) -> usize {
+        let keys: Vec<K> = self
+            .groups
+            .iter()
+            .filter(|(_, resource_group)| resource_group.as_str() == group)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in &keys {
+            self.failed.remove(key);
+        }
+        keys.iter().filter(|key| self.cache.remove(*key).is_some()).count()
+    }
+
+    // Bytes used by the cached resources and the placeholder while it stands in for any, shared ones counted once
+    pub fn memory_usage(&self) -> usize {
+        let mut seen = HashSet::new();
+        let placeholder = self.placeholder.iter().filter(|_| !self.failed.is_empty());
         self.cache
-            .get(details)
-            .cloned()
-            .map(Ok)
-            .unwrap_or_else(|| panic!("Invalid texture ID: {:?}", details))
+            .values()
+            .chain(placeholder)
+            .filter(|resource| seen.insert(Rc::as_ptr(resource)))
+            .map(|resource| self.loader.size_of(resource))
+            .sum()
+    }
+
+    fn placeholder(&mut self) -> Rc<R> {
+        if self.placeholder.is_none() {
+            let placeholder = self.loader.placeholder().expect("Error creating placeholder");
+            self.placeholder = Some(Rc::new(placeholder));
+        }
+        self.placeholder.clone().unwrap()
+    }
+}
+
+impl Display for ResourceError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ResourceError::Unknown(key) => write!(f, "Unknown resource: {}", key),
+            ResourceError::LoadFailed(key, e) => write!(f, "Error loading resource {}: {}", key, e),
+        }
     }
 }
 
 // TextureCreator knows how to load Textures
 impl<'l, T> ResourceLoader<'l, Texture<'l>> for TextureCreator<T> {
     type Args = str;
-    fn load(&'l self, path: &str) -> Result<Texture, String> {
+    fn load(&'l self, path: &str) -> Result<Texture<'l>, String> {
         println!("Loading a texture from path: {:?}", path);
         self.load_texture(path)
     }
+
+    // Magenta and black checkerboard, impossible to miss on screen
+    fn placeholder(&'l self) -> Result<Texture<'l>, String> {
+        let mut texture = self
+            .create_texture_static(PixelFormatEnum::RGB24, PLACEHOLDER_SIZE, PLACEHOLDER_SIZE)
+            .map_err(|e| e.to_string())?;
+
+        let size = PLACEHOLDER_SIZE as usize;
+        let mut pixels = vec![0; size * size * 3];
+        for (i, pixel) in pixels.chunks_mut(3).enumerate() {
+            let (x, y) = (i % size, i / size);
+            if (x / 8 + y / 8) & 1 == 0 {
+                pixel.copy_from_slice(&[255, 0, 255]);
+            }
+        }
+        texture.update(None, &pixels, size * 3).map_err(|e| e.to_string())?;
+        Ok(texture)
+    }
+
+    fn size_of(&self, texture: &Texture<'l>) -> usize {
+        let query = texture.query();
+        query.width as usize * query.height as usize * query.format.byte_size_per_pixel()
+    }
 }
 
 // Generic trait to Load any Resource Kind
 pub trait ResourceLoader<'l, R> {
     type Args: ?Sized;
     fn load(&'l self, data: &Self::Args) -> Result<R, String>;
+
+    fn placeholder(&'l self) -> Result<R, String> {
+        Err(String::from("No placeholder available"))
+    }
+
+    fn size_of(&self, _resource: &R) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl::resource_manager::ResourceError;
+    use sdl::resource_manager::ResourceLoader;
+    use sdl::resource_manager::ResourceManager;
+    use std::cell::Cell;
+
+    struct MockLoader {
+        loads: Cell<u32>,
+    }
+
+    impl<'l> ResourceLoader<'l, String> for MockLoader {
+        type Args = str;
+        fn load(&'l self, path: &str) -> Result<String, String> {
+            self.loads.set(self.loads.get() + 1);
+            match path {
+                "broken" => Err(String::from("corrupted file")),
+                _ => Ok(format!("loaded {}", path)),
+            }
+        }
+
+        fn placeholder(&'l self) -> Result<String, String> {
+            Ok(String::from("placeholder"))
+        }
+
+        fn size_of(&self, resource: &String) -> usize {
+            resource.len()
+        }
+    }
+
+    #[test]
+    fn test_lazy_loading() {
+        //given
+        let loader = MockLoader { loads: Cell::new(0) };
+        let mut manager: ResourceManager<String, String, MockLoader> = ResourceManager::new(&loader);
+        manager.register("plane", "plane.png", "game");
+
+        //when
+        let first = manager.load("plane").unwrap();
+        let second = manager.load("plane").unwrap();
+
+        //then
+        assert_eq!(*first, "loaded plane.png");
+        assert_eq!(*second, "loaded plane.png");
+        assert_eq!(loader.loads.get(), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        //given
+        let loader = MockLoader { loads: Cell::new(0) };
+        let mut manager: ResourceManager<String, String, MockLoader> = ResourceManager::new(&loader);
+        manager.register("broken", "broken", "game");
+
+        //when
+        let unknown = manager.load("missing");
+        let broken = manager.load("broken");
+
+        //then
+        assert_eq!(unknown, Err(ResourceError::Unknown(String::from("\"missing\""))));
+        assert_eq!(
+            broken,
+            Err(ResourceError::LoadFailed(String::from("\"broken\""), String::from("corrupted file")))
+        );
+    }
+
+    #[test]
+    fn test_placeholder() {
+        //given
+        let loader = MockLoader { loads: Cell::new(0) };
+        let mut manager: ResourceManager<String, String, MockLoader> = ResourceManager::new(&loader);
+
+        //when
+        let first = manager.load_or_placeholder("missing");
+        let second = manager.load_or_placeholder("also missing");
+
+        //then
+        assert_eq!(*first, "placeholder");
+        assert_eq!(*second, "placeholder");
+        assert_eq!(manager.memory_usage(), "placeholder".len());
+    }
+
+    #[test]
+    fn test_unloading_group() {
+        //given
+        let loader = MockLoader { loads: Cell::new(0) };
+        let mut manager: ResourceManager<String, String, MockLoader> = ResourceManager::new(&loader);
+        manager.register("plane", "plane.png", "game");
+        manager.register("tiles", "tiles.png", "level");
+        manager.load("plane").unwrap();
+        let tiles = manager.load("tiles").unwrap();
+
+        //when
+        let unloaded = manager.unload_group("level");
+
+        //then
+        assert_eq!(unloaded, 1);
+        assert_eq!(*tiles, "loaded tiles.png");
+        assert_eq!(manager.memory_usage(), "loaded plane.png".len());
+
+        manager.load("tiles").unwrap();
+        assert_eq!(loader.loads.get(), 3);
+    }
+
+    #[test]
+    fn test_unloading_group_drops_placeholder() {
+        //given
+        let loader = MockLoader { loads: Cell::new(0) };
+        let mut manager: ResourceManager<String, String, MockLoader> = ResourceManager::new(&loader);
+        manager.register("tiles", "broken", "level");
+        manager.load_or_placeholder("tiles");
+        manager.load_or_placeholder("tiles");
+
+        //when
+        manager.unload_group("level");
+
+        //then
+        assert_eq!(loader.loads.get(), 1);
+        assert_eq!(manager.memory_usage(), 0);
+
+        manager.load_or_placeholder("tiles");
+        assert_eq!(loader.loads.get(), 2);
+    }
 }
@@ -1,37 +1,61 @@
-use super::sdl2::keyboard::KeyboardState;
 use super::sdl2::keyboard::Scancode;
 use game::InputHandler;
 use game::InputState;
 use sdl::sdl2::event::Event;
+use sdl::sdl2::keyboard::Keycode;
 use sdl::SDLEngine;
 use sdl::SDLInputHandler;
 
+struct KeySet {
+    up: Scancode,
+    down: Scancode,
+    left: Scancode,
+    right: Scancode,
+    shoot: Scancode,
+}
+
+// One set of keys per player sharing the keyboard: arrows and space, then WASD and left ctrl
+const KEY_SETS: [KeySet; 2] = [
+    KeySet { up: Scancode::Up, down: Scancode::Down, left: Scancode::Left, right: Scancode::Right, shoot: Scancode::Space },
+    KeySet { up: Scancode::W, down: Scancode::S, left: Scancode::A, right: Scancode::D, shoot: Scancode::LCtrl },
+];
+
 impl InputHandler for SDLInputHandler {
     fn capture(&mut self) -> Vec<InputState> {
         let mut input = Vec::new();
 
         for event in self.event_pump.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return vec![InputState::Quit];
-            };
+            match event {
+                Event::Quit { .. } => return vec![InputState::Quit],
+                Event::TextInput { text, .. } => input.extend(text.chars().map(InputState::Character)),
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => input.push(InputState::Backspace),
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } => input.push(InputState::Confirm),
+                Event::KeyDown { keycode: Some(Keycode::Backquote), .. } => input.push(InputState::ToggleConsole),
+                Event::KeyDown { keycode: Some(Keycode::F3), .. } => input.push(InputState::ToggleDebugOverlay),
+                Event::KeyDown { keycode: Some(Keycode::F9), .. } => input.push(InputState::Load),
+                Event::KeyDown { keycode: Some(Keycode::F12), .. } => input.push(InputState::Screenshot),
+                _ => {}
+            }
         }
 
         let state = self.event_pump.keyboard_state();
 
-        if SDLInputHandler::is_pressed(&state, &[Scancode::Up, Scancode::W]) {
-            input.push(InputState::Up)
-        }
-        if SDLInputHandler::is_pressed(&state, &[Scancode::Down, Scancode::S]) {
-            input.push(InputState::Down)
-        }
-        if SDLInputHandler::is_pressed(&state, &[Scancode::Left, Scancode::A]) {
-            input.push(InputState::Left)
-        }
-        if SDLInputHandler::is_pressed(&state, &[Scancode::Right, Scancode::D]) {
-            input.push(InputState::Right)
-        }
-        if SDLInputHandler::is_pressed(&state, &[Scancode::Space]) {
-            input.push(InputState::Shoot)
+        for (index, keys) in KEY_SETS.iter().enumerate() {
+            if state.is_scancode_pressed(keys.up) {
+                input.push(InputState::Up(index))
+            }
+            if state.is_scancode_pressed(keys.down) {
+                input.push(InputState::Down(index))
+            }
+            if state.is_scancode_pressed(keys.left) {
+                input.push(InputState::Left(index))
+            }
+            if state.is_scancode_pressed(keys.right) {
+                input.push(InputState::Right(index))
+            }
+            if state.is_scancode_pressed(keys.shoot) {
+                input.push(InputState::Shoot(index))
+            }
         }
         input
     }
@@ -42,13 +66,4 @@ impl SDLInputHandler {
         let event_pump = sdl.context.event_pump().unwrap();
         Self { event_pump }
     }
-
-    fn is_pressed(state: &KeyboardState, scancodes: &[Scancode]) -> bool {
-        for scancode in scancodes {
-            if state.is_scancode_pressed(*scancode) {
-                return true;
-            }
-        }
-        false
-    }
 }
@@ -1,41 +1,95 @@
+use game::Animation;
+use game::AnimationFrame;
+use game::AnimationState;
 use game::Level;
+use game::LevelLayer;
+use game::level;
+use game::level::CHUNK_SIZE;
+use game::PlaybackMode;
 use game::Position;
 use game::Renderer;
+use helpers::assets::AssetKind;
+use helpers::assets::AssetRegistry;
+use helpers::atlas;
+use helpers::atlas::AtlasDefinition;
 use helpers::parsers;
 use SCREEN_SIZE;
+use sdl::sdl2::image::LoadSurface;
+use sdl::sdl2::image::LoadTexture;
 use sdl::sdl2::pixels::Color;
+use sdl::sdl2::pixels::PixelFormatEnum;
+use sdl::sdl2::rect::Point;
 use sdl::sdl2::rect::Rect;
+use sdl::sdl2::render::BlendMode;
 use sdl::sdl2::render::Canvas;
+use sdl::sdl2::render::RenderTarget;
+use sdl::sdl2::render::Texture;
 use sdl::sdl2::render::TextureCreator;
-use sdl::sdl2::TimerSubsystem;
+use sdl::sdl2::surface::Surface;
+use sdl::sdl2::surface::SurfaceContext;
 use sdl::sdl2::video::Window;
 use sdl::sdl2::video::WindowContext;
 use sdl::SDLEngine;
 use sdl::SDLRenderer;
+use sdl::Snapshot;
+#[cfg(feature = "ttf")]
+use sdl::FontManager;
 use sdl::TextureManager;
 use sdl::TextureWrapper;
 use std::collections::HashMap;
+use std::fs;
+#[cfg(feature = "ttf")]
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
 
-impl<'a> Renderer for SDLRenderer<'a> {
+const GAME_TEXTURES: &str = "game";
+const LEVEL_TEXTURES: &str = "level";
+
+// bitmap font sheet, one glyph per frame starting at the space character
+const FONT_TEXTURE: &str = "font";
+const FIRST_GLYPH: u32 = 32;
+#[cfg(feature = "ttf")]
+const TEXT_COLOR: (u8, u8, u8) = (255, 255, 255);
+
+// The game moves things by fractions of a pixel, they only snap to whole ones when drawn
+fn pixel(coordinate: f32) -> i32 {
+    coordinate.round() as i32
+}
+
+impl<'a, T: RenderTarget> Renderer for SDLRenderer<'a, T> {
     fn clear_scene(&mut self) {
         self.canvas.clear();
     }
 
     fn draw_scene(&mut self) {
+        // read back before presenting, afterwards the canvas may hold anything
+        if let Some(path) = self.capture.take() {
+            match self.save_snapshot(&path) {
+                Ok(()) => println!("Saved the frame to {:?}", path),
+                Err(e) => println!("Error saving the frame to {:?}: {}", path, e),
+            }
+        }
         self.canvas.present();
+        // text that went unused for a frame has changed or is gone
+        #[cfg(feature = "ttf")]
+        self.texts.retain(|_, (is_drawn, _)| mem::replace(is_drawn, false));
     }
 
     fn draw_texture(&mut self, texture_id: &str, position: Position, level: &Level) {
-        let texture_wrapper = self.texture_wrappers.get(texture_id).expect("Missing texture wrapper");
-        let texture = self.texture_manager.load(texture_id).expect("Error loading texture");
+        let position_on_screen = position - level.position;
 
-        let src_rect = texture_wrapper.src_rect(0);
+        let texture_wrapper = match self.texture_wrappers.get(texture_id) {
+            Some(texture_wrapper) => texture_wrapper,
+            None => return self.draw_placeholder(texture_id, position_on_screen),
+        };
+        let texture = self.texture_manager.load_or_placeholder(texture_wrapper.texture_id.as_str());
 
-        let position_on_screen = position - level.position;
+        let src_rect = texture_wrapper.src_rect(0);
 
         let dst_rect = Rect::new(
-            position_on_screen.x as i32,
-            position_on_screen.y as i32,
+            pixel(position_on_screen.x),
+            pixel(position_on_screen.y),
             texture_wrapper.width,
             texture_wrapper.height,
         );
@@ -46,14 +100,17 @@ impl<'a> Renderer for SDLRenderer<'a> {
     }
 
     fn draw_tile(&mut self, texture_id: &str, position: Position, tile_id: u8) {
-        let texture_wrapper = self.texture_wrappers.get(texture_id).expect("Missing texture wrapper");
-        let texture = self.texture_manager.load(texture_id).expect("Error loading texture");
+        let texture_wrapper = match self.texture_wrappers.get(texture_id) {
+            Some(texture_wrapper) => texture_wrapper,
+            None => return self.draw_placeholder(texture_id, position),
+        };
+        let texture = self.texture_manager.load_or_placeholder(texture_wrapper.texture_id.as_str());
 
         let src_rect = texture_wrapper.src_rect(tile_id as u32);
 
         let dst_rect = Rect::new(
-            position.x as i32,
-            position.y as i32,
+            pixel(position.x),
+            pixel(position.y),
             texture_wrapper.width,
             texture_wrapper.height,
         );
@@ -63,33 +120,126 @@ impl<'a> Renderer for SDLRenderer<'a> {
             .expect("Problem copying texture");
     }
 
-    fn draw_frame(&mut self, texture_id: &str, position: Position, level: &Level) {
-        let ticks = self.timer.ticks();
-
-        let texture_wrapper = self.texture_wrappers.get(texture_id).expect("Missing texture wrapper");
-        let texture = self.texture_manager.load(texture_id).expect("Error loading texture");
-        let frame = (ticks / 100) % u32::from(texture_wrapper.frames);
+    fn draw_frame(&mut self, animation: &AnimationState, position: Position, level: &Level) {
+        let position_on_screen = position - level.position;
 
-        let src_rect = texture_wrapper.src_rect(frame);
+        let clip = match self.animations.get(&animation.clip) {
+            Some(clip) => clip,
+            None => return self.draw_placeholder(&animation.clip, position_on_screen),
+        };
+        let (texture_id, origin) = match self.texture_wrappers.get(&clip.texture_id) {
+            Some(texture_wrapper) => (texture_wrapper.texture_id.as_str(), texture_wrapper.origin),
+            None => (clip.texture_id.as_str(), (0, 0)),
+        };
+        let texture = self.texture_manager.load_or_placeholder(texture_id);
+        let frame = clip.frame_at(animation.elapsed());
 
-        let position_on_screen = position - level.position;
+        let src_rect = Rect::new(origin.0 + frame.x, origin.1 + frame.y, frame.width, frame.height);
 
         let dst_rect = Rect::new(
-            position_on_screen.x as i32,
-            position_on_screen.y as i32,
-            texture_wrapper.width,
-            texture_wrapper.height,
+            pixel(position_on_screen.x),
+            pixel(position_on_screen.y),
+            frame.width,
+            frame.height,
         );
 
         self.canvas
             .copy(&texture, src_rect, dst_rect)
             .expect("Problem copying texture");
     }
+
+    fn draw_text(&mut self, text: &str, position: Position) {
+        #[cfg(feature = "ttf")]
+        {
+            match self.draw_ttf_text(text, position) {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(e) => {
+                    println!("Error rendering text, falling back to the bitmap font: {}", e);
+                    self.fonts = None;
+                }
+            }
+        }
+
+        self.draw_bitmap_text(text, position);
+    }
+
+    fn fill_rect(&mut self, position: Position, width: u32, height: u32, color: (u8, u8, u8)) {
+        // sdl rects are never smaller than a pixel
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        // the draw color doubles as the background the scene is cleared with
+        let background = self.canvas.draw_color();
+        self.canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+        self.canvas
+            .fill_rect(Rect::new(pixel(position.x), pixel(position.y), width, height))
+            .expect("Problem filling rect");
+        self.canvas.set_draw_color(background);
+    }
+
+    fn draw_rect(&mut self, position: Position, width: u32, height: u32, color: (u8, u8, u8)) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let background = self.canvas.draw_color();
+        self.canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+        self.canvas
+            .draw_rect(Rect::new(pixel(position.x), pixel(position.y), width, height))
+            .expect("Problem drawing rect");
+        self.canvas.set_draw_color(background);
+    }
+
+    fn draw_line(&mut self, from: Position, to: Position, color: (u8, u8, u8)) {
+        let background = self.canvas.draw_color();
+        self.canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+        self.canvas
+            .draw_line(Point::new(pixel(from.x), pixel(from.y)), Point::new(pixel(to.x), pixel(to.y)))
+            .expect("Problem drawing line");
+        self.canvas.set_draw_color(background);
+    }
+
+    fn draw_chunk(&mut self, texture_id: &str, position: Position, level: &Level, layer: usize, chunk: (u32, u32)) {
+        let revision = level.chunk_revision(layer, chunk);
+        let is_baked = matches!(self.chunks.get(&(layer, chunk)), Some((baked, _)) if *baked == revision);
+        // a chunk that can not be baked is drawn tile by tile like before, the error shows when the level is prepared
+        if !is_baked && self.bake_chunk(texture_id, level, layer, chunk).is_err() {
+            return level::draw_chunk_tiles(self, texture_id, position, level, layer, chunk);
+        }
+
+        let (_, ref texture) = self.chunks[&(layer, chunk)];
+        let query = texture.query();
+        let dst_rect = Rect::new(pixel(position.x), pixel(position.y), query.width, query.height);
+        self.canvas
+            .copy(texture, None, dst_rect)
+            .expect("Problem copying texture");
+    }
+
+    // Bakes every chunk up front so that scrolling into them does not stutter
+    fn prepare_level(&mut self, level: &Level) {
+        for layer in &level.layers {
+            if let LevelLayer::Image(ref image) = *layer {
+                self.texture_manager.register(&image.texture_id, &image.source, LEVEL_TEXTURES);
+            }
+        }
+
+        self.chunks.clear();
+        let (columns, rows) = level.chunk_grid();
+        let tile_layers = (0..level.layers.len()).filter(|layer| level.tile_layer(*layer).is_some());
+        for layer in tile_layers {
+            for chunk in (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))) {
+                if let Err(e) = self.bake_chunk("tiles", level, layer, chunk) {
+                    return println!("Error baking tile chunks, drawing tile by tile: {}", e);
+                }
+            }
+        }
+    }
 }
 
-impl<'a> SDLRenderer<'a> {
-    pub fn init(engine: &SDLEngine, color: (u8, u8, u8)) -> (Canvas<Window>, TextureCreator<WindowContext>, TimerSubsystem) {
-        let timer = engine.context.timer().unwrap();
+impl<'a> SDLRenderer<'a, Window> {
+    pub fn init(engine: &SDLEngine, color: (u8, u8, u8)) -> (Canvas<Window>, TextureCreator<WindowContext>) {
         let video_subsystem = engine.context.video().unwrap();
         let (screen_width, screen_height) = SCREEN_SIZE;
         let window = video_subsystem
@@ -103,69 +253,463 @@ impl<'a> SDLRenderer<'a> {
         canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
 
         let texture_creator = canvas.texture_creator();
-        (canvas, texture_creator, timer)
+        (canvas, texture_creator)
     }
+}
 
-    pub fn new(canvas: Canvas<Window>,
-               mut texture_manager: TextureManager<'a, WindowContext>,
+impl<'a> SDLRenderer<'a, Surface<'static>> {
+    // Draws into memory, no display or GPU needed
+    pub fn init_software(width: u32, height: u32, color: (u8, u8, u8))
+                         -> Result<(Canvas<Surface<'static>>, TextureCreator<SurfaceContext<'static>>), String> {
+        let surface = Surface::new(width, height, PixelFormatEnum::RGB888)?;
+        let mut canvas = Canvas::from_surface(surface)?;
+        canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+
+        let texture_creator = canvas.texture_creator();
+        Ok((canvas, texture_creator))
+    }
+}
+
+impl<'a, T: RenderTarget> SDLRenderer<'a, T> {
+    pub fn new(canvas: Canvas<T>,
+               mut texture_manager: TextureManager<'a, T::Context>,
                mut texture_wrappers: HashMap<String, TextureWrapper>,
-               tiles_filename: &str,
-               timer: TimerSubsystem) -> Self {
-        Self::load_textures(&mut texture_manager, &mut texture_wrappers);
-        Self::load_tiles(tiles_filename, &mut texture_manager, &mut texture_wrappers);
+               tiles_filename: &str) -> Self {
+        let mut tilesets = HashMap::new();
+        let animations = Self::load(&mut texture_manager, &mut texture_wrappers, &mut tilesets, tiles_filename);
 
         Self {
             canvas,
             texture_manager,
             texture_wrappers,
-            timer,
+            animations,
+            #[cfg(feature = "ttf")]
+            fonts: None,
+            #[cfg(feature = "ttf")]
+            texts: HashMap::new(),
+            capture: None,
+            tilesets,
+            chunks: HashMap::new(),
         }
     }
 
-    fn load_textures(texture_manager: &mut TextureManager<'a, WindowContext>,
-                     texture_wrappers: &mut HashMap<String, TextureWrapper>, ) {
-        let mut textures = Vec::new();
-        parsers::game_file::parse("assets/game.xml", &mut textures, texture_wrappers);
+    // Text is rendered with this TTF font from now on instead of the bitmap sheet
+    #[cfg(feature = "ttf")]
+    pub fn use_font(&mut self, fonts: FontManager<'a>, font_id: &str) {
+        self.fonts = Some((fonts, font_id.to_string()));
+    }
 
-        for element in textures {
-            let (key, filename) = element;
-            texture_manager
-                .preload(&key, &filename)
-                .expect("Error preloading texture");
+    // Each map brings its own background color
+    pub fn set_background(&mut self, color: (u8, u8, u8)) {
+        self.canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
+    }
+
+    // Re-reads game.xml and the tileset; textures are re-created from disk on their next use
+    pub fn reload(&mut self, mut texture_wrappers: HashMap<String, TextureWrapper>, tiles_filename: &str) {
+        self.texture_manager.unload_group(LEVEL_TEXTURES);
+        self.chunks.clear();
+        self.tilesets.clear();
+        self.animations = Self::load(&mut self.texture_manager, &mut texture_wrappers, &mut self.tilesets, tiles_filename);
+        self.texture_wrappers = texture_wrappers;
+    }
+
+    // Whatever the last frame left on the canvas
+    pub fn snapshot(&self) -> Result<Snapshot, String> {
+        let (width, height) = self.canvas.output_size()?;
+        let pixels = self.canvas.read_pixels(None, PixelFormatEnum::RGB24)?;
+        Ok(Snapshot { width, height, pixels })
+    }
+
+    // The next frame drawn is also written to a PNG
+    pub fn capture_next_frame(&mut self, path: PathBuf) {
+        self.capture = Some(path);
+    }
+
+    fn save_snapshot(&self, path: &Path) -> Result<(), String> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+        self.snapshot()?.save(path)
+    }
+
+    // The tileset is copied without blending, otherwise half transparent edges would be blended
+    // into the chunk and once more when the chunk is drawn
+    fn bake_chunk(&mut self, texture_id: &str, level: &Level, layer: usize, chunk: (u32, u32)) -> Result<(), String> {
+        if !self.canvas.render_target_supported() {
+            return Err(String::from("Render targets are not supported"));
+        }
+        let texture_wrapper = self.texture_wrappers.get(texture_id).ok_or_else(|| format!("Unknown texture: {:?}", texture_id))?;
+        let tileset = self
+            .tilesets
+            .get(&texture_wrapper.texture_id)
+            .ok_or_else(|| format!("Tileset not loaded: {:?}", texture_wrapper.texture_id))?;
+        let (tile_width, tile_height) = (texture_wrapper.width, texture_wrapper.height);
+
+        let mut texture = match self.chunks.remove(&(layer, chunk)) {
+            Some((_, texture)) => texture,
+            None => self
+                .texture_manager
+                .loader()
+                .create_texture_target(PixelFormatEnum::RGBA8888, CHUNK_SIZE * tile_width, CHUNK_SIZE * tile_height)
+                .map_err(|e| e.to_string())?,
+        };
+        texture.set_blend_mode(BlendMode::Blend);
+
+        let background = self.canvas.draw_color();
+        let mut copied = Ok(());
+        self.canvas
+            .with_texture_canvas(&mut texture, |canvas| {
+                canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+                canvas.clear();
+                copied = level
+                    .chunk_tiles(layer, chunk)
+                    .try_for_each(|(column, row, tile_id)| {
+                        let dst_rect = Rect::new((column * tile_width) as i32, (row * tile_height) as i32, tile_width, tile_height);
+                        canvas.copy(tileset, texture_wrapper.src_rect(u32::from(tile_id)), dst_rect)
+                    });
+            })
+            .map_err(|e| e.to_string())?;
+        self.canvas.set_draw_color(background);
+        copied?;
+
+        self.chunks.insert((layer, chunk), (level.chunk_revision(layer, chunk), texture));
+        Ok(())
+    }
+
+    // Anything drawn with an unknown id shows up as a magenta square instead of taking the game down
+    fn draw_placeholder(&mut self, texture_id: &str, position_on_screen: Position) {
+        let texture = self.texture_manager.load_or_placeholder(texture_id);
+        let query = texture.query();
+        let dst_rect = Rect::new(pixel(position_on_screen.x), pixel(position_on_screen.y), query.width, query.height);
+
+        self.canvas
+            .copy(&texture, None, dst_rect)
+            .expect("Problem copying texture");
+    }
+
+    fn draw_bitmap_text(&mut self, text: &str, position: Position) {
+        let texture_wrapper = match self.texture_wrappers.get(FONT_TEXTURE) {
+            Some(texture_wrapper) => texture_wrapper,
+            None => return self.draw_placeholder(FONT_TEXTURE, position),
+        };
+        let texture = self.texture_manager.load_or_placeholder(texture_wrapper.texture_id.as_str());
+        let glyphs = u32::from(texture_wrapper.frames);
+
+        for (i, character) in text.chars().enumerate() {
+            let glyph = match (character as u32).checked_sub(FIRST_GLYPH) {
+                Some(glyph) if glyph < glyphs => glyph,
+                _ => u32::from(b'?') - FIRST_GLYPH,
+            };
+
+            let dst_rect = Rect::new(
+                pixel(position.x) + (i as u32 * texture_wrapper.width) as i32,
+                pixel(position.y),
+                texture_wrapper.width,
+                texture_wrapper.height,
+            );
+
+            self.canvas
+                .copy(&texture, texture_wrapper.src_rect(glyph), dst_rect)
+                .expect("Problem copying texture");
+        }
+    }
+
+    // Ok(false) when no font is in use
+    #[cfg(feature = "ttf")]
+    fn draw_ttf_text(&mut self, text: &str, position: Position) -> Result<bool, String> {
+        let (fonts, font_id) = match self.fonts {
+            Some((ref mut fonts, ref font_id)) => (fonts, font_id),
+            None => return Ok(false),
+        };
+        if text.is_empty() {
+            return Ok(true);
+        }
+
+        let key = (font_id.clone(), text.to_string(), TEXT_COLOR);
+        if !self.texts.contains_key(&key) {
+            let font = fonts.load(font_id.as_str()).map_err(|e| e.to_string())?;
+            let surface = font
+                .render(text)
+                .blended(Color::RGB(TEXT_COLOR.0, TEXT_COLOR.1, TEXT_COLOR.2))
+                .map_err(|e| e.to_string())?;
+            let texture = self
+                .texture_manager
+                .loader()
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+            self.texts.insert(key.clone(), (false, texture));
+        }
+
+        let (ref mut is_drawn, ref texture) = self.texts.get_mut(&key).unwrap();
+        *is_drawn = true;
+        let query = texture.query();
+        let dst_rect = Rect::new(pixel(position.x), pixel(position.y), query.width, query.height);
+        self.canvas.copy(texture, None, dst_rect)?;
+        Ok(true)
+    }
+
+    fn load(texture_manager: &mut TextureManager<'a, T::Context>,
+            texture_wrappers: &mut HashMap<String, TextureWrapper>,
+            tilesets: &mut HashMap<String, Texture<'a>>,
+            tiles_filename: &str) -> HashMap<String, Animation> {
+        let mut animations = HashMap::new();
+        Self::load_textures(texture_manager, texture_wrappers, &mut animations);
+        Self::load_tiles(tiles_filename, texture_manager, texture_wrappers, tilesets);
+        Self::load_default_animations(texture_wrappers, &mut animations);
+
+        println!("Textures in memory: {} bytes", texture_manager.memory_usage());
+        animations
+    }
+
+    fn load_textures(texture_manager: &mut TextureManager<'a, T::Context>,
+                     texture_wrappers: &mut HashMap<String, TextureWrapper>,
+                     animations: &mut HashMap<String, Animation>) {
+        let mut registry = AssetRegistry::new();
+        let mut atlases = HashMap::new();
+        parsers::game_file::parse("assets/game.xml", &mut registry, texture_wrappers, animations, &mut atlases);
+
+        for (key, asset) in registry.of_kind(AssetKind::Texture) {
+            texture_manager.register(key, &asset.filename, GAME_TEXTURES);
+        }
+
+        // packed textures replace the registered ones, if packing fails they are still loaded one by one
+        for (atlas_id, atlas) in &atlases {
+            if let Err(e) = Self::load_atlas(atlas_id, atlas, &registry, texture_manager, texture_wrappers) {
+                println!("Error packing atlas {:?}: {}", atlas_id, e);
+            }
+        }
+    }
+
+    fn load_atlas(atlas_id: &str,
+                  atlas: &AtlasDefinition,
+                  registry: &AssetRegistry,
+                  texture_manager: &mut TextureManager<'a, T::Context>,
+                  texture_wrappers: &mut HashMap<String, TextureWrapper>) -> Result<(), String> {
+        let mut surfaces = Vec::new();
+        for key in &atlas.textures {
+            let filename = &registry.get(key).expect("Missing atlas texture").filename;
+            println!("Packing a texture from path: {:?} into atlas: {:?}", filename, atlas_id);
+            surfaces.push((key, Surface::from_file(filename)?));
+        }
+
+        let sizes: Vec<(String, u32, u32)> = surfaces
+            .iter()
+            .map(|(key, surface)| (key.to_string(), surface.width(), surface.height()))
+            .collect();
+        let layout = atlas::pack(&sizes, atlas.max_width, atlas.padding)?;
+
+        let mut target = Surface::new(layout.width, layout.height, PixelFormatEnum::RGBA8888)?;
+        for (key, surface) in &mut surfaces {
+            let region = layout.regions[*key];
+            surface.set_blend_mode(BlendMode::None)?;
+            surface.blit(None, &mut target, Rect::new(region.x, region.y, region.width, region.height))?;
+        }
+
+        let mut texture = texture_manager
+            .loader()
+            .create_texture_from_surface(&target)
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Blend);
+        texture_manager.insert(atlas_id, texture, GAME_TEXTURES);
+
+        for (key, _) in surfaces {
+            let region = layout.regions[key];
+            texture_wrappers
+                .entry(key.to_string())
+                .or_insert_with(|| TextureWrapper::new(key.to_string(), region.width, region.height, 0, 1, 1))
+                .pack_into(atlas_id, (region.x, region.y));
         }
+        Ok(())
     }
 
     fn load_tiles(filename: &str,
-                  texture_manager: &mut TextureManager<'a, WindowContext>,
-                  texture_wrappers: &mut HashMap<String, TextureWrapper>, ) {
+                  texture_manager: &mut TextureManager<'a, T::Context>,
+                  texture_wrappers: &mut HashMap<String, TextureWrapper>,
+                  tilesets: &mut HashMap<String, Texture<'a>>) {
         let mut textures = Vec::new();
         parsers::tiles_file::parse(&format!("assets/{}", filename), &mut textures, texture_wrappers);
 
         for element in textures {
             let (key, filename) = element;
-            texture_manager
-                .preload(&key, &filename)
-                .expect("Error preloading texture");
+            match texture_manager.loader().load_texture(&filename) {
+                Ok(mut tileset) => {
+                    tileset.set_blend_mode(BlendMode::None);
+                    tilesets.insert(key.clone(), tileset);
+                }
+                Err(e) => println!("Error loading tileset {:?} for baking: {}", filename, e),
+            }
+            texture_manager.register(&key, &filename, LEVEL_TEXTURES);
+        }
+    }
+
+    // Textures without an explicit clip in game.xml keep animating their whole sheet at 10 fps
+    fn load_default_animations(texture_wrappers: &HashMap<String, TextureWrapper>,
+                               animations: &mut HashMap<String, Animation>) {
+        for (key, texture_wrapper) in texture_wrappers {
+            animations
+                .entry(key.clone())
+                .or_insert_with(|| texture_wrapper.default_animation());
         }
     }
 }
 
 impl TextureWrapper {
-    pub fn new(texture_id: String, width: u32, height: u32, padding: u8, frames: u8) -> TextureWrapper {
+    pub fn new(texture_id: String, width: u32, height: u32, padding: u8, frames: u8, columns: u8) -> TextureWrapper {
         TextureWrapper {
             texture_id,
             width,
             height,
             padding,
             frames,
+            columns,
+            origin: (0, 0),
+            regions: Vec::new(),
         }
     }
+
+    pub fn from_frames(texture_id: String, frames: &[AnimationFrame]) -> TextureWrapper {
+        let first = frames.first().expect("Sprite sheet without frames");
+        let mut texture_wrapper = TextureWrapper::new(texture_id, first.width, first.height, 0, frames.len() as u8, frames.len() as u8);
+        texture_wrapper.regions = frames
+            .iter()
+            .map(|frame| Rect::new(frame.x, frame.y, frame.width, frame.height))
+            .collect();
+        texture_wrapper
+    }
+
+    pub fn pack_into(&mut self, atlas_id: &str, origin: (i32, i32)) {
+        self.texture_id = atlas_id.to_string();
+        self.origin = origin;
+    }
+
     pub fn src_rect(&self, frame: u32) -> Rect {
+        let (origin_x, origin_y) = self.origin;
+        if !self.regions.is_empty() {
+            let region = self.regions[frame as usize % self.regions.len()];
+            return Rect::new(origin_x + region.x(), origin_y + region.y(), region.width(), region.height());
+        }
+
         let padding = u32::from(self.padding);
+        let columns = u32::from(self.columns.max(1));
         let width = self.width;
         let height = self.height;
-        let x = (frame * (width + padding) + padding) as i32;
-        let y = padding as i32;
-        Rect::new(x, y, width, height)
+        let x = ((frame % columns) * (width + padding) + padding) as i32;
+        let y = ((frame / columns) * (height + padding) + padding) as i32;
+        Rect::new(origin_x + x, origin_y + y, width, height)
+    }
+
+    pub fn default_animation(&self) -> Animation {
+        // clip frames are relative to the source image, draw_frame adds the atlas origin back
+        let (origin_x, origin_y) = self.origin;
+        let frames = (0..u32::from(self.frames.max(1)))
+            .map(|frame| {
+                let rect = self.src_rect(frame);
+                AnimationFrame::new(rect.x() - origin_x, rect.y() - origin_y, rect.width(), rect.height(), 100)
+            })
+            .collect();
+
+        Animation::new(self.texture_id.clone(), self.texture_id.clone(), PlaybackMode::Loop, frames)
+    }
+}
+
+// The golden images are what the software renderer drew when the test was written,
+// `UPDATE_GOLDEN=1 cargo test golden` writes them again after an intended change
+#[cfg(test)]
+mod tests {
+    use game::AnimationState;
+    use game::Level;
+    use game::Position;
+    use game::Renderer;
+    use helpers::parsers;
+    use sdl::sdl2::render::RenderTarget;
+    use sdl::SDLRenderer;
+    use sdl::Snapshot;
+    use sdl::TextureManager;
+    use std::env;
+    use SCREEN_SIZE;
+
+    // blending is allowed to round differently from one SDL version to the next
+    const CHANNEL_TOLERANCE: u8 = 8;
+    // up to one pixel in a thousand
+    const MAX_DIFFERING_PIXELS_PER_MILLE: usize = 1;
+
+    fn assert_matches_golden<T: RenderTarget>(renderer: &SDLRenderer<T>, golden: &str) {
+        let actual = renderer.snapshot().unwrap();
+        let path = format!("assets/golden/{}.png", golden);
+        if env::var("UPDATE_GOLDEN").is_ok() {
+            actual.save(&path).unwrap();
+            return;
+        }
+
+        let expected = Snapshot::load(&path).unwrap();
+        let differing = actual.differing_pixels(&expected, CHANNEL_TOLERANCE);
+        if differing * 1000 > (actual.width * actual.height) as usize * MAX_DIFFERING_PIXELS_PER_MILLE {
+            let actual_path = env::temp_dir().join(format!("{}.actual.png", golden));
+            actual.save(&actual_path).unwrap();
+            panic!("{} pixels differ from {}, the renderer drew {:?}", differing, path, actual_path);
+        }
+    }
+
+    #[test]
+    fn test_golden_level() {
+        //given
+        let (_, level, texture_wrappers, background_color, tiles_filename) = parsers::map_file::parse("assets/map1.tmx");
+        let (width, height) = SCREEN_SIZE;
+        let (canvas, texture_creator) = SDLRenderer::init_software(width, height, background_color).unwrap();
+        let mut renderer = SDLRenderer::new(canvas, TextureManager::new(&texture_creator), texture_wrappers, &tiles_filename);
+        // the tiles come from baked chunks, the way the game draws them
+        renderer.prepare_level(&level);
+
+        //when
+        renderer.clear_scene();
+        level.draw(&mut renderer);
+        renderer.draw_scene();
+
+        //then
+        assert_matches_golden(&renderer, "level");
+    }
+
+    #[test]
+    fn test_golden_sprites() {
+        //given
+        let (_, _, texture_wrappers, _, tiles_filename) = parsers::map_file::parse("assets/map1.tmx");
+        let (canvas, texture_creator) = SDLRenderer::init_software(320, 240, (0, 0, 0)).unwrap();
+        let mut renderer = SDLRenderer::new(canvas, TextureManager::new(&texture_creator), texture_wrappers, &tiles_filename);
+        let level = Level::new(10, 30, Vec::new());
+        let on_screen = |x: f32, y: f32| level.position + Position::new(x, y);
+
+        //when
+        renderer.clear_scene();
+        renderer.fill_rect(Position::new(0.0, 200.0), 320, 40, (40, 40, 40));
+        renderer.draw_texture("boss", on_screen(150.0, 10.0), &level);
+        renderer.draw_frame(&AnimationState::new("plane"), on_screen(20.0, 120.0), &level);
+        renderer.draw_frame(&AnimationState::new("powerUpBomb"), on_screen(100.0, 150.0), &level);
+        renderer.draw_text("PEW 42", Position::new(10.0, 210.0));
+        renderer.draw_scene();
+
+        //then
+        assert_matches_golden(&renderer, "sprites");
+    }
+
+    #[test]
+    fn test_golden_chunks_match_tiles() {
+        //given
+        let (_, mut level, texture_wrappers, background_color, tiles_filename) = parsers::map_file::parse("assets/map1.tmx");
+        let (width, height) = SCREEN_SIZE;
+        let (canvas, texture_creator) = SDLRenderer::init_software(width, height, background_color).unwrap();
+        let mut renderer = SDLRenderer::new(canvas, TextureManager::new(&texture_creator), texture_wrappers, &tiles_filename);
+        // half way into a chunk, so that chunks are cut off at the edges of the view
+        level.position.y -= 200.0;
+        renderer.prepare_level(&level);
+        renderer.clear_scene();
+        level.draw_tiles(&mut renderer);
+        let expected = renderer.snapshot().unwrap();
+
+        //when
+        renderer.clear_scene();
+        level.draw(&mut renderer);
+
+        //then
+        assert_eq!(renderer.snapshot().unwrap().differing_pixels(&expected, 0), 0);
     }
 }
@@ -0,0 +1,72 @@
+use sdl::sdl2::image::LoadSurface;
+use sdl::sdl2::image::SaveSurface;
+use sdl::sdl2::pixels::PixelFormatEnum;
+use sdl::sdl2::render::BlendMode;
+use sdl::sdl2::surface::Surface;
+use sdl::Snapshot;
+use std::path::Path;
+
+const BYTES_PER_PIXEL: usize = 3;
+
+impl Snapshot {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Snapshot, String> {
+        let mut image = Surface::from_file(path)?;
+        let (width, height) = (image.width(), image.height());
+
+        // whatever format the file comes in, it is compared as plain RGB
+        let mut rgb = Surface::new(width, height, PixelFormatEnum::RGB24)?;
+        image.set_blend_mode(BlendMode::None)?;
+        image.blit(None, &mut rgb, None)?;
+
+        let row = width as usize * BYTES_PER_PIXEL;
+        let pitch = rgb.pitch() as usize;
+        let pixels = rgb.with_lock(|bytes| bytes.chunks(pitch).take(height as usize).flat_map(|line| line[..row].to_vec()).collect());
+        Ok(Snapshot { width, height, pixels })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let mut image = Surface::new(self.width, self.height, PixelFormatEnum::RGB24)?;
+        let row = self.width as usize * BYTES_PER_PIXEL;
+        let pitch = image.pitch() as usize;
+        image.with_lock_mut(|bytes| {
+            for (line, pixels) in bytes.chunks_mut(pitch).zip(self.pixels.chunks(row)) {
+                line[..row].copy_from_slice(pixels);
+            }
+        });
+        image.save(path)
+    }
+
+    // A pixel differs when any of its channels is further off than the tolerance,
+    // snapshots of different sizes differ everywhere
+    pub fn differing_pixels(&self, other: &Snapshot, tolerance: u8) -> usize {
+        if (self.width, self.height) != (other.width, other.height) {
+            return (self.width * self.height).max(other.width * other.height) as usize;
+        }
+        self.pixels
+            .chunks(BYTES_PER_PIXEL)
+            .zip(other.pixels.chunks(BYTES_PER_PIXEL))
+            .filter(|(mine, theirs)| mine.iter().zip(theirs.iter()).any(|(a, b)| (i16::from(*a) - i16::from(*b)).abs() > i16::from(tolerance)))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl::Snapshot;
+
+    #[test]
+    fn test_counting_differing_pixels() {
+        //given
+        let expected = Snapshot { width: 2, height: 2, pixels: vec![10, 20, 30, 0, 0, 0, 255, 255, 255, 100, 100, 100] };
+        let actual = Snapshot { width: 2, height: 2, pixels: vec![12, 20, 30, 0, 0, 9, 255, 255, 255, 100, 90, 100] };
+        let smaller = Snapshot { width: 1, height: 2, pixels: vec![10, 20, 30, 255, 255, 255] };
+
+        //when
+        let differing = actual.differing_pixels(&expected, 8);
+
+        //then
+        assert_eq!(differing, 2);
+        assert_eq!(actual.differing_pixels(&expected, 10), 0);
+        assert_eq!(smaller.differing_pixels(&expected, 8), 4);
+    }
+}